@@ -0,0 +1,176 @@
+//! Optional API-key authentication
+//!
+//! The API key opens the transformation endpoints (OpenAI-, Anthropic- and
+//! Ollama-compatible, `/v1/transform`), the admin key opens rule management,
+//! logs and history and also the transformation endpoints. Without an admin
+//! key, the API key opens everything; without either, the server is open.
+//! Clients send a key as `Authorization: Bearer <key>` or `x-api-key: <key>`.
+
+use crate::error::ApiError;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Header for the key as sent by Anthropic clients
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// What a request needs access to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Transformation endpoints
+    Api,
+    /// Rule management, logs and history
+    Admin,
+}
+
+/// The configured keys
+#[derive(Debug, Clone, Default)]
+pub struct Auth {
+    api_key: Option<String>,
+    admin_key: Option<String>,
+}
+
+impl Auth {
+    /// Empty keys count as unset
+    pub fn new(api_key: Option<String>, admin_key: Option<String>) -> Self {
+        Self {
+            api_key: api_key.filter(|key| !key.is_empty()),
+            admin_key: admin_key.filter(|key| !key.is_empty()),
+        }
+    }
+
+    /// Whether any endpoint requires a key
+    pub fn is_enabled(&self) -> bool {
+        self.api_key.is_some() || self.admin_key.is_some()
+    }
+
+    /// The key for admin access, if one is required
+    pub fn admin_key(&self) -> Option<&str> {
+        self.admin_key.as_deref().or(self.api_key.as_deref())
+    }
+
+    /// Whether `key` grants the access
+    pub fn allows(&self, access: Access, key: Option<&str>) -> bool {
+        let keys: Vec<&str> = match access {
+            Access::Api if self.api_key.is_none() => Vec::new(),
+            Access::Api => [&self.api_key, &self.admin_key]
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect(),
+            Access::Admin => self.admin_key().into_iter().collect(),
+        };
+
+        keys.is_empty() || key.is_some_and(|key| keys.iter().any(|k| same_key(k, key)))
+    }
+}
+
+/// Compare keys in constant time (by their hashes, which have the same length)
+fn same_key(expected: &str, given: &str) -> bool {
+    let (expected, given) = (Sha256::digest(expected), Sha256::digest(given));
+    expected
+        .iter()
+        .zip(given.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// The key sent with a request, from the bearer token or the `x-api-key` header
+fn request_key(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer
+        .or_else(|| headers.get(API_KEY_HEADER)?.to_str().ok())
+        .map(str::trim)
+}
+
+/// Middleware for the transformation endpoints
+pub async fn require_api(State(auth): State<Arc<Auth>>, request: Request, next: Next) -> Response {
+    authorize(&auth, Access::Api, request, next).await
+}
+
+/// Middleware for rule management, logs and history
+pub async fn require_admin(
+    State(auth): State<Arc<Auth>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    authorize(&auth, Access::Admin, request, next).await
+}
+
+async fn authorize(auth: &Auth, access: Access, request: Request, next: Next) -> Response {
+    let key = request_key(request.headers());
+    if auth.allows(access, key) {
+        return next.run(request).await;
+    }
+
+    let message = match (key, access) {
+        (None, _) => "Missing API key, send it as `Authorization: Bearer <key>`",
+        (Some(_), Access::Api) => "Invalid API key",
+        (Some(_), Access::Admin) => "Invalid API key, this endpoint requires the admin key",
+    };
+    tracing::warn!(
+        "Rejected {} {}: {}",
+        request.method(),
+        request.uri().path(),
+        message
+    );
+    (
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        ApiError::new(StatusCode::UNAUTHORIZED, message).with_code("invalid_api_key"),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth(api_key: Option<&str>, admin_key: Option<&str>) -> Auth {
+        Auth::new(api_key.map(String::from), admin_key.map(String::from))
+    }
+
+    #[test]
+    fn test_keys() {
+        // Without keys everything is open
+        let open = auth(None, Some(""));
+        assert!(!open.is_enabled());
+        assert!(open.allows(Access::Admin, None));
+
+        // Separate keys: the admin key opens everything
+        let both = auth(Some("read"), Some("admin"));
+        assert!(both.allows(Access::Api, Some("read")));
+        assert!(both.allows(Access::Api, Some("admin")));
+        assert!(both.allows(Access::Admin, Some("admin")));
+        assert!(!both.allows(Access::Admin, Some("read")));
+        assert!(!both.allows(Access::Api, Some("wrong")));
+        assert!(!both.allows(Access::Api, None));
+
+        // The API key alone opens everything
+        let api_only = auth(Some("read"), None);
+        assert!(api_only.allows(Access::Admin, Some("read")));
+        assert!(!api_only.allows(Access::Admin, None));
+
+        // The admin key alone leaves the transformation endpoints open
+        let admin_only = auth(None, Some("admin"));
+        assert!(admin_only.allows(Access::Api, None));
+        assert!(!admin_only.allows(Access::Admin, None));
+    }
+
+    #[test]
+    fn test_request_key() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_key(&headers), None);
+
+        headers.insert(API_KEY_HEADER, "from-header".parse().unwrap());
+        assert_eq!(request_key(&headers), Some("from-header"));
+
+        headers.insert(header::AUTHORIZATION, "Bearer from-bearer".parse().unwrap());
+        assert_eq!(request_key(&headers), Some("from-bearer"));
+    }
+}
@@ -0,0 +1,221 @@
+//! Inputs of the `transform` command: files, directories and JSONL corpora
+//!
+//! Every input becomes one or more items. A whole file (or all of stdin) is a
+//! single item whose trailing newline is kept aside and written back after the
+//! output, so multi-line input comes out exactly as the rules leave it.
+
+use anyhow::Context;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A text to transform and where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// File the text came from (None for stdin or an argument)
+    pub file: Option<PathBuf>,
+    /// Path of the file relative to the input it was found in
+    pub relative: Option<PathBuf>,
+    pub text: String,
+    /// Written after the output: the stripped trailing newline or a separator
+    pub ending: String,
+    /// JSONL record the text came from, as an object; the output is added to it
+    pub object: Option<Map<String, Value>>,
+}
+
+impl Item {
+    /// An item for text given as a whole, keeping its trailing newline aside
+    pub fn whole(text: &str) -> Self {
+        let (text, ending) = split_trailing_newline(text);
+        Self {
+            file: None,
+            relative: None,
+            text: text.to_string(),
+            ending: ending.to_string(),
+            object: None,
+        }
+    }
+
+    /// An item for one record of a delimited stream, followed by `separator`
+    pub fn record(text: &str, separator: &str) -> Self {
+        Self {
+            file: None,
+            relative: None,
+            text: text.to_string(),
+            ending: separator.to_string(),
+            object: None,
+        }
+    }
+
+    /// The item as a JSON object with its output: the JSONL record with an
+    /// `output` field, or the file, text and output
+    pub fn to_json(&self, output: &str) -> Value {
+        let mut object = match &self.object {
+            Some(object) => object.clone(),
+            None => {
+                let mut object = Map::new();
+                if let Some(file) = &self.file {
+                    object.insert("file".to_string(), file.display().to_string().into());
+                }
+                object.insert("text".to_string(), self.text.clone().into());
+                object
+            },
+        };
+        object.insert("output".to_string(), output.into());
+        Value::Object(object)
+    }
+}
+
+/// Split one trailing `\n` or `\r\n` off the text
+fn split_trailing_newline(text: &str) -> (&str, &str) {
+    for newline in ["\r\n", "\n"] {
+        if let Some(stripped) = text.strip_suffix(newline) {
+            return (stripped, newline);
+        }
+    }
+    (text, "")
+}
+
+/// Whether a file is read as a JSONL corpus
+pub fn is_jsonl(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "jsonl")
+}
+
+/// Items of a JSONL corpus: one JSON string or object with a string `text`
+/// field per line; blank lines are skipped. `file` is the corpus file with its
+/// path relative to its input.
+pub fn parse_jsonl(content: &str, file: Option<(&Path, &Path)>) -> anyhow::Result<Vec<Item>> {
+    let source = file.map_or("stdin".to_string(), |(f, _)| f.display().to_string());
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let value: Value = serde_json::from_str(line)
+                .with_context(|| format!("{}:{}: invalid JSON", source, index + 1))?;
+            let (text, object) = match value {
+                Value::String(text) => {
+                    let mut object = Map::new();
+                    object.insert("text".to_string(), text.clone().into());
+                    (text, object)
+                },
+                Value::Object(object) => match object.get("text") {
+                    Some(Value::String(text)) => (text.clone(), object),
+                    _ => anyhow::bail!("{}:{}: object without a text field", source, index + 1),
+                },
+                _ => anyhow::bail!(
+                    "{}:{}: expected a string or an object with a text field",
+                    source,
+                    index + 1
+                ),
+            };
+            Ok(Item {
+                file: file.map(|(f, _)| f.to_path_buf()),
+                relative: file.map(|(_, r)| r.to_path_buf()),
+                text,
+                ending: "\n".to_string(),
+                object: Some(object),
+            })
+        })
+        .collect()
+}
+
+/// Files of the inputs as (file, path relative to its input); directories
+/// are walked recursively in name order, skipping hidden entries
+pub fn collect_files(inputs: &[PathBuf]) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            walk(input, input, &mut files)?;
+        } else if input.is_file() {
+            let name = input.file_name().map(PathBuf::from).unwrap_or_default();
+            files.push((input.clone(), name));
+        } else {
+            anyhow::bail!("{} is not a file or directory", input.display());
+        }
+    }
+    Ok(files)
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<(PathBuf, PathBuf)>) -> anyhow::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    for path in entries {
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            walk(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            files.push((path, relative));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whole_text_keeps_trailing_newline() {
+        let item = Item::whole("line one\nline two\r\n");
+        assert_eq!(item.text, "line one\nline two");
+        assert_eq!(item.ending, "\r\n");
+
+        let item = Item::whole("no newline");
+        assert_eq!(item.text, "no newline");
+        assert_eq!(item.ending, "");
+
+        let item = Item::record("a\n", "\0");
+        assert_eq!(item.text, "a\n");
+        assert_eq!(item.ending, "\0");
+    }
+
+    #[test]
+    fn test_parse_jsonl() {
+        let items = parse_jsonl("\"a slash b\"\n\n{\"id\": 7, \"text\": \"c\"}\n", None).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].text, "a slash b");
+        assert_eq!(
+            items[0].to_json("a / b"),
+            serde_json::json!({"text": "a slash b", "output": "a / b"})
+        );
+        assert_eq!(
+            items[1].to_json("C"),
+            serde_json::json!({"id": 7, "text": "c", "output": "C"})
+        );
+
+        let error = parse_jsonl("\"a\"\n{\"id\": 1}\n", None).unwrap_err();
+        assert_eq!(error.to_string(), "stdin:2: object without a text field");
+        assert!(parse_jsonl("not json", None).is_err());
+    }
+
+    #[test]
+    fn test_collect_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("b.txt"), "b").unwrap();
+        fs::write(dir.path().join("sub/a.txt"), "a").unwrap();
+        fs::write(dir.path().join(".hidden"), "h").unwrap();
+
+        let files = collect_files(&[dir.path().to_path_buf()]).unwrap();
+        let relative: Vec<&Path> = files.iter().map(|(_, r)| r.as_path()).collect();
+        assert_eq!(relative, [Path::new("b.txt"), Path::new("sub/a.txt")]);
+
+        let file = dir.path().join("b.txt");
+        let files = collect_files(std::slice::from_ref(&file)).unwrap();
+        assert_eq!(files, [(file, PathBuf::from("b.txt"))]);
+
+        assert!(collect_files(&[dir.path().join("missing")]).is_err());
+    }
+}
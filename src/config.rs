@@ -1,6 +1,14 @@
 //! Configuration management
 
+use crate::auth::Auth;
+use crate::error::AppError;
+use crate::extract::Extraction;
+use crate::history::HistoryOptions;
+use crate::privacy::{HASH_KEY_FILE, Privacy, PrivacyMode};
+use crate::rules::{EngineOptions, RuleSelector};
+use crate::upstream::UpstreamOptions;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -64,7 +72,7 @@ pub struct Config {
     /// - "rules.json" (single file)
     /// - ["rules.json", "custom-rules.json"] (multiple files)
     /// - "rules/*.json" (glob pattern)
-    /// - "rules/" (directory - loads all .json files)
+    /// - "rules/" (directory - loads all .json, .jsonc, .json5, .yaml, .yml and .toml files)
     #[serde(default = "default_rules_paths", alias = "rules_path")]
     pub rules_paths: RulesPaths,
 
@@ -77,6 +85,171 @@ pub struct Config {
     /// Only enable this if you trust all rule sources.
     #[serde(default)]
     pub enable_shell_rules: bool,
+
+    /// Overrides file patching shipped rules by id (JSON, YAML or TOML)
+    /// Kept apart from the rule packs so local changes survive pack updates.
+    /// Toggling a pack rule is saved here when set.
+    #[serde(default, alias = "overrides")]
+    pub overrides_path: Option<String>,
+
+    /// Load rules whose ids are already used by other rules, with a warning,
+    /// instead of failing (lenient mode). Such rules are addressed as `pack:id`.
+    #[serde(default)]
+    pub allow_duplicate_ids: bool,
+
+    /// Number of requests kept in the transformation log (`/v1/logs`)
+    #[serde(default = "default_max_log_entries")]
+    pub max_log_entries: usize,
+
+    /// On-disk transformation history (disabled by default)
+    #[serde(default)]
+    pub history: HistoryConfig,
+
+    /// What of the dictated text is logged (log, tracing output and history)
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+
+    /// Extraction of the transcript from prompts that embed it in instructions
+    #[serde(default)]
+    pub extraction: ExtractionConfig,
+
+    /// Upstream LLM stage after the rules (disabled by default)
+    #[serde(default)]
+    pub upstream: UpstreamConfig,
+
+    /// Named rule selections for `/v1/transform` requests
+    #[serde(default)]
+    pub profiles: BTreeMap<String, RuleSelector>,
+
+    /// Key required by the transformation endpoints (unset leaves them open)
+    /// Sent as `Authorization: Bearer <key>` or `x-api-key: <key>`.
+    #[serde(default)]
+    pub api_key: Option<String>,
+
+    /// Key required by rule management, logs and history; it also opens the
+    /// transformation endpoints (default: the API key)
+    #[serde(default)]
+    pub admin_key: Option<String>,
+}
+
+/// Settings of the upstream LLM stage
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpstreamConfig {
+    /// Base URL of an OpenAI-compatible API, e.g. `http://127.0.0.1:8080/v1` (unset disables the stage)
+    #[serde(default)]
+    pub base_url: Option<String>,
+
+    /// API key sent as a bearer token
+    #[serde(default)]
+    pub api_key: Option<String>,
+
+    /// Model to request (default: the model of the incoming request)
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Timeout of the upstream request in milliseconds
+    #[serde(default = "default_upstream_timeout_ms")]
+    pub timeout_ms: u64,
+
+    /// Rules files applied to the upstream answer (files, directories, or glob patterns)
+    #[serde(default)]
+    pub post_rules_paths: Vec<String>,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            base_url: None,
+            api_key: None,
+            model: None,
+            timeout_ms: default_upstream_timeout_ms(),
+            post_rules_paths: Vec::new(),
+        }
+    }
+}
+
+/// Settings of the transcript extraction
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtractionConfig {
+    /// Only look for the transcript in the user message (default true)
+    #[serde(default = "default_ignore_system_message")]
+    pub ignore_system_message: bool,
+
+    /// Extractors, tried in order; the first match wins
+    #[serde(default)]
+    pub extractors: Vec<ExtractorConfig>,
+}
+
+impl Default for ExtractionConfig {
+    fn default() -> Self {
+        Self {
+            ignore_system_message: default_ignore_system_message(),
+            extractors: Vec::new(),
+        }
+    }
+}
+
+/// An extractor: a regex, or start and end markers around the transcript
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtractorConfig {
+    /// Name shown in the transformation log (default: `extractor-<n>`)
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Regex; the transcript is its `transcript` group, its first group or the whole match
+    #[serde(default)]
+    pub pattern: Option<String>,
+
+    /// Marker after which the transcript starts
+    #[serde(default)]
+    pub start: Option<String>,
+
+    /// Marker at which the transcript ends (default: the end of the message)
+    #[serde(default)]
+    pub end: Option<String>,
+}
+
+/// Settings of what gets logged about requests
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PrivacyConfig {
+    /// full, hashed, redacted or metadata
+    #[serde(default)]
+    pub mode: PrivacyMode,
+
+    /// Regex patterns replaced in redacted mode (default: passwords, emails, long numbers)
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+}
+
+/// Settings of the on-disk transformation history
+#[derive(Debug, Clone, Deserialize)]
+pub struct HistoryConfig {
+    /// Record every request of the server to disk
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Directory of the daily history files (default: ~/.handy-local-rules/history/)
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Days to keep (0 keeps everything)
+    #[serde(default = "default_history_max_age_days")]
+    pub max_age_days: u64,
+
+    /// Total size in megabytes; the oldest files are removed beyond it (0 means unlimited)
+    #[serde(default = "default_history_max_size_mb")]
+    pub max_size_mb: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            max_age_days: default_history_max_age_days(),
+            max_size_mb: default_history_max_size_mb(),
+        }
+    }
 }
 
 /// Rules paths can be a single string or an array of strings
@@ -119,6 +292,26 @@ fn default_log_level() -> String {
     "info".to_string()
 }
 
+fn default_max_log_entries() -> usize {
+    1000
+}
+
+fn default_ignore_system_message() -> bool {
+    true
+}
+
+fn default_upstream_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_history_max_age_days() -> u64 {
+    30
+}
+
+fn default_history_max_size_mb() -> u64 {
+    100
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -127,11 +320,99 @@ impl Default for Config {
             rules_paths: default_rules_paths(),
             log_level: default_log_level(),
             enable_shell_rules: false,
+            overrides_path: None,
+            allow_duplicate_ids: false,
+            max_log_entries: default_max_log_entries(),
+            history: HistoryConfig::default(),
+            privacy: PrivacyConfig::default(),
+            extraction: ExtractionConfig::default(),
+            upstream: UpstreamConfig::default(),
+            profiles: BTreeMap::new(),
+            api_key: None,
+            admin_key: None,
         }
     }
 }
 
 impl Config {
+    /// Options for the rule engine
+    /// Fails if a redaction pattern is not a valid regex
+    pub fn engine_options(&self) -> Result<EngineOptions, AppError> {
+        Ok(EngineOptions {
+            enable_shell_rules: self.enable_shell_rules,
+            overrides_path: self.overrides_path.clone(),
+            allow_duplicate_ids: self.allow_duplicate_ids,
+            max_log_entries: self.max_log_entries,
+            privacy: self.privacy()?,
+            profiles: self.profiles.clone(),
+        })
+    }
+
+    /// Privacy mode with its redaction patterns; in hashed mode with the key
+    /// stored in the history directory (created on first use)
+    fn privacy(&self) -> Result<Privacy, AppError> {
+        let privacy = Privacy::new(self.privacy.mode, &self.privacy.redact_patterns)?;
+        if self.privacy.mode != PrivacyMode::Hashed {
+            return Ok(privacy);
+        }
+
+        let dir = self.history_dir().ok_or_else(|| {
+            AppError::IoError(std::io::Error::other(
+                "no home directory for the privacy hash key, set history.path",
+            ))
+        })?;
+        privacy.with_hash_key(&dir.join(HASH_KEY_FILE))
+    }
+
+    /// Directory of the history (default: ~/.handy-local-rules/history/)
+    fn history_dir(&self) -> Option<PathBuf> {
+        match &self.history.path {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(get_config_dir()?.join("history")),
+        }
+    }
+
+    /// Compiled transcript extractors
+    /// Fails if an extractor has an invalid pattern or neither a pattern nor a start marker
+    pub fn extraction(&self) -> Result<Extraction, AppError> {
+        Extraction::new(&self.extraction)
+    }
+
+    /// Options for the on-disk history, if it is enabled
+    pub fn history_options(&self) -> Option<HistoryOptions> {
+        if !self.history.enabled {
+            return None;
+        }
+
+        Some(HistoryOptions {
+            dir: self.history_dir()?,
+            max_age_days: self.history.max_age_days,
+            max_size_bytes: self.history.max_size_mb * 1024 * 1024,
+        })
+    }
+
+    /// Options for the upstream stage, if a base URL is set
+    pub fn upstream_options(&self) -> Option<UpstreamOptions> {
+        let base_url = self
+            .upstream
+            .base_url
+            .clone()
+            .filter(|url| !url.is_empty())?;
+
+        Some(UpstreamOptions {
+            base_url,
+            api_key: self.upstream.api_key.clone(),
+            model: self.upstream.model.clone(),
+            timeout: std::time::Duration::from_millis(self.upstream.timeout_ms),
+            post_rules_paths: self.upstream.post_rules_paths.clone(),
+        })
+    }
+
+    /// API keys of the server
+    pub fn auth(&self) -> Auth {
+        Auth::new(self.api_key.clone(), self.admin_key.clone())
+    }
+
     /// Load configuration from a JSON file
     pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
         let content = fs::read_to_string(path.as_ref())?;
@@ -185,6 +466,84 @@ mod tests {
         let config = Config::load(file.path()).unwrap();
         assert_eq!(config.port, 9000);
         assert_eq!(config.get_rules_paths(), vec!["my-rules.json"]);
+        assert_eq!(config.max_log_entries, 1000);
+        assert!(config.history_options().is_none());
+    }
+
+    #[test]
+    fn test_load_history_config() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"history": {{"enabled": true, "path": "/tmp/history", "max_size_mb": 2}}}}"#
+        )
+        .unwrap();
+
+        let options = Config::load(file.path())
+            .unwrap()
+            .history_options()
+            .unwrap();
+        assert_eq!(options.dir, PathBuf::from("/tmp/history"));
+        assert_eq!(options.max_age_days, 30);
+        assert_eq!(options.max_size_bytes, 2 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_load_privacy_config() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"privacy": {{"mode": "redacted", "redact_patterns": ["secret \\w+"]}}}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(config.privacy.mode, PrivacyMode::Redacted);
+        let options = config.engine_options().unwrap();
+        assert_eq!(options.privacy.text("my secret plan"), "my [redacted]");
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"privacy": {{"mode": "everything"}}}}"#).unwrap();
+        assert!(Config::load(file.path()).is_err());
+    }
+
+    #[test]
+    fn test_load_extraction_config() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"extraction": {{"extractors": [{{"name": "handy", "start": "<<", "end": ">>"}}]}}}}"#
+        )
+        .unwrap();
+
+        let config = Config::load(file.path()).unwrap();
+        assert!(config.extraction.ignore_system_message);
+        let extracted = config
+            .extraction()
+            .unwrap()
+            .extract("Fix: <<a slash b>>", &[]);
+        assert_eq!(extracted.text, "a slash b");
+        assert_eq!(extracted.extractor.as_deref(), Some("handy"));
+    }
+
+    #[test]
+    fn test_load_upstream_config() {
+        assert!(Config::default().upstream_options().is_none());
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"upstream": {{"base_url": "http://127.0.0.1:8080/v1", "post_rules_paths": ["post/"]}}}}"#
+        )
+        .unwrap();
+
+        let options = Config::load(file.path())
+            .unwrap()
+            .upstream_options()
+            .unwrap();
+        assert_eq!(options.base_url, "http://127.0.0.1:8080/v1");
+        assert_eq!(options.timeout, std::time::Duration::from_secs(10));
+        assert_eq!(options.post_rules_paths, vec!["post/"]);
     }
 
     #[test]
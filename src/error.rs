@@ -1,13 +1,46 @@
 //! Error types for the application
 
+use axum::body::Bytes;
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts, Query, Request};
+use axum::http::request::Parts;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, async_trait};
+use serde::de::DeserializeOwned;
 use thiserror::Error;
 
+use crate::models::{
+    AnthropicErrorDetail, AnthropicErrorResponse, ErrorDetail, ErrorResponse, OllamaErrorResponse,
+};
+
 /// Application error types
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Failed to load rules: {0}")]
     RulesLoadError(String),
 
+    #[error("Invalid rule: {0}")]
+    InvalidRule(String),
+
+    #[error("Rule already exists: {0}")]
+    RuleExists(String),
+
+    #[error("Ambiguous rule id: {0}, use pack:id")]
+    AmbiguousRuleId(String),
+
+    #[error("Invalid extractor: {0}")]
+    InvalidExtractor(String),
+
+    #[error("Upstream error: {0}")]
+    UpstreamError(String),
+
+    #[error("Invalid history query: {0}")]
+    InvalidHistoryQuery(String),
+
+    #[error("Transform failed: {0}")]
+    TransformFailed(String),
+
     #[error("Invalid regex pattern: {0}")]
     InvalidRegex(#[from] regex::Error),
 
@@ -20,3 +53,354 @@ pub enum AppError {
     #[error("File watcher error: {0}")]
     WatcherError(#[from] notify::Error),
 }
+
+/// Error response of the HTTP API, rendered as an OpenAI-style body:
+/// `{"error": {"message": ..., "type": ..., "param": null, "code": ...}}`
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+    /// Machine-readable error code, e.g. `rule_not_found`
+    pub code: Option<&'static str>,
+}
+
+impl ApiError {
+    /// Create an error with a status code and message
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            code: None,
+        }
+    }
+
+    /// Set the error code
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// 400 Bad Request
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    /// 404 Not Found
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message).with_code("not_found")
+    }
+
+    /// 422 Unprocessable Entity
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, message)
+    }
+
+    /// OpenAI error type for the status code
+    fn error_type(&self) -> &'static str {
+        match self.status {
+            StatusCode::UNAUTHORIZED => "authentication_error",
+            StatusCode::FORBIDDEN => "permission_error",
+            status if status.is_server_error() => "server_error",
+            _ => "invalid_request_error",
+        }
+    }
+}
+
+impl From<AppError> for ApiError {
+    fn from(e: AppError) -> Self {
+        let (status, code) = match e {
+            AppError::InvalidRule(_) | AppError::InvalidRegex(_) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "invalid_rule")
+            },
+            AppError::RuleExists(_) => (StatusCode::CONFLICT, "rule_exists"),
+            AppError::AmbiguousRuleId(_) => (StatusCode::CONFLICT, "ambiguous_rule_id"),
+            AppError::InvalidHistoryQuery(_) => (StatusCode::BAD_REQUEST, "invalid_history_query"),
+            _ => {
+                tracing::error!("{}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            },
+        };
+        Self::new(status, e.to_string()).with_code(code)
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text()).with_code("invalid_json")
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), rejection.body_text()).with_code("invalid_query")
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorResponse {
+            error: ErrorDetail {
+                error_type: self.error_type().to_string(),
+                message: self.message,
+                param: None,
+                code: self.code.map(String::from),
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Error of the Anthropic-compatible endpoint, rendered as
+/// `{"type": "error", "error": {"type": ..., "message": ...}}`
+#[derive(Debug)]
+pub struct AnthropicApiError(pub ApiError);
+
+impl AnthropicApiError {
+    /// Anthropic error type for the status code
+    fn error_type(&self) -> &'static str {
+        match self.0.status {
+            StatusCode::UNAUTHORIZED => "authentication_error",
+            StatusCode::FORBIDDEN => "permission_error",
+            StatusCode::NOT_FOUND => "not_found_error",
+            StatusCode::PAYLOAD_TOO_LARGE => "request_too_large",
+            StatusCode::TOO_MANY_REQUESTS => "rate_limit_error",
+            status if status.is_server_error() => "api_error",
+            _ => "invalid_request_error",
+        }
+    }
+}
+
+impl From<ApiError> for AnthropicApiError {
+    fn from(e: ApiError) -> Self {
+        Self(e)
+    }
+}
+
+impl IntoResponse for AnthropicApiError {
+    fn into_response(self) -> Response {
+        let body = AnthropicErrorResponse {
+            response_type: "error".to_string(),
+            error: AnthropicErrorDetail {
+                error_type: self.error_type().to_string(),
+                message: self.0.message,
+            },
+        };
+        (self.0.status, Json(body)).into_response()
+    }
+}
+
+/// Error of the Ollama-compatible endpoints, rendered as `{"error": ...}`
+#[derive(Debug)]
+pub struct OllamaApiError(pub ApiError);
+
+impl From<ApiError> for OllamaApiError {
+    fn from(e: ApiError) -> Self {
+        Self(e)
+    }
+}
+
+impl IntoResponse for OllamaApiError {
+    fn into_response(self) -> Response {
+        let body = OllamaErrorResponse {
+            error: self.0.message,
+        };
+        (self.0.status, Json(body)).into_response()
+    }
+}
+
+/// JSON body extractor that rejects invalid bodies with an [`ApiError`]
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(Self(value))
+    }
+}
+
+/// JSON body extractor that does not require a JSON content type, for clients
+/// such as Ollama's that send none
+pub struct LenientJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for LenientJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| ApiError::new(e.status(), e.body_text()))?;
+        serde_json::from_slice(&body).map(Self).map_err(|e| {
+            ApiError::bad_request(format!("Failed to parse the request body as JSON: {}", e))
+                .with_code("invalid_json")
+        })
+    }
+}
+
+/// Body extractor that takes a `text/plain` body as the text of the request
+/// and any other body as JSON
+pub struct TextOrJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for TextOrJson<T>
+where
+    T: DeserializeOwned + From<String>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_text = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/plain"));
+
+        if is_text {
+            let text = String::from_request(req, state)
+                .await
+                .map_err(|e| ApiError::new(e.status(), e.body_text()))?;
+            return Ok(Self(T::from(text)));
+        }
+
+        let ApiJson(value) = ApiJson::<T>::from_request(req, state).await?;
+        Ok(Self(value))
+    }
+}
+
+/// Query string extractor that rejects invalid parameters with an [`ApiError`]
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    Query<T>: FromRequestParts<S, Rejection = QueryRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        Ok(Self(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body(error: ApiError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_openai_error_body() {
+        let (status, json) =
+            body(ApiError::bad_request("No content").with_code("missing_content")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            json,
+            serde_json::json!({"error": {
+                "message": "No content",
+                "type": "invalid_request_error",
+                "param": null,
+                "code": "missing_content",
+            }})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_error_body() {
+        let response = AnthropicApiError::from(ApiError::bad_request("No content")).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap(),
+            serde_json::json!({"type": "error", "error": {
+                "type": "invalid_request_error",
+                "message": "No content",
+            }})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ollama_error_body() {
+        let response = OllamaApiError::from(ApiError::bad_request("No content")).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap(),
+            serde_json::json!({"error": "No content"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_app_error_status() {
+        let (status, json) = body(AppError::RuleExists("slash".to_string()).into()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["error"]["code"], "rule_exists");
+
+        let io = std::io::Error::other("disk full");
+        let (status, json) = body(AppError::IoError(io).into()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(json["error"]["type"], "server_error");
+    }
+
+    #[tokio::test]
+    async fn test_text_or_json() {
+        #[derive(serde::Deserialize)]
+        struct Text {
+            text: String,
+        }
+        impl From<String> for Text {
+            fn from(text: String) -> Self {
+                Self { text }
+            }
+        }
+
+        let request = |content_type: &str, body: &'static str| {
+            Request::builder()
+                .header(header::CONTENT_TYPE, content_type)
+                .body(axum::body::Body::from(body))
+                .unwrap()
+        };
+
+        let TextOrJson(plain) =
+            TextOrJson::<Text>::from_request(request("text/plain; charset=utf-8", "{\"a\"}"), &())
+                .await
+                .unwrap();
+        assert_eq!(plain.text, "{\"a\"}");
+
+        let TextOrJson(json) =
+            TextOrJson::<Text>::from_request(request("application/json", r#"{"text": "a"}"#), &())
+                .await
+                .unwrap();
+        assert_eq!(json.text, "a");
+
+        let Err(error) = TextOrJson::<Text>::from_request(request("text/html", "a"), &()).await
+        else {
+            panic!("expected a rejection");
+        };
+        assert_eq!(error.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
@@ -0,0 +1,220 @@
+//! Transcript extraction from prompt templates
+//!
+//! Handy can send a post-processing prompt in which the transcript is embedded
+//! in instructions for an LLM. Extractors pull the transcript out of such a
+//! prompt so that only the dictated text is run through the rules. Requests
+//! that no extractor matches are processed as a whole, as before.
+
+use crate::config::{ExtractionConfig, ExtractorConfig};
+use crate::error::AppError;
+use regex::Regex;
+
+/// Name of the capture group holding the transcript in extractor patterns
+const TRANSCRIPT_GROUP: &str = "transcript";
+
+/// Text to transform, and the extractor that pulled it out of the prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extracted {
+    pub text: String,
+    /// Name of the matching extractor, None if the whole message is used
+    pub extractor: Option<String>,
+}
+
+/// How an extractor finds the transcript
+#[derive(Debug, Clone)]
+enum Matcher {
+    /// The `transcript` group, the first group or the whole match of a regex
+    Pattern(Regex),
+    /// The text between a start marker and the next end marker (or the end of the text)
+    Markers { start: String, end: Option<String> },
+}
+
+/// A named, compiled extractor
+#[derive(Debug, Clone)]
+struct Extractor {
+    name: String,
+    matcher: Matcher,
+}
+
+impl Extractor {
+    fn new(index: usize, config: &ExtractorConfig) -> Result<Self, AppError> {
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("extractor-{}", index + 1));
+
+        let matcher = match (&config.pattern, &config.start) {
+            (Some(pattern), None) => Matcher::Pattern(Regex::new(pattern)?),
+            (None, Some(start)) if !start.is_empty() => Matcher::Markers {
+                start: start.clone(),
+                end: config.end.clone().filter(|end| !end.is_empty()),
+            },
+            _ => {
+                return Err(AppError::InvalidExtractor(format!(
+                    "'{}' needs either a pattern or a start marker",
+                    name
+                )));
+            },
+        };
+
+        Ok(Self { name, matcher })
+    }
+
+    /// The trimmed transcript, None if the extractor does not match or
+    /// matches only whitespace
+    fn extract<'a>(&self, text: &'a str) -> Option<&'a str> {
+        let transcript = match &self.matcher {
+            Matcher::Pattern(regex) => {
+                let captures = regex.captures(text)?;
+                captures
+                    .name(TRANSCRIPT_GROUP)
+                    .or_else(|| captures.get(1))
+                    .or_else(|| captures.get(0))?
+                    .as_str()
+            },
+            Matcher::Markers { start, end } => {
+                let (_, rest) = text.split_once(start.as_str())?;
+                match end {
+                    Some(end) => rest.split_once(end.as_str())?.0,
+                    None => rest,
+                }
+            },
+        };
+
+        let transcript = transcript.trim();
+        (!transcript.is_empty()).then_some(transcript)
+    }
+}
+
+/// The configured extractors, tried in order
+#[derive(Debug, Clone)]
+pub struct Extraction {
+    extractors: Vec<Extractor>,
+    /// Only look for the transcript in the user message
+    ignore_system_message: bool,
+}
+
+impl Extraction {
+    /// Compile the extractors; fails on an invalid pattern or an extractor
+    /// without a pattern or start marker
+    pub fn new(config: &ExtractionConfig) -> Result<Self, AppError> {
+        let extractors = config
+            .extractors
+            .iter()
+            .enumerate()
+            .map(|(index, extractor)| Extractor::new(index, extractor))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            extractors,
+            ignore_system_message: config.ignore_system_message,
+        })
+    }
+
+    /// The text to transform for a request
+    ///
+    /// The extractors are tried on the user message, then (unless system
+    /// messages are ignored) on the system messages. The first match wins;
+    /// without one the whole user message is used.
+    pub fn extract(&self, user: &str, system: &[String]) -> Extracted {
+        let system = if self.ignore_system_message {
+            &[]
+        } else {
+            system
+        };
+
+        std::iter::once(user)
+            .chain(system.iter().map(String::as_str))
+            .find_map(|text| {
+                self.extractors.iter().find_map(|extractor| {
+                    extractor.extract(text).map(|transcript| Extracted {
+                        text: transcript.to_string(),
+                        extractor: Some(extractor.name.clone()),
+                    })
+                })
+            })
+            .unwrap_or_else(|| Extracted {
+                text: user.to_string(),
+                extractor: None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extraction(json: serde_json::Value) -> Extraction {
+        Extraction::new(&serde_json::from_value(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_extractors() {
+        let extraction = extraction(serde_json::json!({
+            "extractors": [
+                {"name": "tags", "pattern": "(?s)<transcript>(?P<transcript>.*?)</transcript>"},
+                {"name": "quoted", "start": "Transcript: \"", "end": "\""},
+                {"start": "Text:"}
+            ]
+        }));
+
+        let prompt = "Fix the punctuation.\n<transcript>\n foo slash bar \n</transcript>\nReturn only the text.";
+        assert_eq!(
+            extraction.extract(prompt, &[]),
+            Extracted {
+                text: "foo slash bar".to_string(),
+                extractor: Some("tags".to_string()),
+            }
+        );
+
+        let prompt = "Clean up. Transcript: \"foo slash bar\" Thanks";
+        assert_eq!(
+            extraction.extract(prompt, &[]).extractor.as_deref(),
+            Some("quoted")
+        );
+
+        let extracted = extraction.extract("Clean up.\nText: foo slash bar", &[]);
+        assert_eq!(extracted.text, "foo slash bar");
+        assert_eq!(extracted.extractor.as_deref(), Some("extractor-3"));
+
+        // Unmatched requests and empty transcripts use the whole message
+        for text in ["foo slash bar", "<transcript> </transcript>"] {
+            assert_eq!(
+                extraction.extract(text, &[]),
+                Extracted {
+                    text: text.to_string(),
+                    extractor: None,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_system_message() {
+        let system = vec!["Fix this: <t>foo slash bar</t>".to_string()];
+
+        let ignoring = extraction(serde_json::json!({
+            "extractors": [{"pattern": "<t>(.*)</t>"}]
+        }));
+        assert_eq!(ignoring.extract("go", &system).text, "go");
+
+        let searching = extraction(serde_json::json!({
+            "ignore_system_message": false,
+            "extractors": [{"pattern": "<t>(.*)</t>"}]
+        }));
+        assert_eq!(searching.extract("go", &system).text, "foo slash bar");
+        // The user message is searched first
+        assert_eq!(searching.extract("<t>mine</t>", &system).text, "mine");
+    }
+
+    #[test]
+    fn test_invalid_extractors() {
+        for json in [
+            serde_json::json!({"extractors": [{"pattern": "("}]}),
+            serde_json::json!({"extractors": [{"name": "empty"}]}),
+            serde_json::json!({"extractors": [{"pattern": "a", "start": "b"}]}),
+        ] {
+            assert!(Extraction::new(&serde_json::from_value(json).unwrap()).is_err());
+        }
+    }
+}
@@ -1,16 +1,36 @@
 //! HTTP request handlers
 
+use crate::error::{
+    AnthropicApiError, ApiError, ApiJson, ApiQuery, LenientJson, OllamaApiError, TextOrJson,
+};
+use crate::history::HistoryQuery;
 use crate::models::{
-    ChatCompletionRequest, ChatCompletionResponse, HealthResponse, ModelsResponse, RuleInfo,
-    RuleToggleResponse, RulesResponse, TransformationLogEntry, TransformationLogResponse,
+    AnthropicMessage, AnthropicMessagesRequest, BatchTransformRequest, BatchTransformResponse,
+    ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, CompletionRequest,
+    CompletionResponse, CreateRuleRequest, DEFAULT_MODEL, HealthResponse, HistoryResponse,
+    LogStreamFilter, ModelsResponse, OllamaChatRequest, OllamaChatResponse, OllamaGenerateRequest,
+    OllamaGenerateResponse, OllamaTagsResponse, PackSummary, ResponsesRequest, ResponsesResponse,
+    RuleInfo, RulePreviewEntry, RulePreviewRequest, RulePreviewResponse, RuleToggleResponse,
+    RuleValidationError, RulesBulkResponse, RulesResponse, TransformRequest, TransformResponse,
+    TransformationLogEntry, TransformationLogResponse,
 };
+use crate::rules::{Rule, RuleFilter, RuleSelector, TransformationRecord};
 use crate::server::AppState;
+use crate::upstream::UpstreamChat;
 use axum::{
     Json,
+    body::Body,
     extract::{Path, State},
-    http::StatusCode,
+    http::{StatusCode, header},
     response::Html,
+    response::sse::{Event, KeepAlive, Sse},
+    response::{IntoResponse, Response},
 };
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::time::Instant;
+use tokio::sync::broadcast::error::RecvError;
 
 /// Health check endpoint
 #[utoipa::path(
@@ -19,6 +39,7 @@ use axum::{
     responses(
         (status = 200, description = "Server is healthy", body = HealthResponse)
     ),
+    security(()),
     tag = "Health"
 )]
 pub async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
@@ -31,41 +52,466 @@ pub async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
     })
 }
 
+/// Transform endpoint
+///
+/// Applies the rules selected by the profile and `rules_include` /
+/// `rules_exclude` to the text. Accepts JSON or a `text/plain` body. With
+/// `trace`, every rule evaluated is returned with the text before and after
+/// it. Requests are not written to the transformation log or the history,
+/// which are kept for the dictations of the chat endpoints.
+#[utoipa::path(
+    post,
+    path = "/v1/transform",
+    request_body = TransformRequest,
+    responses(
+        (status = 200, description = "Text transformed successfully", body = TransformResponse),
+        (status = 404, description = "Profile not found", body = ErrorResponse)
+    ),
+    tag = "Transform"
+)]
+pub async fn transform_text(
+    State(state): State<AppState>,
+    TextOrJson(request): TextOrJson<TransformRequest>,
+) -> Result<Json<TransformResponse>, ApiError> {
+    let engine = &state.rule_engine;
+    let profile = profile(&state, request.profile.as_deref())?;
+    let selectors: Vec<&RuleSelector> = profile.iter().chain([&request.rules]).collect();
+
+    if request.trace {
+        let trace = engine.trace(&request.text, &selectors);
+        return Ok(Json(TransformResponse {
+            output: trace.output,
+            duration_ms: trace.duration.as_secs_f64() * 1000.0,
+            trace: Some(trace.steps.into_iter().map(Into::into).collect()),
+        }));
+    }
+
+    let started = Instant::now();
+    let output = engine.transform_selected(&request.text, &selectors);
+
+    Ok(Json(TransformResponse {
+        output,
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        trace: None,
+    }))
+}
+
+/// Batch transform endpoint
+///
+/// Applies the selected rules to each text, spread over all cores. The outputs
+/// are in the order of the texts. Batches are not written to the
+/// transformation log or the history.
+#[utoipa::path(
+    post,
+    path = "/v1/transform/batch",
+    request_body = BatchTransformRequest,
+    responses(
+        (status = 200, description = "Texts transformed successfully", body = BatchTransformResponse),
+        (status = 404, description = "Profile not found", body = ErrorResponse)
+    ),
+    tag = "Transform"
+)]
+pub async fn transform_batch(
+    State(state): State<AppState>,
+    ApiJson(request): ApiJson<BatchTransformRequest>,
+) -> Result<Json<BatchTransformResponse>, ApiError> {
+    let started = Instant::now();
+    let profile = profile(&state, request.profile.as_deref())?;
+    let engine = state.rule_engine.clone();
+
+    // Rules may run shell commands, keep them off the async workers
+    let outputs = tokio::task::spawn_blocking(move || {
+        let selectors: Vec<&RuleSelector> = profile.iter().chain([&request.rules]).collect();
+        engine.transform_batch(&request.texts, &selectors)
+    })
+    .await
+    .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    Ok(Json(BatchTransformResponse {
+        outputs,
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+    }))
+}
+
 /// Chat completion endpoint (OpenAI-compatible)
 ///
 /// Accepts text input and applies transformation rules.
 /// Supports multiple input formats: messages array, prompt, input, or text fields.
+/// With `stream: true` the result is sent as `chat.completion.chunk` events
+/// ending in `data: [DONE]`.
 #[utoipa::path(
     post,
     path = "/v1/chat/completions",
     request_body = ChatCompletionRequest,
     responses(
-        (status = 200, description = "Text transformed successfully", body = ChatCompletionResponse),
-        (status = 400, description = "No user content found in request")
+        (status = 200, description = "Text transformed successfully", content(
+            ("application/json" = ChatCompletionResponse),
+            ("text/event-stream" = ChatCompletionChunk),
+        )),
+        (status = 400, description = "No user content found in request", body = ErrorResponse)
     ),
     tag = "Chat"
 )]
 pub async fn chat_completions(
     State(state): State<AppState>,
-    Json(request): Json<ChatCompletionRequest>,
-) -> Result<Json<ChatCompletionResponse>, StatusCode> {
+    ApiJson(request): ApiJson<ChatCompletionRequest>,
+) -> Result<Response, ApiError> {
     // Extract text to process
-    let input_text = request.extract_user_content().ok_or_else(|| {
-        tracing::warn!("No user content found in request");
-        StatusCode::BAD_REQUEST
-    })?;
+    let input_text = request.extract_user_content().ok_or_else(missing_content)?;
 
-    tracing::debug!("Processing input: {}", input_text);
+    let model = request.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let chat = UpstreamChat::new(
+        &[],
+        request.messages.as_deref().unwrap_or_default(),
+        request.sampling.clone(),
+    );
+    let processed_text =
+        transform(&state, &input_text, &request.system_content(), model, chat).await;
 
-    // Apply rules
-    let processed_text = state.rule_engine.apply(&input_text);
+    if request.stream {
+        let include_usage = request.stream_options.is_some_and(|o| o.include_usage);
+        let chunks = ChatCompletionChunk::stream(&input_text, processed_text, model, include_usage);
 
-    tracing::debug!("Output: {}", processed_text);
+        let events = chunks
+            .into_iter()
+            .map(|chunk| Event::default().json_data(chunk))
+            .chain(std::iter::once(Ok(Event::default().data("[DONE]"))))
+            .map(|event| Ok::<_, Infallible>(event.unwrap_or_default()));
+
+        return Ok(Sse::new(stream::iter(events)).into_response());
+    }
 
     // Build response with deterministic ID based on input
-    let response = ChatCompletionResponse::new(&input_text, processed_text);
+    let response = ChatCompletionResponse::new(&input_text, processed_text, model);
+
+    Ok(Json(response).into_response())
+}
+
+/// Legacy completion endpoint (OpenAI-compatible)
+///
+/// Applies transformation rules to the prompt and returns it as `choices[].text`.
+/// A batch of prompts yields one choice per prompt.
+#[utoipa::path(
+    post,
+    path = "/v1/completions",
+    request_body = CompletionRequest,
+    responses(
+        (status = 200, description = "Text transformed successfully", body = CompletionResponse),
+        (status = 400, description = "No prompt found in request, or streaming requested", body = ErrorResponse)
+    ),
+    tag = "Chat"
+)]
+pub async fn completions(
+    State(state): State<AppState>,
+    ApiJson(request): ApiJson<CompletionRequest>,
+) -> Result<Json<CompletionResponse>, ApiError> {
+    if request.stream {
+        return Err(stream_unsupported());
+    }
+    let prompts = request.prompts().ok_or_else(missing_content)?;
+
+    let model = request.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let mut results = Vec::with_capacity(prompts.len());
+    for prompt in prompts {
+        let chat = UpstreamChat::new(&[], &[], request.sampling.clone());
+        results.push((prompt, transform(&state, prompt, &[], model, chat).await));
+    }
+
+    Ok(Json(CompletionResponse::new(results, model)))
+}
+
+/// Responses endpoint (OpenAI Responses API)
+///
+/// Applies transformation rules to the input string or the last user message of
+/// the input items and returns it as an `output_text` message.
+#[utoipa::path(
+    post,
+    path = "/v1/responses",
+    request_body = ResponsesRequest,
+    responses(
+        (status = 200, description = "Text transformed successfully", body = ResponsesResponse),
+        (status = 400, description = "No user content found in request, or streaming requested", body = ErrorResponse)
+    ),
+    tag = "Chat"
+)]
+pub async fn responses(
+    State(state): State<AppState>,
+    ApiJson(request): ApiJson<ResponsesRequest>,
+) -> Result<Json<ResponsesResponse>, ApiError> {
+    if request.stream {
+        return Err(stream_unsupported());
+    }
+    let input_text = request.extract_user_content().ok_or_else(missing_content)?;
+
+    let model = request.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let chat = UpstreamChat::new(
+        request.instructions.as_slice(),
+        request.messages(),
+        request.sampling.clone(),
+    );
+    let processed_text =
+        transform(&state, &input_text, &request.system_content(), model, chat).await;
+
+    Ok(Json(ResponsesResponse::new(
+        &input_text,
+        processed_text,
+        model,
+    )))
+}
+
+/// Messages endpoint (Anthropic-compatible)
+///
+/// Applies transformation rules to the last user turn and returns it as a text
+/// block. With `stream: true` the result is sent as message events ending in
+/// `message_stop`.
+#[utoipa::path(
+    post,
+    path = "/v1/messages",
+    request_body = AnthropicMessagesRequest,
+    responses(
+        (status = 200, description = "Text transformed successfully", content(
+            ("application/json" = AnthropicMessage),
+            ("text/event-stream" = AnthropicStreamEvent),
+        )),
+        (status = 400, description = "No user content found in request", body = AnthropicErrorResponse)
+    ),
+    tag = "Anthropic"
+)]
+pub async fn anthropic_messages(
+    State(state): State<AppState>,
+    request: Result<ApiJson<AnthropicMessagesRequest>, ApiError>,
+) -> Result<Response, AnthropicApiError> {
+    let ApiJson(request) = request?;
+    let input_text = request.extract_user_content().ok_or_else(missing_content)?;
+
+    let model = request.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let system = request.system_content();
+    let chat = UpstreamChat::new(&system, &request.messages, request.sampling.clone());
+    let processed_text = transform(&state, &input_text, &system, model, chat).await;
+    let message = AnthropicMessage::new(&input_text, processed_text, model);
+
+    if !request.stream {
+        return Ok(Json(message).into_response());
+    }
+
+    let events = message.stream().into_iter().map(|event| {
+        let name = event.name();
+        Ok::<_, Infallible>(
+            Event::default()
+                .event(name)
+                .json_data(event)
+                .unwrap_or_default(),
+        )
+    });
+    Ok(Sse::new(stream::iter(events)).into_response())
+}
+
+/// Ollama chat endpoint
+///
+/// Applies transformation rules to the last user message. Streams
+/// newline-delimited JSON unless `stream` is false.
+#[utoipa::path(
+    post,
+    path = "/api/chat",
+    request_body = OllamaChatRequest,
+    responses(
+        (status = 200, description = "Text transformed successfully", content(
+            ("application/json" = OllamaChatResponse),
+            ("application/x-ndjson" = OllamaChatResponse),
+        )),
+        (status = 400, description = "No user content found in request", body = OllamaErrorResponse)
+    ),
+    tag = "Ollama"
+)]
+pub async fn ollama_chat(
+    State(state): State<AppState>,
+    request: Result<LenientJson<OllamaChatRequest>, ApiError>,
+) -> Result<Response, OllamaApiError> {
+    let LenientJson(request) = request?;
+    let input_text = request.extract_user_content().ok_or_else(missing_content)?;
+
+    let started = Instant::now();
+    let model = request.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let chat = UpstreamChat::new(&[], &request.messages, request.options.sampling());
+    let processed_text =
+        transform(&state, &input_text, &request.system_content(), model, chat).await;
+
+    let lines = OllamaChatResponse::lines(
+        &input_text,
+        processed_text,
+        model,
+        started.elapsed(),
+        request.stream,
+    );
+    Ok(ollama_response(lines, request.stream))
+}
+
+/// Ollama generate endpoint
+///
+/// Applies transformation rules to the prompt. Streams newline-delimited JSON
+/// unless `stream` is false.
+#[utoipa::path(
+    post,
+    path = "/api/generate",
+    request_body = OllamaGenerateRequest,
+    responses(
+        (status = 200, description = "Text transformed successfully", content(
+            ("application/json" = OllamaGenerateResponse),
+            ("application/x-ndjson" = OllamaGenerateResponse),
+        )),
+        (status = 400, description = "No prompt found in request", body = OllamaErrorResponse)
+    ),
+    tag = "Ollama"
+)]
+pub async fn ollama_generate(
+    State(state): State<AppState>,
+    request: Result<LenientJson<OllamaGenerateRequest>, ApiError>,
+) -> Result<Response, OllamaApiError> {
+    let LenientJson(request) = request?;
+    if request.prompt.is_empty() {
+        return Err(missing_content().into());
+    }
+
+    let started = Instant::now();
+    let system: Vec<String> = request.system.iter().cloned().collect();
+    let model = request.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let chat = UpstreamChat::new(&system, &[], request.options.sampling());
+    let processed_text = transform(&state, &request.prompt, &system, model, chat).await;
+
+    let lines = OllamaGenerateResponse::lines(
+        &request.prompt,
+        processed_text,
+        model,
+        started.elapsed(),
+        request.stream,
+    );
+    Ok(ollama_response(lines, request.stream))
+}
+
+/// List models in the Ollama format
+#[utoipa::path(
+    get,
+    path = "/api/tags",
+    responses(
+        (status = 200, description = "List of available models", body = OllamaTagsResponse)
+    ),
+    tag = "Ollama"
+)]
+pub async fn ollama_tags(State(state): State<AppState>) -> Json<OllamaTagsResponse> {
+    Json(OllamaTagsResponse::new(state.started_at))
+}
+
+/// Send Ollama response lines as newline-delimited JSON, or the single line
+/// of a non-streaming response as plain JSON
+fn ollama_response<T: Serialize + Send + 'static>(lines: Vec<T>, stream: bool) -> Response {
+    if !stream {
+        return match lines.into_iter().next() {
+            Some(line) => Json(line).into_response(),
+            None => StatusCode::NO_CONTENT.into_response(),
+        };
+    }
+
+    let lines = lines.into_iter().map(|line| {
+        let mut bytes = serde_json::to_vec(&line)?;
+        bytes.push(b'\n');
+        Ok::<_, serde_json::Error>(bytes)
+    });
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(stream::iter(lines)),
+    )
+        .into_response()
+}
+
+/// Apply the rules to a request's text, record it in the history and run the
+/// upstream stage, if configured
+///
+/// The transcript is pulled out of the user message or the system messages
+/// by the configured extractors; without a match the whole user message is used.
+/// The upstream gets the request's conversation and sampling parameters
+/// (`chat`) with the rule output in place of the transcript in the last user
+/// turn or the system messages; when it fails, the rule output is returned.
+async fn transform(
+    state: &AppState,
+    user: &str,
+    system: &[String],
+    model: &str,
+    mut chat: UpstreamChat,
+) -> String {
+    let input = state.extraction.extract(user, system);
+    let extracted = input.extractor.is_some();
+
+    let privacy = state.rule_engine.privacy();
+    match &input.extractor {
+        Some(extractor) => tracing::debug!(
+            "Processing input (extracted by '{}'): {}",
+            extractor,
+            privacy.text(&input.text)
+        ),
+        None => tracing::debug!("Processing input: {}", privacy.text(&input.text)),
+    }
+
+    let (output, record) = state
+        .rule_engine
+        .transform_extracted(&input.text, input.extractor);
+
+    tracing::debug!("Output: {}", privacy.text(&output));
+    append_history(state, record);
+
+    let Some(upstream) = &state.upstream else {
+        return output;
+    };
+
+    // Substitute the rule output for the transcript, wherever it was found
+    let substitute = |text: &str| text.replacen(&input.text, &output, 1);
+    if !extracted {
+        chat.set_user(output.clone());
+    } else if user.contains(&input.text) {
+        chat.set_user(substitute(user));
+    } else {
+        for message in chat.system_mut() {
+            message.content = substitute(&message.content);
+        }
+    }
+
+    upstream.process(&chat, model).await.unwrap_or(output)
+}
 
-    Ok(Json(response))
+/// The profile of the given name, if any; unknown names are an error
+fn profile(state: &AppState, name: Option<&str>) -> Result<Option<RuleSelector>, ApiError> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    match state.rule_engine.profile(name) {
+        Some(profile) => Ok(Some(profile.clone())),
+        None => Err(ApiError::not_found(format!("Profile '{}' not found", name))
+            .with_code("profile_not_found")),
+    }
+}
+
+/// Record a request in the on-disk history, if enabled
+/// The record is already stripped according to the privacy mode. The file I/O
+/// (and pruning old files) runs on a blocking thread, off the async workers.
+fn append_history(state: &AppState, record: TransformationRecord) {
+    if let Some(history) = state.history.clone() {
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = history.append(&TransformationLogEntry::from(record)) {
+                tracing::error!("Failed to write history: {}", e);
+            }
+        });
+    }
+}
+
+/// Error for a request without text to transform
+fn missing_content() -> ApiError {
+    tracing::warn!("No user content found in request");
+    ApiError::bad_request("No user content found in request").with_code("missing_content")
+}
+
+/// Error for a streaming request to an endpoint that cannot stream
+fn stream_unsupported() -> ApiError {
+    ApiError::bad_request("Streaming is only supported by /v1/chat/completions")
+        .with_code("stream_unsupported")
 }
 
 /// List available models endpoint
@@ -83,7 +529,8 @@ pub async fn list_models() -> Json<ModelsResponse> {
 
 /// Get transformation logs
 ///
-/// Returns recent transformation logs showing which rules matched and how text was transformed.
+/// Returns the most recent requests with their input, final output and the rules that
+/// matched on the way, each with the text after it was applied.
 #[utoipa::path(
     get,
     path = "/v1/logs",
@@ -96,19 +543,51 @@ pub async fn get_logs(State(state): State<AppState>) -> Json<TransformationLogRe
     let logs = state.rule_engine.get_transformation_log();
 
     Json(TransformationLogResponse {
-        logs: logs
-            .into_iter()
-            .map(|l| TransformationLogEntry {
-                rule_id: l.rule_id,
-                rule_type: l.rule_type,
-                input: l.input,
-                output: l.output,
-                matched: l.matched,
-            })
-            .collect(),
+        logs: logs.into_iter().map(TransformationLogEntry::from).collect(),
     })
 }
 
+/// Stream transformations as they happen (server-sent events)
+///
+/// Sends a `transformation` event with a log entry for every request, optionally
+/// filtered by rule id or to requests in which a rule matched. A `lagged` event
+/// carries the number of entries dropped for a client that fell behind.
+#[utoipa::path(
+    get,
+    path = "/v1/logs/stream",
+    params(LogStreamFilter),
+    responses(
+        (status = 200, description = "Stream of transformation events", body = TransformationLogEntry, content_type = "text/event-stream")
+    ),
+    tag = "Logs"
+)]
+pub async fn stream_logs(
+    State(state): State<AppState>,
+    ApiQuery(filter): ApiQuery<LogStreamFilter>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.rule_engine.subscribe();
+
+    let events = stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(record) if filter.matches(&record) => Event::default()
+                    .event("transformation")
+                    .id(record.id.clone())
+                    .json_data(TransformationLogEntry::from(record))
+                    .unwrap_or_else(|e| Event::default().event("error").data(e.to_string())),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    Event::default().event("lagged").data(skipped.to_string())
+                },
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(event), (receiver, filter)));
+        }
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
 /// Clear transformation logs
 #[utoipa::path(
     delete,
@@ -123,32 +602,199 @@ pub async fn clear_logs(State(state): State<AppState>) -> StatusCode {
     StatusCode::NO_CONTENT
 }
 
+/// Query the on-disk transformation history
+///
+/// Filters by time range, matched rule id and text in the input or output.
+/// Only available when `history.enabled` is set in the config.
+#[utoipa::path(
+    get,
+    path = "/v1/history",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "Matching history entries", body = HistoryResponse),
+        (status = 400, description = "Invalid time in the query", body = ErrorResponse),
+        (status = 404, description = "History is not enabled", body = ErrorResponse)
+    ),
+    tag = "Logs"
+)]
+pub async fn get_history(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<HistoryQuery>,
+) -> Result<Json<HistoryResponse>, ApiError> {
+    let history = state.history.clone().ok_or_else(|| {
+        ApiError::not_found("History is not enabled (set history.enabled in the config)")
+            .with_code("history_disabled")
+    })?;
+
+    // Queries read the daily files, keep them off the async workers
+    let entries = tokio::task::spawn_blocking(move || history.query(&query))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    Ok(Json(HistoryResponse {
+        count: entries.len(),
+        entries,
+    }))
+}
+
 /// Get all loaded rules
+///
+/// Optional query parameters narrow the list down by tag, pack, file, type or state.
 #[utoipa::path(
     get,
     path = "/v1/rules",
+    params(RuleFilter),
     responses(
-        (status = 200, description = "List of all rules", body = RulesResponse)
+        (status = 200, description = "List of matching rules", body = RulesResponse)
     ),
     tag = "Rules"
 )]
-pub async fn get_rules(State(state): State<AppState>) -> Json<RulesResponse> {
-    let rules = state.rule_engine.get_rules();
+pub async fn get_rules(
+    State(state): State<AppState>,
+    ApiQuery(filter): ApiQuery<RuleFilter>,
+) -> Json<RulesResponse> {
+    let mut rules = state.rule_engine.get_rules();
+    rules.retain(|r| filter.matches(r));
 
     Json(RulesResponse {
         count: rules.len(),
-        rules: rules
+        packs: PackSummary::collect(&rules),
+        rules: rules.into_iter().map(RuleInfo::from).collect(),
+        files: state.rule_engine.rule_files(),
+    })
+}
+
+/// Error for a rule ID that matches no loaded rule
+fn rule_not_found(rule_id: &str) -> ApiError {
+    ApiError::not_found(format!("Rule '{}' not found", rule_id)).with_code("rule_not_found")
+}
+
+/// Create a rule
+///
+/// Adds a rule to a rules file. The file must be one of the loaded rules files
+/// and may be omitted when only one file is loaded.
+#[utoipa::path(
+    post,
+    path = "/v1/rules",
+    request_body = CreateRuleRequest,
+    responses(
+        (status = 201, description = "Rule created", body = RuleInfo),
+        (status = 409, description = "A rule with this ID already exists", body = ErrorResponse),
+        (status = 422, description = "Rule is invalid, a shell rule or the target file is unknown", body = ErrorResponse)
+    ),
+    tag = "Rules"
+)]
+pub async fn create_rule(
+    State(state): State<AppState>,
+    ApiJson(request): ApiJson<CreateRuleRequest>,
+) -> Result<(StatusCode, Json<RuleInfo>), ApiError> {
+    let rule = state
+        .rule_engine
+        .create_rule(request.rule, request.file.as_deref())?;
+
+    Ok((StatusCode::CREATED, Json(RuleInfo::from(rule))))
+}
+
+/// Update a rule
+///
+/// Replaces a rule in its source file. The ID in the body may differ from the
+/// path to rename the rule.
+#[utoipa::path(
+    put,
+    path = "/v1/rules/{rule_id}",
+    params(
+        ("rule_id" = String, Path, description = "The rule ID to update (`id` or `pack:id`)")
+    ),
+    request_body = Rule,
+    responses(
+        (status = 200, description = "Rule updated", body = RuleInfo),
+        (status = 404, description = "Rule not found", body = ErrorResponse),
+        (status = 409, description = "Another rule already uses the new ID", body = ErrorResponse),
+        (status = 422, description = "Rule is invalid or a shell rule", body = ErrorResponse)
+    ),
+    tag = "Rules"
+)]
+pub async fn update_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<String>,
+    ApiJson(rule): ApiJson<Rule>,
+) -> Result<Json<RuleInfo>, ApiError> {
+    match state.rule_engine.update_rule(&rule_id, rule)? {
+        Some(rule) => Ok(Json(RuleInfo::from(rule))),
+        None => Err(rule_not_found(&rule_id)),
+    }
+}
+
+/// Delete a rule
+///
+/// Removes a rule from its source file.
+#[utoipa::path(
+    delete,
+    path = "/v1/rules/{rule_id}",
+    params(
+        ("rule_id" = String, Path, description = "The rule ID to delete (`id` or `pack:id`)")
+    ),
+    responses(
+        (status = 204, description = "Rule deleted"),
+        (status = 404, description = "Rule not found", body = ErrorResponse)
+    ),
+    tag = "Rules"
+)]
+pub async fn delete_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if state.rule_engine.delete_rule(&rule_id)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(rule_not_found(&rule_id))
+    }
+}
+
+/// Preview a draft rule set
+///
+/// Runs inputs through the current rules and a draft rule set without saving
+/// anything or writing to the transformation log. Without explicit inputs the
+/// most recent inputs from the transformation log are used.
+#[utoipa::path(
+    post,
+    path = "/v1/preview",
+    request_body = RulePreviewRequest,
+    responses(
+        (status = 200, description = "Preview results", body = RulePreviewResponse)
+    ),
+    tag = "Rules"
+)]
+pub async fn preview_rules(
+    State(state): State<AppState>,
+    ApiJson(request): ApiJson<RulePreviewRequest>,
+) -> Json<RulePreviewResponse> {
+    let inputs = request
+        .inputs
+        .unwrap_or_else(|| state.rule_engine.recent_inputs(request.limit));
+
+    let outcome = state
+        .rule_engine
+        .preview(request.upsert, &request.remove, &inputs);
+
+    let results: Vec<RulePreviewEntry> = outcome
+        .results
+        .into_iter()
+        .map(|r| RulePreviewEntry {
+            changed: r.current != r.preview,
+            input: r.input,
+            current: r.current,
+            preview: r.preview,
+        })
+        .collect();
+
+    Json(RulePreviewResponse {
+        errors: outcome
+            .errors
             .into_iter()
-            .map(|r| RuleInfo {
-                id: r.id,
-                description: r.description,
-                rule_type: format!("{:?}", r.rule_type).to_lowercase(),
-                pattern: r.pattern,
-                replacement: r.replacement,
-                priority: r.priority,
-                enabled: r.enabled,
-            })
+            .map(|(id, message)| RuleValidationError { id, message })
             .collect(),
+        changed: results.iter().filter(|r| r.changed).count(),
+        results,
     })
 }
 
@@ -164,20 +810,21 @@ pub async fn dashboard() -> Html<&'static str> {
     post,
     path = "/v1/rules/{rule_id}/toggle",
     params(
-        ("rule_id" = String, Path, description = "The rule ID to toggle")
+        ("rule_id" = String, Path, description = "The rule ID to toggle (`id` or `pack:id`)")
     ),
     responses(
         (status = 200, description = "Rule toggled successfully", body = RuleToggleResponse),
-        (status = 404, description = "Rule not found")
+        (status = 404, description = "Rule not found", body = ErrorResponse),
+        (status = 409, description = "The ID is used by several packs, use pack:id", body = ErrorResponse)
     ),
     tag = "Rules"
 )]
 pub async fn toggle_rule(
     State(state): State<AppState>,
     Path(rule_id): Path<String>,
-) -> Result<Json<RuleToggleResponse>, StatusCode> {
-    match state.rule_engine.toggle_rule(&rule_id) {
-        Ok(Some(enabled)) => {
+) -> Result<Json<RuleToggleResponse>, ApiError> {
+    match state.rule_engine.toggle_rule(&rule_id)? {
+        Some(enabled) => {
             let status = if enabled { "enabled" } else { "disabled" };
             Ok(Json(RuleToggleResponse {
                 id: rule_id.clone(),
@@ -185,10 +832,67 @@ pub async fn toggle_rule(
                 message: format!("Rule '{}' is now {}", rule_id, status),
             }))
         },
-        Ok(None) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            tracing::error!("Failed to toggle rule '{}': {}", rule_id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        },
+        None => Err(rule_not_found(&rule_id)),
     }
 }
+
+/// Enable all rules matching a filter
+///
+/// Selects rules by `tag`, `pack` and/or `file` (at least one is required),
+/// optionally narrowed down by `type`, and persists their new state.
+#[utoipa::path(
+    post,
+    path = "/v1/rules/bulk/enable",
+    request_body = RuleFilter,
+    responses(
+        (status = 200, description = "Rules enabled", body = RulesBulkResponse),
+        (status = 422, description = "No tag, pack or file given", body = ErrorResponse)
+    ),
+    tag = "Rules"
+)]
+pub async fn enable_rules(
+    State(state): State<AppState>,
+    ApiJson(filter): ApiJson<RuleFilter>,
+) -> Result<Json<RulesBulkResponse>, ApiError> {
+    set_rules_enabled(&state, filter, true)
+}
+
+/// Disable all rules matching a filter
+///
+/// Selects rules by `tag`, `pack` and/or `file` (at least one is required),
+/// optionally narrowed down by `type`, and persists their new state.
+#[utoipa::path(
+    post,
+    path = "/v1/rules/bulk/disable",
+    request_body = RuleFilter,
+    responses(
+        (status = 200, description = "Rules disabled", body = RulesBulkResponse),
+        (status = 422, description = "No tag, pack or file given", body = ErrorResponse)
+    ),
+    tag = "Rules"
+)]
+pub async fn disable_rules(
+    State(state): State<AppState>,
+    ApiJson(filter): ApiJson<RuleFilter>,
+) -> Result<Json<RulesBulkResponse>, ApiError> {
+    set_rules_enabled(&state, filter, false)
+}
+
+/// Shared implementation of the bulk enable/disable endpoints
+fn set_rules_enabled(
+    state: &AppState,
+    filter: RuleFilter,
+    enabled: bool,
+) -> Result<Json<RulesBulkResponse>, ApiError> {
+    if !filter.is_selective() {
+        return Err(ApiError::unprocessable("Select rules by tag, pack or file")
+            .with_code("missing_rule_selector"));
+    }
+
+    let rules = state.rule_engine.set_enabled(&filter, enabled)?;
+    Ok(Json(RulesBulkResponse {
+        enabled,
+        count: rules.len(),
+        rules,
+    }))
+}
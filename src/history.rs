@@ -0,0 +1,544 @@
+//! Persistent transformation history
+//!
+//! Requests are appended as JSON lines to one file per day (`YYYY-MM-DD.jsonl`,
+//! UTC) in the history directory. With a size limit, a day is continued in
+//! further parts (`YYYY-MM-DD.1.jsonl`, ...) once its file holds a tenth of the
+//! limit. Files are dropped once their day is older than `max_age_days`, or
+//! (oldest first) while the directory is larger than `max_size_mb`; the file
+//! being written is always kept.
+
+use crate::error::AppError;
+use crate::models::TransformationLogEntry;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use utoipa::IntoParams;
+
+/// Number of entries returned by a query without a limit
+const DEFAULT_LIMIT: usize = 100;
+
+/// A file is continued in a new part once it holds this fraction of the size
+/// limit, so that pruning can drop the older parts of a busy day
+const PARTS_PER_SIZE_LIMIT: u64 = 10;
+
+/// Day and part of a history file (part 0 is `YYYY-MM-DD.jsonl`)
+type FileKey = (NaiveDate, u32);
+
+/// Where and how long the history is kept
+#[derive(Debug, Clone)]
+pub struct HistoryOptions {
+    /// Directory holding the daily files
+    pub dir: PathBuf,
+
+    /// Days to keep (0 keeps every day)
+    pub max_age_days: u64,
+
+    /// Total size of the directory in bytes (0 means unlimited)
+    pub max_size_bytes: u64,
+}
+
+/// Filters for querying the history; unset fields match every entry
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    /// Entries at or after this time (RFC 3339 or `YYYY-MM-DD`)
+    #[serde(default)]
+    #[param(example = "2026-02-01")]
+    pub since: Option<String>,
+
+    /// Entries before this time (RFC 3339, or `YYYY-MM-DD` for the end of that day)
+    #[serde(default)]
+    #[param(example = "2026-02-02T12:00:00Z")]
+    pub until: Option<String>,
+
+    /// Requests in which this rule matched (`id` or `pack:id`)
+    #[serde(default)]
+    #[param(example = "de-punkt")]
+    pub rule: Option<String>,
+
+    /// Requests whose input or output contains this text (case-insensitive)
+    #[serde(default)]
+    #[param(example = "commit")]
+    pub text: Option<String>,
+
+    /// Maximum number of entries, the most recent ones are returned (default 100)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Parsed time range of a query
+struct TimeRange {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    fn parse(query: &HistoryQuery) -> Result<Self, AppError> {
+        Ok(Self {
+            since: query
+                .since
+                .as_deref()
+                .map(|s| parse_time(s, false))
+                .transpose()?,
+            until: query
+                .until
+                .as_deref()
+                .map(|s| parse_time(s, true))
+                .transpose()?,
+        })
+    }
+
+    fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.since.map_or(true, |since| time >= since)
+            && self.until.map_or(true, |until| time < until)
+    }
+
+    /// Whether a daily file can hold entries in the range
+    fn overlaps_day(&self, day: NaiveDate) -> bool {
+        self.since.map_or(true, |since| day >= since.date_naive())
+            && self.until.map_or(true, |until| day <= until.date_naive())
+    }
+}
+
+/// Parse an RFC 3339 time or a date; a date as upper bound means the end of that day
+fn parse_time(value: &str, end_of_day: bool) -> Result<DateTime<Utc>, AppError> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        AppError::InvalidHistoryQuery(format!(
+            "'{}' is neither an RFC 3339 time nor a YYYY-MM-DD date",
+            value
+        ))
+    })?;
+    let date = if end_of_day {
+        date.succ_opt().unwrap_or(date)
+    } else {
+        date
+    };
+    Ok(date.and_time(chrono::NaiveTime::MIN).and_utc())
+}
+
+/// Append-only history of transformations
+pub struct History {
+    options: HistoryOptions,
+
+    /// Serializes appends and pruning; holds the file of the last append and its size
+    state: Mutex<Option<(FileKey, u64)>>,
+}
+
+impl History {
+    /// Open (and create) the history directory and drop expired files
+    pub fn open(options: HistoryOptions) -> Result<Self, AppError> {
+        fs::create_dir_all(&options.dir)?;
+
+        let history = Self::open_read_only(options);
+        let today = Utc::now().date_naive();
+        let (current, _) = history.last_part(today)?;
+        history.prune(today, current)?;
+
+        Ok(history)
+    }
+
+    /// Open the history for queries only: nothing is created or removed
+    pub fn open_read_only(options: HistoryOptions) -> Self {
+        Self {
+            options,
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Directory holding the daily files
+    pub fn dir(&self) -> &Path {
+        &self.options.dir
+    }
+
+    /// Append a request to the file of its day, starting a new part when it is full
+    pub fn append(&self, entry: &TransformationLogEntry) -> Result<(), AppError> {
+        let day = DateTime::parse_from_rfc3339(&entry.timestamp)
+            .map(|t| t.with_timezone(&Utc).date_naive())
+            .unwrap_or_else(|_| Utc::now().date_naive());
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let len = line.len() as u64;
+
+        let mut state = self.state.lock().unwrap();
+
+        let (mut key, mut size) = match *state {
+            Some((key, size)) if key.0 == day => (key, size),
+            _ => self.last_part(day)?,
+        };
+        let part_limit = self.options.max_size_bytes / PARTS_PER_SIZE_LIMIT;
+        if self.options.max_size_bytes > 0 && size > 0 && size + len > part_limit.max(1) {
+            key.1 += 1;
+            size = 0;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(key))?
+            .write_all(line.as_bytes())?;
+
+        // A new file (a new day or part) is the time to check the limits again
+        let new_file = state.map(|(key, _)| key) != Some(key);
+        *state = Some((key, size + len));
+        if new_file {
+            self.prune(day, key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Entries matching the query (oldest first), at most `limit` of the most recent ones
+    pub fn query(&self, query: &HistoryQuery) -> Result<Vec<TransformationLogEntry>, AppError> {
+        let range = TimeRange::parse(query)?;
+        let text = query.text.as_deref().map(str::to_lowercase);
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+
+        let mut entries = Vec::new();
+        for ((day, _), path) in self.files()? {
+            if !range.overlaps_day(day) {
+                continue;
+            }
+
+            let reader = BufReader::new(fs::File::open(&path)?);
+            for (index, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let entry: TransformationLogEntry = match serde_json::from_str(&line) {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        tracing::warn!("Skipping {}:{}: {}", path.display(), index + 1, e);
+                        continue;
+                    },
+                };
+
+                let in_range = DateTime::parse_from_rfc3339(&entry.timestamp)
+                    .is_ok_and(|t| range.contains(t.with_timezone(&Utc)));
+                let rule_matched = query
+                    .rule
+                    .as_deref()
+                    .map_or(true, |rule| entry.matches.iter().any(|m| m.has_id(rule)));
+                let text_found = text.as_deref().map_or(true, |text| {
+                    entry.input.to_lowercase().contains(text)
+                        || entry.output.to_lowercase().contains(text)
+                });
+
+                if in_range && rule_matched && text_found {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        let start = entries.len().saturating_sub(limit);
+        Ok(entries.split_off(start))
+    }
+
+    fn path(&self, (day, part): FileKey) -> PathBuf {
+        let day = day.format("%Y-%m-%d");
+        let name = match part {
+            0 => format!("{}.jsonl", day),
+            part => format!("{}.{}.jsonl", day, part),
+        };
+        self.options.dir.join(name)
+    }
+
+    /// The last part of a day and its size (part 0 and size 0 if there is none)
+    fn last_part(&self, day: NaiveDate) -> Result<(FileKey, u64), AppError> {
+        match self.files()?.into_iter().rfind(|(key, _)| key.0 == day) {
+            Some((key, path)) => Ok((key, fs::metadata(path)?.len())),
+            None => Ok(((day, 0), 0)),
+        }
+    }
+
+    /// History files, oldest first (none if the directory does not exist)
+    fn files(&self) -> Result<Vec<(FileKey, PathBuf)>, AppError> {
+        let entries = match fs::read_dir(&self.options.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut files: Vec<(FileKey, PathBuf)> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .filter_map(|path| {
+                let stem = path.file_stem()?.to_str()?;
+                let (day, part) = match stem.split_once('.') {
+                    Some((day, part)) => (day, part.parse().ok()?),
+                    None => (stem, 0),
+                };
+                let day = NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()?;
+                Some(((day, part), path))
+            })
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Drop files of days past the age limit, then the oldest files while over
+    /// the size limit, except the `current` file
+    fn prune(&self, today: NaiveDate, current: FileKey) -> Result<(), AppError> {
+        let files = self.files()?;
+
+        let mut sizes = Vec::with_capacity(files.len());
+        for (key, path) in files {
+            let size = fs::metadata(&path)?.len();
+            sizes.push((key, path, size));
+        }
+        let mut total: u64 = sizes.iter().map(|(_, _, size)| size).sum();
+
+        for (key, path, size) in sizes {
+            if key == current {
+                continue;
+            }
+
+            let expired = self.options.max_age_days > 0
+                && (today - key.0).num_days() >= self.options.max_age_days as i64;
+            let too_large = self.options.max_size_bytes > 0 && total > self.options.max_size_bytes;
+            if !expired && !too_large {
+                continue;
+            }
+
+            fs::remove_file(&path)?;
+            total -= size;
+            tracing::info!("Removed history file {}", path.display());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RuleMatchEntry;
+    use tempfile::TempDir;
+
+    fn entry(timestamp: &str, input: &str, output: &str, rules: &[&str]) -> TransformationLogEntry {
+        TransformationLogEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: timestamp.to_string(),
+            duration_ms: 0.1,
+            input: input.to_string(),
+            output: output.to_string(),
+            matches: rules
+                .iter()
+                .map(|rule| RuleMatchEntry {
+                    rule_id: rule.to_string(),
+                    qualified_id: Some(format!("basics:{}", rule)),
+                    rule_type: "Regex".to_string(),
+                    group: None,
+                    output: output.to_string(),
+                })
+                .collect(),
+            skipped: Vec::new(),
+            extractor: None,
+        }
+    }
+
+    fn open(dir: &TempDir, max_age_days: u64, max_size_bytes: u64) -> History {
+        History::open(HistoryOptions {
+            dir: dir.path().to_path_buf(),
+            max_age_days,
+            max_size_bytes,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let dir = TempDir::new().unwrap();
+        let history = open(&dir, 0, 0);
+        history
+            .append(&entry(
+                "2026-02-01T09:00:00Z",
+                "a slash b",
+                "a / b",
+                &["slash"],
+            ))
+            .unwrap();
+        history
+            .append(&entry(
+                "2026-02-02T09:00:00Z",
+                "Hello dot",
+                "Hello.",
+                &["dot"],
+            ))
+            .unwrap();
+        history
+            .append(&entry("2026-02-02T18:00:00Z", "nothing", "nothing", &[]))
+            .unwrap();
+
+        let inputs = |query: HistoryQuery| -> Vec<String> {
+            history
+                .query(&query)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.input)
+                .collect()
+        };
+
+        assert_eq!(inputs(HistoryQuery::default()).len(), 3);
+        assert_eq!(
+            inputs(HistoryQuery {
+                since: Some("2026-02-02".to_string()),
+                until: Some("2026-02-02T12:00:00Z".to_string()),
+                ..Default::default()
+            }),
+            ["Hello dot"]
+        );
+        assert_eq!(
+            inputs(HistoryQuery {
+                until: Some("2026-02-01".to_string()),
+                ..Default::default()
+            }),
+            ["a slash b"]
+        );
+        assert_eq!(
+            inputs(HistoryQuery {
+                rule: Some("slash".to_string()),
+                ..Default::default()
+            }),
+            ["a slash b"]
+        );
+        assert_eq!(
+            inputs(HistoryQuery {
+                rule: Some("basics:slash".to_string()),
+                ..Default::default()
+            }),
+            ["a slash b"]
+        );
+        assert!(
+            inputs(HistoryQuery {
+                rule: Some("other:slash".to_string()),
+                ..Default::default()
+            })
+            .is_empty()
+        );
+        assert_eq!(
+            inputs(HistoryQuery {
+                text: Some("HELLO".to_string()),
+                ..Default::default()
+            }),
+            ["Hello dot"]
+        );
+        assert_eq!(
+            inputs(HistoryQuery {
+                limit: Some(1),
+                ..Default::default()
+            }),
+            ["nothing"]
+        );
+
+        let err = history
+            .query(&HistoryQuery {
+                since: Some("yesterday".to_string()),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(matches!(err, AppError::InvalidHistoryQuery(_)));
+    }
+
+    #[test]
+    fn test_rotation_by_age_and_size() {
+        let dir = TempDir::new().unwrap();
+        let today = Utc::now().date_naive();
+        for days_ago in [40, 3, 2, 1] {
+            let day = today - chrono::Duration::days(days_ago);
+            fs::write(
+                dir.path().join(format!("{}.jsonl", day.format("%Y-%m-%d"))),
+                "x".repeat(100),
+            )
+            .unwrap();
+        }
+
+        // Old days go first, then the oldest remaining ones until under the size limit
+        let history = open(&dir, 30, 250);
+        let days: Vec<NaiveDate> = history
+            .files()
+            .unwrap()
+            .into_iter()
+            .map(|((d, _), _)| d)
+            .collect();
+        assert_eq!(
+            days,
+            [
+                today - chrono::Duration::days(2),
+                today - chrono::Duration::days(1)
+            ]
+        );
+
+        // The current day is never removed
+        let history = open(&dir, 30, 1);
+        history
+            .append(&entry(&Utc::now().to_rfc3339(), "a", "b", &[]))
+            .unwrap();
+        let days: Vec<NaiveDate> = history
+            .files()
+            .unwrap()
+            .into_iter()
+            .map(|((d, _), _)| d)
+            .collect();
+        assert_eq!(days, [today]);
+    }
+
+    #[test]
+    fn test_rotation_within_a_day() {
+        let dir = TempDir::new().unwrap();
+        let history = open(&dir, 0, 2000);
+        let now = Utc::now().to_rfc3339();
+        for i in 0..50 {
+            history
+                .append(&entry(&now, &format!("input {}", i), "output", &[]))
+                .unwrap();
+        }
+
+        // A busy day is split into parts, the oldest of which are dropped
+        let files = history.files().unwrap();
+        assert!(files.len() > 1);
+        assert!(files.iter().any(|((_, part), _)| *part > 0));
+        let total: u64 = files
+            .iter()
+            .map(|(_, path)| fs::metadata(path).unwrap().len())
+            .sum();
+        assert!(total <= 2000 + 2000 / PARTS_PER_SIZE_LIMIT, "{}", total);
+
+        // The parts are read in order; a read-only history sees the same entries
+        let read_only = History::open_read_only(HistoryOptions {
+            dir: dir.path().to_path_buf(),
+            max_age_days: 0,
+            max_size_bytes: 0,
+        });
+        let numbers: Vec<usize> = read_only
+            .query(&HistoryQuery::default())
+            .unwrap()
+            .iter()
+            .map(|e| e.input["input ".len()..].parse().unwrap())
+            .collect();
+        assert_eq!(numbers.last(), Some(&49));
+        assert!(numbers.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_read_only_history() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+        let history = History::open_read_only(HistoryOptions {
+            dir: missing.clone(),
+            max_age_days: 1,
+            max_size_bytes: 1,
+        });
+        assert!(history.query(&HistoryQuery::default()).unwrap().is_empty());
+        assert!(!missing.exists());
+    }
+}
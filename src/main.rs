@@ -7,17 +7,28 @@
 //! - HTTP server: `handy-rules serve`
 //! - CLI tool: `handy-rules transform "text to transform"`
 
+mod auth;
+mod batch;
 mod config;
 mod error;
+mod extract;
 mod handlers;
+mod history;
 mod models;
+mod privacy;
 mod rules;
 mod server;
+mod upstream;
 
+use crate::batch::Item;
 use crate::config::{Config, find_config_file, get_config_dir};
-use crate::rules::RuleEngine;
+use crate::history::{History, HistoryQuery};
+use crate::rules::{Rule, RuleEngine, RuleFilter, RulesFormat, is_rules_file, load_rules_file};
+use anyhow::Context;
 use clap::{Parser, Subcommand};
-use std::io::{self, BufRead, Write};
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser)]
@@ -54,18 +65,19 @@ enum Command {
         /// Port to listen on (overrides config file)
         #[arg(short, long)]
         port: Option<u16>,
-    },
 
-    /// Transform text using rules (CLI mode)
-    Transform {
-        /// Text to transform (if not provided, reads from stdin)
-        text: Option<String>,
+        /// Key required by the transformation endpoints (overrides config file)
+        #[arg(long, env = "HANDY_RULES_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
 
-        /// Read input line by line from stdin
-        #[arg(short, long)]
-        stdin: bool,
+        /// Key required by rule management, logs and history (overrides config file)
+        #[arg(long, env = "HANDY_RULES_ADMIN_KEY", hide_env_values = true)]
+        admin_key: Option<String>,
     },
 
+    /// Transform text using rules (CLI mode)
+    Transform(TransformArgs),
+
     /// Validate rules file
     Validate,
 
@@ -73,6 +85,36 @@ enum Command {
     #[command(name = "list-rules")]
     ListRules,
 
+    /// Enable all rules with a tag, from a pack or from a file
+    Enable {
+        #[command(flatten)]
+        selection: RuleSelection,
+    },
+
+    /// Disable all rules with a tag, from a pack or from a file
+    Disable {
+        #[command(flatten)]
+        selection: RuleSelection,
+    },
+
+    /// Convert a rules file to another format (json, jsonc, json5, yaml, toml).
+    /// Comments are not carried over.
+    Convert {
+        /// Rules file to read
+        input: String,
+
+        /// File to write (format taken from its extension); prints to stdout if omitted
+        output: Option<String>,
+
+        /// Output format (overrides the output file extension)
+        #[arg(short, long)]
+        to: Option<String>,
+
+        /// Overwrite an existing output file
+        #[arg(short, long)]
+        force: bool,
+    },
+
     /// Show service installation status (macOS)
     Status,
 
@@ -96,14 +138,128 @@ enum Command {
         #[arg(short = 'n', long, default_value = "10")]
         count: usize,
 
-        /// Follow mode: continuously print new logs
+        /// Follow mode: print transformations as they happen
         #[arg(short, long)]
         follow: bool,
 
+        /// Only transformations in which this rule matched
+        #[arg(long)]
+        rule: Option<String>,
+
         /// Clear logs after showing (ignored with --follow)
         #[arg(long)]
         clear: bool,
+
+        /// Admin key of the server (default: `admin_key` or `api_key` of the config file)
+        #[arg(long, env = "HANDY_RULES_ADMIN_KEY", hide_env_values = true)]
+        admin_key: Option<String>,
     },
+
+    /// Search the on-disk transformation history (requires history.enabled)
+    History {
+        #[command(flatten)]
+        query: HistorySelection,
+
+        /// Print matching entries as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Filters for the history command
+#[derive(clap::Args)]
+struct HistorySelection {
+    /// Entries at or after this time (RFC 3339 or YYYY-MM-DD)
+    #[arg(long)]
+    since: Option<String>,
+
+    /// Entries before this time (RFC 3339, or YYYY-MM-DD for the end of that day)
+    #[arg(long)]
+    until: Option<String>,
+
+    /// Requests in which this rule matched
+    #[arg(long)]
+    rule: Option<String>,
+
+    /// Requests whose input or output contains this text (case-insensitive)
+    #[arg(short, long)]
+    search: Option<String>,
+
+    /// Number of most recent entries to show
+    #[arg(short = 'n', long, default_value = "20")]
+    count: usize,
+}
+
+impl From<HistorySelection> for HistoryQuery {
+    fn from(selection: HistorySelection) -> Self {
+        HistoryQuery {
+            since: selection.since,
+            until: selection.until,
+            rule: selection.rule,
+            text: selection.search,
+            limit: Some(selection.count),
+        }
+    }
+}
+
+/// Rules to enable or disable (at least one criterion; rules must match all given)
+#[derive(clap::Args)]
+struct TransformArgs {
+    /// Text to transform (if not provided, reads all of stdin)
+    #[arg(conflicts_with = "inputs")]
+    text: Option<String>,
+
+    /// Read input line by line from stdin
+    #[arg(short, long)]
+    stdin: bool,
+
+    /// Read NUL-separated records from stdin and separate the outputs with NUL
+    #[arg(short = '0', long, conflicts_with = "stdin")]
+    null: bool,
+
+    /// Files or directories to transform, each file as a whole (directories recursively)
+    #[arg(short, long = "input", value_name = "PATH", conflicts_with_all = ["stdin", "null"])]
+    inputs: Vec<PathBuf>,
+
+    /// Read a JSONL corpus: a string or an object with a `text` field per line
+    /// (implied for .jsonl files)
+    #[arg(long, conflicts_with_all = ["stdin", "null"])]
+    jsonl: bool,
+
+    /// Write a JSON object with the text and the output per line
+    #[arg(long)]
+    json: bool,
+
+    /// Write to this file; for several files or a directory, to this directory
+    /// under the same relative paths
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct RuleSelection {
+    /// Rules with this tag
+    #[arg(short, long)]
+    tag: Option<String>,
+
+    /// Rules of this pack
+    #[arg(short, long)]
+    pack: Option<String>,
+
+    /// Rules from this file (full path or trailing components, e.g. git.json)
+    #[arg(short, long)]
+    file: Option<String>,
+}
+
+impl From<RuleSelection> for RuleFilter {
+    fn from(selection: RuleSelection) -> Self {
+        RuleFilter {
+            tag: selection.tag,
+            pack: selection.pack,
+            file: selection.file,
+            ..Default::default()
+        }
+    }
 }
 
 #[tokio::main]
@@ -116,30 +272,53 @@ async fn main() -> anyhow::Result<()> {
     // Merge global CLI args
     let config = config.merge_with_args(None, None, args.rules, args.log_level);
 
-    // Initialize logging
+    // Initialize logging (on stderr, stdout is for transformed text)
     tracing_subscriber::fmt()
         .with_env_filter(
             EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log_level)),
         )
+        .with_writer(io::stderr)
         .init();
 
     // Handle command
     match args.command {
-        Some(Command::Serve { host, port }) => {
-            let config = config.merge_with_args(host, port, None, None);
+        Some(Command::Serve {
+            host,
+            port,
+            api_key,
+            admin_key,
+        }) => {
+            let mut config = config.merge_with_args(host, port, None, None);
+            if api_key.is_some() {
+                config.api_key = api_key;
+            }
+            if admin_key.is_some() {
+                config.admin_key = admin_key;
+            }
             run_server(config).await
         },
-        Some(Command::Transform { text, stdin }) => run_transform(&config, text, stdin),
+        Some(Command::Transform(args)) => run_transform(&config, args),
         Some(Command::Validate) => run_validate(&config),
         Some(Command::ListRules) => run_list_rules(&config),
+        Some(Command::Enable { selection }) => run_set_enabled(&config, selection.into(), true),
+        Some(Command::Disable { selection }) => run_set_enabled(&config, selection.into(), false),
+        Some(Command::Convert {
+            input,
+            output,
+            to,
+            force,
+        }) => run_convert(&input, output.as_deref(), to.as_deref(), force),
         Some(Command::Status) => run_status(&config).await,
         Some(Command::Setup { force }) => run_setup(force),
         Some(Command::Dashboard { browser }) => run_dashboard(&config, browser),
         Some(Command::Logs {
             count,
             follow,
+            rule,
             clear,
-        }) => run_logs(&config, count, follow, clear).await,
+            admin_key,
+        }) => run_logs(&config, count, follow, rule, clear, admin_key).await,
+        Some(Command::History { query, json }) => run_history(&config, query.into(), json),
         None => {
             // Default: start server (backward compatible)
             run_server(config).await
@@ -186,47 +365,134 @@ async fn run_server(config: Config) -> anyhow::Result<()> {
     );
     tracing::debug!("Configuration: {:?}", config);
 
-    server::run(
-        &config.host,
-        config.port,
-        &config.get_rules_paths(),
-        config.enable_shell_rules,
-    )
-    .await
+    server::run(&config).await
 }
 
-fn run_transform(config: &Config, text: Option<String>, stdin: bool) -> anyhow::Result<()> {
-    let engine = RuleEngine::new_from_paths(&config.get_rules_paths(), config.enable_shell_rules)?;
+fn run_transform(config: &Config, args: TransformArgs) -> anyhow::Result<()> {
+    let engine = RuleEngine::with_options(&config.get_rules_paths(), config.engine_options()?)?;
 
-    if let Some(input) = text {
-        // Transform provided text
-        let output = engine.apply(&input);
-        println!("{}", output);
-    } else if stdin {
-        // Read and transform line by line
-        let stdin = io::stdin();
-        let stdout = io::stdout();
-        let mut stdout = stdout.lock();
+    if args.stdin {
+        // Transform line by line as the lines arrive
+        let mut out = open_output(&args)?;
+        for line in io::stdin().lock().lines() {
+            let item = Item::record(&line?, "\n");
+            write_item(&mut out, &item, &engine.apply(&item.text), args.json)?;
+            out.flush()?;
+        }
+        return Ok(());
+    }
 
-        for line in stdin.lock().lines() {
-            let line = line?;
-            let output = engine.apply(&line);
-            writeln!(stdout, "{}", output)?;
+    // Read all inputs before creating the output, which may be one of them
+    let items = read_items(&args)?;
+    let texts: Vec<String> = items.iter().map(|item| item.text.clone()).collect();
+    let outputs = engine.transform_batch(&texts, &[])?;
+
+    if let (Some(dir), true) = (&args.output, writes_to_dir(&args)) {
+        // Items of the same file (lines of a corpus) go to one output file
+        let mut files: Vec<(&Path, Vec<u8>)> = Vec::new();
+        for (item, output) in items.iter().zip(&outputs) {
+            let relative = item.relative.as_deref().unwrap_or(Path::new("stdin"));
+            match files.last_mut() {
+                Some((path, content)) if *path == relative => {
+                    write_item(content, item, output, item.object.is_some())?;
+                },
+                _ => {
+                    let mut content = Vec::new();
+                    write_item(&mut content, item, output, item.object.is_some())?;
+                    files.push((relative, content));
+                },
+            }
         }
-    } else {
-        // Read all from stdin, transform, output
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let output = engine.apply(input.trim());
-        println!("{}", output);
+        for (relative, content) in &files {
+            let path = dir.join(relative);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        eprintln!("Wrote {} files to {}", files.len(), dir.display());
+        return Ok(());
     }
 
+    let mut out = open_output(&args)?;
+    for (item, output) in items.iter().zip(&outputs) {
+        write_item(&mut out, item, output, args.json || item.object.is_some())?;
+    }
+    out.flush()?;
+
     Ok(())
 }
 
+/// The `--output` file, or stdout
+fn open_output(args: &TransformArgs) -> anyhow::Result<Box<dyn Write>> {
+    Ok(match &args.output {
+        Some(path) if !writes_to_dir(args) => Box::new(io::BufWriter::new(
+            fs::File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?,
+        )),
+        _ => Box::new(io::BufWriter::new(io::stdout().lock())),
+    })
+}
+
+/// Whether `--output` names a directory: for several files or a directory
+/// transformed as plain text
+fn writes_to_dir(args: &TransformArgs) -> bool {
+    !args.json && !args.jsonl && (args.inputs.len() > 1 || args.inputs.iter().any(|p| p.is_dir()))
+}
+
+/// The items to transform: the text argument, the input files, or stdin
+fn read_items(args: &TransformArgs) -> anyhow::Result<Vec<Item>> {
+    if let Some(text) = &args.text {
+        return Ok(vec![Item::record(text, "\n")]);
+    }
+
+    if !args.inputs.is_empty() {
+        let mut items = Vec::new();
+        for (file, relative) in batch::collect_files(&args.inputs)? {
+            let content = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            if args.jsonl || batch::is_jsonl(&file) {
+                items.extend(batch::parse_jsonl(&content, Some((&file, &relative)))?);
+            } else {
+                items.push(Item {
+                    file: Some(file),
+                    relative: Some(relative),
+                    ..Item::whole(&content)
+                });
+            }
+        }
+        return Ok(items);
+    }
+
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+
+    if args.null {
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        let records = input.strip_suffix('\0').unwrap_or(&input);
+        Ok(records.split('\0').map(|r| Item::record(r, "\0")).collect())
+    } else if args.jsonl {
+        batch::parse_jsonl(&input, None)
+    } else {
+        Ok(vec![Item::whole(&input)])
+    }
+}
+
+/// Write an output as a JSON line or as text followed by the item's ending
+fn write_item(out: &mut dyn Write, item: &Item, output: &str, json: bool) -> io::Result<()> {
+    if json {
+        writeln!(out, "{}", item.to_json(output))
+    } else {
+        write!(out, "{}{}", output, item.ending)
+    }
+}
+
 fn run_validate(config: &Config) -> anyhow::Result<()> {
     let paths = config.get_rules_paths();
-    match RuleEngine::new_from_paths(&paths, config.enable_shell_rules) {
+    match RuleEngine::with_options(&paths, config.engine_options()?) {
         Ok(engine) => {
             println!("✓ Rules files are valid");
             println!("  Loaded {} rules from {:?}", engine.rules_count(), paths);
@@ -241,27 +507,148 @@ fn run_validate(config: &Config) -> anyhow::Result<()> {
 
 fn run_list_rules(config: &Config) -> anyhow::Result<()> {
     let paths = config.get_rules_paths();
-    let engine = RuleEngine::new_from_paths(&paths, config.enable_shell_rules)?;
+    let engine = RuleEngine::with_options(&paths, config.engine_options()?)?;
     let rules = engine.get_rules();
 
     println!("Loaded {} rules from {:?}:\n", rules.len(), paths);
 
-    for rule in rules {
-        let status = if rule.enabled { "✓" } else { "✗" };
-        let rule_type = format!("{:?}", rule.rule_type).to_lowercase();
-        println!(
-            "{} [{}] {} (priority: {}, type: {})",
-            status,
-            rule.id,
-            rule.description.unwrap_or_default(),
-            rule.priority,
-            rule_type
-        );
-        println!("    pattern: {}", rule.pattern);
-        if !rule.replacement.is_empty() {
-            println!("    replacement: {}", rule.replacement);
+    // Group rules by pack (in order of first appearance), rules without a pack last
+    let mut groups: Vec<(Option<String>, Vec<&Rule>)> = Vec::new();
+    for rule in &rules {
+        let name = rule.pack.as_ref().map(|p| p.name.clone());
+        match groups.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, members)) => members.push(rule),
+            None => groups.push((name, vec![rule])),
         }
-        println!();
+    }
+    groups.sort_by_key(|(name, _)| name.is_none());
+    let has_packs = groups.iter().any(|(name, _)| name.is_some());
+
+    for (_, members) in groups {
+        if has_packs {
+            match &members[0].pack {
+                Some(pack) => {
+                    let mut header = pack.name.clone();
+                    if let Some(version) = &pack.version {
+                        header.push_str(&format!(" v{}", version));
+                    }
+                    if let Some(language) = &pack.language {
+                        header.push_str(&format!(" [{}]", language));
+                    }
+                    if let Some(author) = &pack.author {
+                        header.push_str(&format!(" by {}", author));
+                    }
+                    println!("== {} ({} rules) ==", header, members.len());
+                    if let Some(description) = &pack.description {
+                        println!("   {}", description);
+                    }
+                },
+                None => println!("== Rules without pack ({} rules) ==", members.len()),
+            }
+            println!();
+        }
+
+        for rule in members {
+            let status = if rule.enabled { "✓" } else { "✗" };
+            let rule_type = format!("{:?}", rule.rule_type).to_lowercase();
+            println!(
+                "{} [{}] {} (priority: {}, type: {})",
+                status,
+                rule.id,
+                rule.description.as_deref().unwrap_or_default(),
+                rule.priority,
+                rule_type
+            );
+            if !rule.tags.is_empty() {
+                println!("    tags: {}", rule.tags.join(", "));
+            }
+            if let Some(group) = &rule.group {
+                println!("    group: {}", group);
+            }
+            println!("    pattern: {}", rule.pattern);
+            if !rule.replacement.is_empty() {
+                println!("    replacement: {}", rule.replacement);
+            }
+            if let Some(overrides) = &config.overrides_path {
+                if rule.source_file.as_ref() == Some(overrides) {
+                    println!("    added by: {}", overrides);
+                } else if !rule.overridden.is_empty() {
+                    println!(
+                        "    overridden: {} (from {})",
+                        rule.overridden.join(", "),
+                        overrides
+                    );
+                }
+            }
+            println!();
+        }
+    }
+
+    Ok(())
+}
+
+fn run_set_enabled(config: &Config, filter: RuleFilter, enabled: bool) -> anyhow::Result<()> {
+    if !filter.is_selective() {
+        anyhow::bail!("Select rules with --tag, --pack or --file");
+    }
+
+    let engine = RuleEngine::with_options(&config.get_rules_paths(), config.engine_options()?)?;
+    let matching = engine
+        .get_rules()
+        .iter()
+        .filter(|r| filter.matches(r))
+        .count();
+    let changed = engine.set_enabled(&filter, enabled)?;
+
+    let action = if enabled { "Enabled" } else { "Disabled" };
+    for rule_id in &changed {
+        println!("{} {}", action, rule_id);
+    }
+    println!(
+        "{} {} rule(s) ({} matching, {} already {})",
+        action,
+        changed.len(),
+        matching,
+        matching - changed.len(),
+        if enabled { "enabled" } else { "disabled" }
+    );
+
+    Ok(())
+}
+
+fn run_convert(
+    input: &str,
+    output: Option<&str>,
+    to: Option<&str>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let format = match (to, output) {
+        (Some(name), _) => RulesFormat::from_name(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown format: {}", name))?,
+        (None, Some(path)) => RulesFormat::from_path(std::path::Path::new(path))
+            .ok_or_else(|| anyhow::anyhow!("Can't tell the format of {} (use --to)", path))?,
+        (None, None) => anyhow::bail!("Specify an output file or --to"),
+    };
+
+    let file = load_rules_file(std::path::Path::new(input))?;
+    let content = format
+        .serialize(&file)
+        .map_err(|e| anyhow::anyhow!("Failed to convert {}: {}", input, e))?;
+
+    match output {
+        Some(path) => {
+            if std::path::Path::new(path).exists() && !force {
+                anyhow::bail!("{} already exists (use --force to overwrite)", path);
+            }
+            std::fs::write(path, content)?;
+            eprintln!(
+                "Converted {} rules: {} -> {}",
+                file.rules.len(),
+                input,
+                path
+            );
+        },
+        None => print!("{}", content),
     }
 
     Ok(())
@@ -315,17 +702,30 @@ fn run_dashboard(config: &Config, browser: Option<String>) -> anyhow::Result<()>
     Ok(())
 }
 
-async fn run_logs(config: &Config, count: usize, follow: bool, clear: bool) -> anyhow::Result<()> {
+async fn run_logs(
+    config: &Config,
+    count: usize,
+    follow: bool,
+    rule: Option<String>,
+    clear: bool,
+    admin_key: Option<String>,
+) -> anyhow::Result<()> {
     use serde::Deserialize;
-    use std::collections::HashSet;
 
-    #[derive(Deserialize, Clone)]
-    #[allow(dead_code)]
+    #[derive(Deserialize)]
     struct LogEntry {
-        rule_id: String,
         input: String,
         output: String,
-        matched: bool,
+        matches: Vec<RuleMatch>,
+        #[serde(default)]
+        extractor: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct RuleMatch {
+        rule_id: String,
+        #[serde(default)]
+        qualified_id: Option<String>,
     }
 
     #[derive(Deserialize)]
@@ -335,90 +735,115 @@ async fn run_logs(config: &Config, count: usize, follow: bool, clear: bool) -> a
 
     let logs_url = format!("http://{}:{}/v1/logs", config.host, config.port);
 
-    // Helper to group logs into requests
-    fn group_logs(logs: &[LogEntry]) -> Vec<(String, String)> {
-        let mut requests: Vec<(String, String)> = Vec::new();
-        let mut current_input: Option<String> = None;
-        let mut current_output: Option<String> = None;
-
-        for log in logs {
-            if current_input.is_none() || Some(&log.input) != current_output.as_ref() {
-                if let (Some(inp), Some(out)) = (current_input.take(), current_output.take()) {
-                    requests.push((inp, out));
-                }
-                current_input = Some(log.input.clone());
-            }
-            current_output = Some(log.output.clone());
+    // The log endpoints require the admin key if the server sets one
+    let admin_key = admin_key.or_else(|| config.auth().admin_key().map(String::from));
+    let client = reqwest::Client::new();
+    let request = |method: reqwest::Method, url: &str| {
+        let request = client.request(method, url);
+        match &admin_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
         }
-
-        if let (Some(inp), Some(out)) = (current_input, current_output) {
-            requests.push((inp, out));
+    };
+
+    // Helper to check the status of a response
+    fn check_status(response: &reqwest::Response, action: &str) -> anyhow::Result<()> {
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            reqwest::StatusCode::UNAUTHORIZED => Err(anyhow::anyhow!(
+                "Failed to {} logs: the server requires the admin key (--admin-key)",
+                action
+            )),
+            _ => Err(anyhow::anyhow!(
+                "Failed to {} logs (is the server running?)",
+                action
+            )),
         }
-
-        requests
     }
 
-    // Helper to fetch logs
-    async fn fetch_logs(url: &str) -> anyhow::Result<Vec<LogEntry>> {
-        let response = reqwest::get(url).await?;
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "Failed to fetch logs (is the server running?)"
-            ));
+    // Helper to print a request that changed the text
+    fn print_log(log: &LogEntry) {
+        let rules: Vec<&str> = log.matches.iter().map(|m| m.rule_id.as_str()).collect();
+        println!("IN:  {}", log.input);
+        println!("OUT: {}", log.output);
+        println!("     via {}", rules.join(" → "));
+        if let Some(extractor) = &log.extractor {
+            println!("     extracted by {}", extractor);
         }
-        let logs_response: LogsResponse = response.json().await?;
-        Ok(logs_response.logs)
+        println!();
     }
 
     if follow {
-        // Follow mode: continuously poll for new logs
+        // Follow mode: print each transformation pushed by the server
+        let mut query = vec![("matched_only", "true")];
+        if let Some(rule) = &rule {
+            query.push(("rule", rule));
+        }
+        let mut response = request(reqwest::Method::GET, &format!("{}/stream", logs_url))
+            .query(&query)
+            .send()
+            .await?;
+        check_status(&response, "stream")?;
+
         println!("=== Following Transformations (Ctrl+C to stop) ===\n");
 
-        let mut seen: HashSet<String> = HashSet::new();
-
-        loop {
-            let logs = fetch_logs(&logs_url).await?;
-            let requests = group_logs(&logs);
-
-            for (inp, out) in &requests {
-                if inp != out {
-                    // Create a unique key for this transformation
-                    let key = format!("{}|{}", inp, out);
-                    if !seen.contains(&key) {
-                        seen.insert(key);
-                        println!("IN:  {}", inp);
-                        println!("OUT: {}", out);
-                        println!();
-                    }
+        // Server-sent events are separated by a blank line
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+                let event: Vec<u8> = buffer.drain(..end + 2).collect();
+                let event = String::from_utf8_lossy(&event);
+
+                let name = event.lines().find_map(|l| l.strip_prefix("event:"));
+                let data: Vec<&str> = event
+                    .lines()
+                    .filter_map(|l| l.strip_prefix("data:"))
+                    .map(str::trim_start)
+                    .collect();
+                match name.map(str::trim) {
+                    Some("transformation") => print_log(&serde_json::from_str(&data.join("\n"))?),
+                    Some("lagged") => eprintln!("({} transformations missed)\n", data.join("")),
+                    _ => {},
                 }
             }
-
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
         }
+
+        println!("Server closed the stream.");
     } else {
         // One-shot mode
-        let logs = fetch_logs(&logs_url).await?;
-        let requests = group_logs(&logs);
+        let response = request(reqwest::Method::GET, &logs_url).send().await?;
+        check_status(&response, "fetch")?;
+        let logs = response.json::<LogsResponse>().await?.logs;
+        let logs: Vec<&LogEntry> = logs
+            .iter()
+            .filter(|log| {
+                rule.as_ref().map_or(true, |rule| {
+                    log.matches
+                        .iter()
+                        .any(|m| &m.rule_id == rule || m.qualified_id.as_ref() == Some(rule))
+                })
+            })
+            .collect();
 
         println!("=== Recent Transformations (Input → Output) ===\n");
 
-        let start = requests.len().saturating_sub(count);
-        for (inp, out) in &requests[start..] {
-            if inp != out {
-                println!("IN:  {}", inp);
-                println!("OUT: {}", out);
-                println!();
+        let start = logs.len().saturating_sub(count);
+        for log in &logs[start..] {
+            if log.input != log.output {
+                print_log(log);
             }
         }
 
-        if requests.is_empty() {
+        if logs.is_empty() {
             println!("(no logs yet)");
         }
 
         // Clear logs if requested
         if clear {
-            let client = reqwest::Client::new();
-            client.delete(&logs_url).send().await?;
+            let response = request(reqwest::Method::DELETE, &logs_url).send().await?;
+            check_status(&response, "clear")?;
             println!("Logs cleared.");
         }
     }
@@ -426,6 +851,42 @@ async fn run_logs(config: &Config, count: usize, follow: bool, clear: bool) -> a
     Ok(())
 }
 
+fn run_history(config: &Config, query: HistoryQuery, json: bool) -> anyhow::Result<()> {
+    let options = config.history_options().ok_or_else(|| {
+        anyhow::anyhow!(
+            "History is not enabled. Set \"history\": {{\"enabled\": true}} in the config."
+        )
+    })?;
+    let entries = History::open_read_only(options).query(&query)?;
+
+    if json {
+        for entry in &entries {
+            println!("{}", serde_json::to_string(entry)?);
+        }
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("(no matching history entries)");
+    }
+
+    for entry in &entries {
+        let rules: Vec<&str> = entry.matches.iter().map(|m| m.rule_id.as_str()).collect();
+        println!("[{}]", entry.timestamp);
+        println!("IN:  {}", entry.input);
+        println!("OUT: {}", entry.output);
+        if !rules.is_empty() {
+            println!("     via {}", rules.join(" → "));
+        }
+        if let Some(extractor) = &entry.extractor {
+            println!("     extracted by {}", extractor);
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
 fn run_setup(force: bool) -> anyhow::Result<()> {
     use std::fs;
     use std::path::Path;
@@ -461,9 +922,12 @@ fn run_setup(force: bool) -> anyhow::Result<()> {
     let mut copied = 0;
     let mut skipped = 0;
 
-    // Walk through rules directory and copy .json files
-    for entry in glob::glob("rules/**/*.json")? {
+    // Walk through rules directory and copy rules files (.json, .jsonc, .json5)
+    for entry in glob::glob("rules/**/*")? {
         let entry = entry?;
+        if !entry.is_file() || !is_rules_file(&entry) {
+            continue;
+        }
         let relative = entry.strip_prefix("rules").unwrap();
         let dest_file = dest_rules.join(relative);
 
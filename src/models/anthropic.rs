@@ -0,0 +1,341 @@
+//! Anthropic Messages-compatible request and response types
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::request::{Message, MessageContent, SamplingParams, last_user_text};
+use super::response::{Usage, response_id};
+
+/// Messages request (Anthropic-compatible)
+/// Other fields such as `tools` or `metadata` are accepted and ignored
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnthropicMessagesRequest {
+    /// Model name, echoed in the response
+    #[serde(default)]
+    #[schema(example = "local-rules")]
+    pub model: Option<String>,
+
+    /// Conversation turns; content is a string or an array of content blocks
+    #[serde(default)]
+    pub messages: Vec<Message>,
+
+    /// System prompt, a string or text blocks; searched by transcript extractors
+    #[serde(default)]
+    pub system: Option<MessageContent>,
+
+    /// Send the result as server-sent message events
+    #[serde(default)]
+    pub stream: bool,
+
+    /// Sampling parameters (`stop_sequences` as `stop`), forwarded to the
+    /// upstream stage
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
+}
+
+impl AnthropicMessagesRequest {
+    /// Extract the user content to process: the text of the last user turn,
+    /// with the same rules as for chat completions
+    pub fn extract_user_content(&self) -> Option<String> {
+        last_user_text(&self.messages)
+    }
+
+    /// Text of the system prompt
+    pub fn system_content(&self) -> Vec<String> {
+        self.system
+            .iter()
+            .filter_map(MessageContent::text)
+            .collect()
+    }
+}
+
+/// Message response (Anthropic-compatible)
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AnthropicMessage {
+    /// Unique message ID
+    pub id: String,
+    /// Object type (always "message")
+    #[serde(rename = "type")]
+    #[schema(example = "message")]
+    pub message_type: String,
+    /// Message role (always "assistant")
+    #[schema(example = "assistant")]
+    pub role: String,
+    /// Model name, as requested
+    #[schema(example = "local-rules")]
+    pub model: String,
+    /// Content blocks, a single text block (empty in `message_start` events)
+    pub content: Vec<AnthropicContentBlock>,
+    /// Why the message ended ("end_turn", null in `message_start` events)
+    #[schema(example = "end_turn")]
+    pub stop_reason: Option<String>,
+    /// Stop sequence that ended the message (always null)
+    pub stop_sequence: Option<String>,
+    /// Token usage
+    pub usage: AnthropicUsage,
+}
+
+impl AnthropicMessage {
+    /// Create a new message with the given content
+    pub fn new(input: &str, content: String, model: &str) -> Self {
+        Self {
+            id: response_id(input),
+            message_type: "message".to_string(),
+            role: "assistant".to_string(),
+            model: model.to_string(),
+            usage: Usage::estimate(input, &content).into(),
+            content: vec![AnthropicContentBlock::text(content)],
+            stop_reason: Some("end_turn".to_string()),
+            stop_sequence: None,
+        }
+    }
+
+    /// The events of a streamed message: the message without content, the
+    /// text block, the stop reason with the output tokens and the stop
+    pub fn stream(self) -> Vec<AnthropicStreamEvent> {
+        let mut start = self.clone();
+        start.content.clear();
+        start.stop_reason = None;
+        start.usage.output_tokens = 0;
+
+        let text = self
+            .content
+            .into_iter()
+            .map(|block| block.text)
+            .collect::<String>();
+
+        vec![
+            AnthropicStreamEvent::MessageStart { message: start },
+            AnthropicStreamEvent::ContentBlockStart {
+                index: 0,
+                content_block: AnthropicContentBlock::text(String::new()),
+            },
+            AnthropicStreamEvent::ContentBlockDelta {
+                index: 0,
+                delta: AnthropicTextDelta {
+                    delta_type: "text_delta".to_string(),
+                    text,
+                },
+            },
+            AnthropicStreamEvent::ContentBlockStop { index: 0 },
+            AnthropicStreamEvent::MessageDelta {
+                delta: AnthropicMessageDelta {
+                    stop_reason: self.stop_reason,
+                    stop_sequence: None,
+                },
+                usage: AnthropicDeltaUsage {
+                    output_tokens: self.usage.output_tokens,
+                },
+            },
+            AnthropicStreamEvent::MessageStop,
+        ]
+    }
+}
+
+/// A content block of a message
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AnthropicContentBlock {
+    /// Block type (always "text")
+    #[serde(rename = "type")]
+    #[schema(example = "text")]
+    pub block_type: String,
+    /// Transformed content
+    #[schema(example = "Hello / world")]
+    pub text: String,
+}
+
+impl AnthropicContentBlock {
+    fn text(text: String) -> Self {
+        Self {
+            block_type: "text".to_string(),
+            text,
+        }
+    }
+}
+
+/// Token usage (Anthropic-compatible), approximated like [`Usage`]
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AnthropicUsage {
+    /// Input tokens (estimated)
+    pub input_tokens: u32,
+    /// Output tokens (estimated)
+    pub output_tokens: u32,
+}
+
+impl From<Usage> for AnthropicUsage {
+    fn from(usage: Usage) -> Self {
+        Self {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+/// Server-sent event of a streamed message (Anthropic-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicStreamEvent {
+    /// The message with empty content
+    MessageStart { message: AnthropicMessage },
+    /// Start of a content block
+    ContentBlockStart {
+        index: u32,
+        content_block: AnthropicContentBlock,
+    },
+    /// Text appended to a content block
+    ContentBlockDelta {
+        index: u32,
+        delta: AnthropicTextDelta,
+    },
+    /// End of a content block
+    ContentBlockStop { index: u32 },
+    /// Stop reason and output tokens
+    MessageDelta {
+        delta: AnthropicMessageDelta,
+        usage: AnthropicDeltaUsage,
+    },
+    /// End of the message
+    MessageStop,
+}
+
+impl AnthropicStreamEvent {
+    /// Name of the event, the same as its `type`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MessageStart { .. } => "message_start",
+            Self::ContentBlockStart { .. } => "content_block_start",
+            Self::ContentBlockDelta { .. } => "content_block_delta",
+            Self::ContentBlockStop { .. } => "content_block_stop",
+            Self::MessageDelta { .. } => "message_delta",
+            Self::MessageStop => "message_stop",
+        }
+    }
+}
+
+/// Text added to a content block
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnthropicTextDelta {
+    /// Delta type (always "text_delta")
+    #[serde(rename = "type")]
+    #[schema(example = "text_delta")]
+    pub delta_type: String,
+    /// Text to append
+    #[schema(example = "Hello / world")]
+    pub text: String,
+}
+
+/// Top-level message changes at the end of a stream
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnthropicMessageDelta {
+    /// Why the message ended
+    #[schema(example = "end_turn")]
+    pub stop_reason: Option<String>,
+    /// Stop sequence that ended the message (always null)
+    pub stop_sequence: Option<String>,
+}
+
+/// Output tokens reported at the end of a stream
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnthropicDeltaUsage {
+    /// Output tokens (estimated)
+    pub output_tokens: u32,
+}
+
+/// Error response (Anthropic-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnthropicErrorResponse {
+    /// Object type (always "error")
+    #[serde(rename = "type")]
+    #[schema(example = "error")]
+    pub response_type: String,
+    /// Error details
+    pub error: AnthropicErrorDetail,
+}
+
+/// Error details (Anthropic-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnthropicErrorDetail {
+    /// Error category (invalid_request_error, not_found_error, api_error, ...)
+    #[serde(rename = "type")]
+    #[schema(example = "invalid_request_error")]
+    pub error_type: String,
+    /// Human-readable error message
+    #[schema(example = "No user content found in request")]
+    pub message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let request: AnthropicMessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet",
+            "max_tokens": 1024,
+            "system": "Be brief",
+            "messages": [
+                {"role": "user", "content": [{"type": "text", "text": "a slash b"}]},
+                {"role": "assistant", "content": "prefill"}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            request.extract_user_content(),
+            Some("a slash b".to_string())
+        );
+
+        let message = AnthropicMessage::new("a slash b", "a / b".to_string(), "claude-sonnet");
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["type"], "message");
+        assert_eq!(json["role"], "assistant");
+        assert_eq!(json["model"], "claude-sonnet");
+        assert_eq!(
+            json["content"],
+            serde_json::json!([{"type": "text", "text": "a / b"}])
+        );
+        assert_eq!(json["stop_reason"], "end_turn");
+        assert!(json["stop_sequence"].is_null());
+        assert_eq!(
+            json["usage"],
+            serde_json::json!({"input_tokens": 3, "output_tokens": 2})
+        );
+    }
+
+    #[test]
+    fn test_stream_events() {
+        let events = AnthropicMessage::new("a slash b", "a / b".to_string(), "m").stream();
+        let json: Vec<serde_json::Value> = events
+            .iter()
+            .map(|e| serde_json::to_value(e).unwrap())
+            .collect();
+
+        let names: Vec<&str> = events.iter().map(AnthropicStreamEvent::name).collect();
+        assert_eq!(
+            names,
+            [
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert!(json.iter().zip(names).all(|(e, name)| e["type"] == name));
+
+        assert_eq!(json[0]["message"]["content"], serde_json::json!([]));
+        assert!(json[0]["message"]["stop_reason"].is_null());
+        assert_eq!(json[0]["message"]["usage"]["input_tokens"], 3);
+        assert_eq!(
+            json[1]["content_block"],
+            serde_json::json!({"type": "text", "text": ""})
+        );
+        assert_eq!(
+            json[2]["delta"],
+            serde_json::json!({"type": "text_delta", "text": "a / b"})
+        );
+        assert_eq!(json[4]["delta"]["stop_reason"], "end_turn");
+        assert_eq!(json[4]["usage"]["output_tokens"], 2);
+    }
+}
@@ -1,11 +1,31 @@
 //! Data models for API requests and responses
 
+mod anthropic;
+mod ollama;
 mod request;
 mod response;
 
-pub use request::{ChatCompletionRequest, Message};
+pub use anthropic::{
+    AnthropicContentBlock, AnthropicDeltaUsage, AnthropicErrorDetail, AnthropicErrorResponse,
+    AnthropicMessage, AnthropicMessageDelta, AnthropicMessagesRequest, AnthropicStreamEvent,
+    AnthropicTextDelta, AnthropicUsage,
+};
+pub use ollama::{
+    OllamaChatRequest, OllamaChatResponse, OllamaErrorResponse, OllamaGenerateRequest,
+    OllamaGenerateResponse, OllamaMessage, OllamaModel, OllamaModelDetails, OllamaOptions,
+    OllamaStats, OllamaTagsResponse,
+};
+pub use request::{
+    BatchTransformRequest, ChatCompletionRequest, CompletionPrompt, CompletionRequest, ContentPart,
+    CreateRuleRequest, LogStreamFilter, Message, MessageContent, ResponsesInput, ResponsesRequest,
+    RulePreviewRequest, SamplingParams, StopSequences, StreamOptions, TransformRequest,
+};
 pub use response::{
-    ChatCompletionResponse, Choice, HealthResponse, ModelInfo, ModelsResponse, ResponseMessage,
-    RuleInfo, RuleToggleResponse, RulesResponse, TransformationLogEntry, TransformationLogResponse,
-    Usage,
+    BatchTransformResponse, ChatCompletionChunk, ChatCompletionResponse, Choice, ChunkChoice,
+    ChunkDelta, CompletionChoice, CompletionResponse, DEFAULT_MODEL, ErrorDetail, ErrorResponse,
+    HealthResponse, HistoryResponse, MatchSpan, ModelInfo, ModelsResponse, PackSummary,
+    ResponseMessage, ResponseOutputContent, ResponseOutputItem, ResponsesResponse, ResponsesUsage,
+    RuleInfo, RuleMatchEntry, RulePreviewEntry, RulePreviewResponse, RuleToggleResponse,
+    RuleTraceEntry, RuleValidationError, RulesBulkResponse, RulesResponse, SkippedRuleEntry,
+    TransformResponse, TransformationLogEntry, TransformationLogResponse, Usage,
 };
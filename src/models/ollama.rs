@@ -0,0 +1,377 @@
+//! Ollama-compatible request and response types
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use super::request::{Message, SamplingParams, StopSequences, last_user_text, system_texts};
+use super::response::{DEFAULT_MODEL, Usage};
+
+fn default_stream() -> bool {
+    true
+}
+
+/// Chat request (Ollama-compatible)
+/// Other fields such as `format` or `keep_alive` are accepted and ignored
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct OllamaChatRequest {
+    /// Model name, echoed in the response
+    #[serde(default)]
+    #[schema(example = "local-rules")]
+    pub model: Option<String>,
+
+    /// Chat messages
+    #[serde(default)]
+    pub messages: Vec<Message>,
+
+    /// Send the result as newline-delimited JSON (default true)
+    #[serde(default = "default_stream")]
+    pub stream: bool,
+
+    /// Model options; the sampling options are forwarded to the upstream stage
+    #[serde(default)]
+    pub options: OllamaOptions,
+}
+
+impl OllamaChatRequest {
+    /// Extract the user content to process: the last user message
+    pub fn extract_user_content(&self) -> Option<String> {
+        last_user_text(&self.messages)
+    }
+
+    /// Texts of the system messages, searched by transcript extractors
+    pub fn system_content(&self) -> Vec<String> {
+        system_texts(&self.messages)
+    }
+}
+
+/// Generate request (Ollama-compatible)
+/// Other fields such as `keep_alive` are accepted and ignored
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct OllamaGenerateRequest {
+    /// Model name, echoed in the response
+    #[serde(default)]
+    #[schema(example = "local-rules")]
+    pub model: Option<String>,
+
+    /// Prompt to transform
+    #[serde(default)]
+    #[schema(example = "Hello slash world")]
+    pub prompt: String,
+
+    /// System prompt, searched by transcript extractors
+    #[serde(default)]
+    pub system: Option<String>,
+
+    /// Send the result as newline-delimited JSON (default true)
+    #[serde(default = "default_stream")]
+    pub stream: bool,
+
+    /// Model options; the sampling options are forwarded to the upstream stage
+    #[serde(default)]
+    pub options: OllamaOptions,
+}
+
+/// Model options of a request (Ollama-compatible)
+/// Other options such as `seed` or `num_ctx` are accepted and ignored
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct OllamaOptions {
+    /// Sampling temperature
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// Nucleus sampling probability mass
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    /// Maximum number of tokens to generate (-1 for no limit)
+    #[serde(default)]
+    pub num_predict: Option<i64>,
+    /// Sequences that end the generation
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+}
+
+impl OllamaOptions {
+    /// The options as OpenAI sampling parameters
+    pub fn sampling(&self) -> SamplingParams {
+        SamplingParams {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_tokens: self.num_predict.and_then(|n| u32::try_from(n).ok()),
+            stop: self.stop.clone().map(StopSequences::Many),
+        }
+    }
+}
+
+/// Chat response or streamed chat chunk (Ollama-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OllamaChatResponse {
+    /// Model name, as requested
+    #[schema(example = "local-rules")]
+    pub model: String,
+    /// Time of the response (RFC 3339)
+    #[schema(example = "2026-02-02T10:15:30.123456Z")]
+    pub created_at: String,
+    /// Response message, or the part of it in this chunk
+    pub message: OllamaMessage,
+    /// Whether this is the last chunk
+    pub done: bool,
+    /// Statistics, only in the last chunk
+    #[serde(flatten)]
+    pub stats: Option<OllamaStats>,
+}
+
+impl OllamaChatResponse {
+    /// The lines of a response: the content and a final line with the stats
+    /// when streaming, otherwise a single line with both
+    pub fn lines(
+        input: &str,
+        content: String,
+        model: &str,
+        duration: Duration,
+        stream: bool,
+    ) -> Vec<Self> {
+        let stats = OllamaStats::new(input, &content, duration);
+        let line = |content: String, stats: Option<OllamaStats>| Self {
+            model: model.to_string(),
+            created_at: created_at(),
+            message: OllamaMessage {
+                role: "assistant".to_string(),
+                content,
+            },
+            done: stats.is_some(),
+            stats,
+        };
+
+        if stream {
+            vec![line(content, None), line(String::new(), Some(stats))]
+        } else {
+            vec![line(content, Some(stats))]
+        }
+    }
+}
+
+/// Generate response or streamed generate chunk (Ollama-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OllamaGenerateResponse {
+    /// Model name, as requested
+    #[schema(example = "local-rules")]
+    pub model: String,
+    /// Time of the response (RFC 3339)
+    #[schema(example = "2026-02-02T10:15:30.123456Z")]
+    pub created_at: String,
+    /// Transformed prompt, or the part of it in this chunk
+    #[schema(example = "Hello / world")]
+    pub response: String,
+    /// Whether this is the last chunk
+    pub done: bool,
+    /// Statistics, only in the last chunk
+    #[serde(flatten)]
+    pub stats: Option<OllamaStats>,
+}
+
+impl OllamaGenerateResponse {
+    /// The lines of a response: the content and a final line with the stats
+    /// when streaming, otherwise a single line with both
+    pub fn lines(
+        input: &str,
+        content: String,
+        model: &str,
+        duration: Duration,
+        stream: bool,
+    ) -> Vec<Self> {
+        let stats = OllamaStats::new(input, &content, duration);
+        let line = |response: String, stats: Option<OllamaStats>| Self {
+            model: model.to_string(),
+            created_at: created_at(),
+            response,
+            done: stats.is_some(),
+            stats,
+        };
+
+        if stream {
+            vec![line(content, None), line(String::new(), Some(stats))]
+        } else {
+            vec![line(content, Some(stats))]
+        }
+    }
+}
+
+/// Current time in the format Ollama uses
+fn created_at() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+/// Message of an Ollama chat response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OllamaMessage {
+    /// Message role (always "assistant")
+    #[schema(example = "assistant")]
+    pub role: String,
+    /// Transformed content
+    #[schema(example = "Hello / world")]
+    pub content: String,
+}
+
+/// Statistics in the last chunk of an Ollama response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OllamaStats {
+    /// Reason for completion (always "stop")
+    #[schema(example = "stop")]
+    pub done_reason: String,
+    /// Time spent applying the rules, in nanoseconds
+    #[schema(example = 420000)]
+    pub total_duration: u64,
+    /// Input tokens (estimated)
+    pub prompt_eval_count: u32,
+    /// Output tokens (estimated)
+    pub eval_count: u32,
+}
+
+impl OllamaStats {
+    fn new(input: &str, output: &str, duration: Duration) -> Self {
+        let usage = Usage::estimate(input, output);
+        Self {
+            done_reason: "stop".to_string(),
+            total_duration: u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX),
+            prompt_eval_count: usage.prompt_tokens,
+            eval_count: usage.completion_tokens,
+        }
+    }
+}
+
+/// Local models response (Ollama-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OllamaTagsResponse {
+    /// Available models
+    pub models: Vec<OllamaModel>,
+}
+
+/// Model info (Ollama-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OllamaModel {
+    /// Model name with tag
+    #[schema(example = "local-rules:latest")]
+    pub name: String,
+    /// Model name with tag
+    #[schema(example = "local-rules:latest")]
+    pub model: String,
+    /// When the model was last modified (RFC 3339): the server start
+    #[schema(example = "2026-02-02T00:00:00Z")]
+    pub modified_at: String,
+    /// Size in bytes (always 0)
+    pub size: u64,
+    /// SHA-256 digest of the model name
+    pub digest: String,
+    /// Model details
+    pub details: OllamaModelDetails,
+}
+
+/// Model details (Ollama-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OllamaModelDetails {
+    /// Model format
+    #[schema(example = "rules")]
+    pub format: String,
+    /// Model family
+    #[schema(example = "handy-local-rules")]
+    pub family: String,
+    /// Parameter size (always empty)
+    pub parameter_size: String,
+    /// Quantization level (always empty)
+    pub quantization_level: String,
+}
+
+impl OllamaTagsResponse {
+    /// The single model, reported as modified at `modified_at`
+    pub fn new(modified_at: DateTime<Utc>) -> Self {
+        let name = format!("{}:latest", DEFAULT_MODEL);
+        let mut digest = String::new();
+        for byte in Sha256::digest(name.as_bytes()) {
+            let _ = write!(digest, "{:02x}", byte);
+        }
+
+        Self {
+            models: vec![OllamaModel {
+                model: name.clone(),
+                name,
+                modified_at: modified_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                size: 0,
+                digest,
+                details: OllamaModelDetails {
+                    format: "rules".to_string(),
+                    family: "handy-local-rules".to_string(),
+                    parameter_size: String::new(),
+                    quantization_level: String::new(),
+                },
+            }],
+        }
+    }
+}
+
+/// Error response (Ollama-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OllamaErrorResponse {
+    /// Human-readable error message
+    #[schema(example = "No user content found in request")]
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_lines() {
+        let lines =
+            OllamaChatResponse::lines("a slash b", "a / b".to_string(), "m", Duration::ZERO, true);
+        let json: Vec<serde_json::Value> = lines
+            .iter()
+            .map(|l| serde_json::to_value(l).unwrap())
+            .collect();
+
+        assert_eq!(json.len(), 2);
+        assert_eq!(
+            json[0]["message"],
+            serde_json::json!({"role": "assistant", "content": "a / b"})
+        );
+        assert_eq!(json[0]["done"], false);
+        assert!(json[0].get("done_reason").is_none());
+        assert_eq!(json[1]["message"]["content"], "");
+        assert_eq!(json[1]["done"], true);
+        assert_eq!(json[1]["done_reason"], "stop");
+        assert_eq!(json[1]["prompt_eval_count"], 3);
+        assert_eq!(json[1]["eval_count"], 2);
+        assert!(json.iter().all(|l| l["model"] == "m"));
+
+        let lines =
+            OllamaChatResponse::lines("a slash b", "a / b".to_string(), "m", Duration::ZERO, false);
+        let json = serde_json::to_value(&lines[0]).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(json["message"]["content"], "a / b");
+        assert_eq!(json["done"], true);
+    }
+
+    #[test]
+    fn test_generate_request_defaults() {
+        let request: OllamaGenerateRequest =
+            serde_json::from_value(serde_json::json!({"model": "local-rules", "prompt": "a"}))
+                .unwrap();
+        assert!(request.stream);
+
+        let lines = OllamaGenerateResponse::lines("a", "a".to_string(), "m", Duration::ZERO, false);
+        let json = serde_json::to_value(&lines[0]).unwrap();
+        assert_eq!(json["response"], "a");
+        assert_eq!(json["done"], true);
+    }
+
+    #[test]
+    fn test_tags_report_modification_time() {
+        let modified_at = DateTime::parse_from_rfc3339("2026-10-18T09:30:00.25+02:00").unwrap();
+        let json = serde_json::to_value(OllamaTagsResponse::new(modified_at.into())).unwrap();
+        assert_eq!(json["models"][0]["modified_at"], "2026-10-18T07:30:00Z");
+        assert_eq!(json["models"][0]["name"], "local-rules:latest");
+    }
+}
@@ -1,10 +1,12 @@
 //! OpenAI-compatible request types
 
-use serde::Deserialize;
-use utoipa::ToSchema;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::rules::{Rule, RuleSelector, TransformationRecord};
 
 /// Chat completion request (OpenAI-compatible)
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Default, Deserialize, ToSchema)]
 pub struct ChatCompletionRequest {
     /// Chat messages
     #[serde(default)]
@@ -22,17 +24,205 @@ pub struct ChatCompletionRequest {
     /// Alternative: text field
     #[serde(default)]
     pub text: Option<String>,
+
+    /// Model name, echoed in the response
+    #[serde(default)]
+    #[schema(example = "local-rules")]
+    pub model: Option<String>,
+
+    /// Send the result as server-sent `chat.completion.chunk` events
+    #[serde(default)]
+    pub stream: bool,
+
+    /// Streaming options
+    #[serde(default)]
+    pub stream_options: Option<StreamOptions>,
+
+    /// Sampling parameters, forwarded to the upstream stage
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
+}
+
+/// Sampling parameters of a request, forwarded to the upstream stage if one
+/// is configured. The names of the other APIs are accepted as aliases.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema)]
+pub struct SamplingParams {
+    /// Sampling temperature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Nucleus sampling probability mass
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    /// Maximum number of tokens to generate
+    #[serde(
+        default,
+        alias = "max_completion_tokens",
+        alias = "max_output_tokens",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_tokens: Option<u32>,
+    /// Sequences that end the generation
+    #[serde(
+        default,
+        alias = "stop_sequences",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stop: Option<StopSequences>,
+}
+
+/// A single stop sequence or a list of them
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum StopSequences {
+    #[schema(example = "\n")]
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Options for streamed responses (OpenAI-compatible)
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct StreamOptions {
+    /// Send a final chunk with token usage and no choices
+    #[serde(default)]
+    pub include_usage: bool,
 }
 
 /// A single message in the chat
+/// Other fields such as `name` or `tool_calls` are accepted and ignored
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct Message {
-    /// Message role (system, user, assistant)
+    /// Message role (system, user, assistant, tool)
     #[schema(example = "user")]
     pub role: String,
-    /// Message content
+    /// Message content: a string, an array of content parts or null
+    #[serde(default)]
+    pub content: Option<MessageContent>,
+}
+
+/// Message content as a plain string or as content parts
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum MessageContent {
     #[schema(example = "Please transform: foo slash bar dot com")]
-    pub content: String,
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+/// A part of a message's content, e.g. `{"type": "text", "text": "..."}`
+/// Only text parts are used; images, audio and other parts are skipped
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ContentPart {
+    /// Part type (text, input_text, image_url, ...)
+    #[serde(rename = "type")]
+    #[schema(example = "text")]
+    pub part_type: String,
+    /// Text of a text part
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+impl Message {
+    /// Text of the message, with the text parts joined by line breaks
+    /// None for null content or content without text parts
+    pub fn text(&self) -> Option<String> {
+        self.content.as_ref()?.text()
+    }
+}
+
+impl MessageContent {
+    /// The text, with the text parts joined by line breaks, so that words
+    /// at the end and start of two parts stay apart
+    /// None for content without text parts
+    pub fn text(&self) -> Option<String> {
+        match self {
+            MessageContent::Text(text) => Some(text.clone()),
+            MessageContent::Parts(parts) => {
+                let texts: Vec<&str> = parts
+                    .iter()
+                    .filter(|p| matches!(p.part_type.as_str(), "text" | "input_text"))
+                    .filter_map(|p| p.text.as_deref())
+                    .collect();
+                (!texts.is_empty()).then(|| texts.join("\n"))
+            },
+        }
+    }
+}
+
+/// Request to add a new rule
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateRuleRequest {
+    /// The rule to add
+    pub rule: Rule,
+    /// Rules file to add the rule to (required when several files are loaded)
+    #[serde(default)]
+    #[schema(example = "rules/de/woerter.json")]
+    pub file: Option<String>,
+}
+
+/// Request to transform text with the rules
+/// A `text/plain` body is taken as the text, with the defaults for the other fields
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TransformRequest {
+    /// Text to transform
+    #[schema(example = "foo slash bar")]
+    pub text: String,
+    /// Profile selecting the rules (see `profiles` in the configuration)
+    #[serde(default)]
+    #[schema(example = "dictation")]
+    pub profile: Option<String>,
+    /// Return every rule evaluated with the text before and after it
+    #[serde(default)]
+    pub trace: bool,
+    /// Rules to apply or leave out, on top of the profile
+    #[serde(flatten)]
+    pub rules: RuleSelector,
+}
+
+impl From<String> for TransformRequest {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            profile: None,
+            trace: false,
+            rules: RuleSelector::default(),
+        }
+    }
+}
+
+/// Request to transform many texts with the rules
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchTransformRequest {
+    /// Texts to transform
+    #[schema(example = json!(["foo slash bar", "a dot"]))]
+    pub texts: Vec<String>,
+    /// Profile selecting the rules (see `profiles` in the configuration)
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Rules to apply or leave out, on top of the profile
+    #[serde(flatten)]
+    pub rules: RuleSelector,
+}
+
+/// Request to preview a draft rule set against sample inputs
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RulePreviewRequest {
+    /// Draft rules that replace (by id) or extend the current rules
+    #[serde(default)]
+    pub upsert: Vec<Rule>,
+    /// Rule ids to leave out of the draft
+    #[serde(default)]
+    pub remove: Vec<String>,
+    /// Inputs to run; defaults to the most recent inputs from the transformation log
+    #[serde(default)]
+    pub inputs: Option<Vec<String>>,
+    /// Number of recent inputs to use when `inputs` is not given
+    #[serde(default = "default_preview_limit")]
+    #[schema(example = 20)]
+    pub limit: usize,
+}
+
+fn default_preview_limit() -> usize {
+    20
 }
 
 impl ChatCompletionRequest {
@@ -48,13 +238,9 @@ impl ChatCompletionRequest {
     /// This prevents accidentally processing assistant prefills or system metadata.
     pub fn extract_user_content(&self) -> Option<String> {
         // Strategy 1: Get last user message (strict role check)
-        if let Some(messages) = &self.messages {
-            // Find the last user message - only accept role == "user"
-            if let Some(msg) = messages.iter().rev().find(|m| m.role == "user") {
-                return Some(msg.content.clone());
-            }
-            // No fallback to last message - this could be an assistant prefill
-            // or system metadata which we should not process
+        // A last user message without text falls through to the other fields
+        if let Some(text) = self.messages.as_deref().and_then(last_user_text) {
+            return Some(text);
         }
 
         // Strategy 2: prompt field
@@ -80,11 +266,172 @@ impl ChatCompletionRequest {
 
         None
     }
+
+    /// Texts of the system messages, searched by transcript extractors
+    pub fn system_content(&self) -> Vec<String> {
+        self.messages
+            .as_deref()
+            .map(system_texts)
+            .unwrap_or_default()
+    }
+}
+
+/// Texts of the system (and developer) messages, in order
+pub(super) fn system_texts(messages: &[Message]) -> Vec<String> {
+    messages
+        .iter()
+        .filter(|m| matches!(m.role.as_str(), "system" | "developer"))
+        .filter_map(Message::text)
+        .collect()
+}
+
+/// Text of the last user message
+///
+/// Only messages with role "user" are considered. There is no fallback to the
+/// last message regardless of role: it could be an assistant prefill or system
+/// metadata which we should not process.
+pub(super) fn last_user_text(messages: &[Message]) -> Option<String> {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .and_then(Message::text)
+}
+
+/// Legacy completion request (OpenAI-compatible)
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CompletionRequest {
+    /// Prompt to transform, or several prompts for one choice each
+    pub prompt: CompletionPrompt,
+
+    /// Model name, echoed in the response
+    #[serde(default)]
+    #[schema(example = "local-rules")]
+    pub model: Option<String>,
+
+    /// Streaming is not supported for legacy completions
+    #[serde(default)]
+    pub stream: bool,
+
+    /// Sampling parameters, forwarded to the upstream stage
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
+}
+
+/// A single prompt or a batch of prompts
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum CompletionPrompt {
+    #[schema(example = "Hello slash world")]
+    Text(String),
+    Batch(Vec<String>),
+}
+
+impl CompletionRequest {
+    /// The prompts to process, None if there is no non-empty prompt
+    pub fn prompts(&self) -> Option<Vec<&str>> {
+        let prompts: Vec<&str> = match &self.prompt {
+            CompletionPrompt::Text(text) => vec![text.as_str()],
+            CompletionPrompt::Batch(texts) => texts.iter().map(String::as_str).collect(),
+        };
+        prompts.iter().any(|p| !p.is_empty()).then_some(prompts)
+    }
+}
+
+/// Responses API request (OpenAI-compatible)
+/// Other fields such as `tools` are accepted and ignored
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ResponsesRequest {
+    /// Input text or input messages
+    pub input: ResponsesInput,
+
+    /// System instructions, searched by transcript extractors
+    #[serde(default)]
+    pub instructions: Option<String>,
+
+    /// Model name, echoed in the response
+    #[serde(default)]
+    #[schema(example = "local-rules")]
+    pub model: Option<String>,
+
+    /// Streaming is not supported for the Responses API
+    #[serde(default)]
+    pub stream: bool,
+
+    /// Sampling parameters (`max_output_tokens` as `max_tokens`), forwarded
+    /// to the upstream stage
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
+}
+
+/// Input of a Responses API request: a string or a list of message items
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum ResponsesInput {
+    #[schema(example = "Hello slash world")]
+    Text(String),
+    Messages(Vec<Message>),
+}
+
+impl ResponsesRequest {
+    /// Extract the user content to process: the input string, or the last
+    /// user message of the input items (same rules as for chat completions)
+    pub fn extract_user_content(&self) -> Option<String> {
+        match &self.input {
+            ResponsesInput::Text(text) => (!text.is_empty()).then(|| text.clone()),
+            ResponsesInput::Messages(messages) => last_user_text(messages),
+        }
+    }
+
+    /// The input messages, none for an input string
+    pub fn messages(&self) -> &[Message] {
+        match &self.input {
+            ResponsesInput::Text(_) => &[],
+            ResponsesInput::Messages(messages) => messages,
+        }
+    }
+
+    /// The instructions and the texts of system input messages
+    pub fn system_content(&self) -> Vec<String> {
+        let mut texts: Vec<String> = self.instructions.iter().cloned().collect();
+        if let ResponsesInput::Messages(messages) = &self.input {
+            texts.extend(system_texts(messages));
+        }
+        texts
+    }
+}
+
+/// Filters for the live transformation stream
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogStreamFilter {
+    /// Only requests in which this rule matched (`id` or `pack:id`)
+    #[serde(default)]
+    #[param(example = "de-punkt")]
+    pub rule: Option<String>,
+
+    /// Only requests in which at least one rule matched
+    #[serde(default)]
+    pub matched_only: bool,
+}
+
+impl LogStreamFilter {
+    /// Whether a request passes the filter
+    pub fn matches(&self, record: &TransformationRecord) -> bool {
+        if self.matched_only && record.matches.is_empty() {
+            return false;
+        }
+        match &self.rule {
+            Some(rule) => record.matches.iter().any(|m| m.has_id(rule)),
+            None => true,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rules::RuleMatch;
 
     #[test]
     fn test_extract_from_messages() {
@@ -92,16 +439,14 @@ mod tests {
             messages: Some(vec![
                 Message {
                     role: "system".to_string(),
-                    content: "You are helpful.".to_string(),
+                    content: Some(MessageContent::Text("You are helpful.".to_string())),
                 },
                 Message {
                     role: "user".to_string(),
-                    content: "Hello world".to_string(),
+                    content: Some(MessageContent::Text("Hello world".to_string())),
                 },
             ]),
-            prompt: None,
-            input: None,
-            text: None,
+            ..Default::default()
         };
 
         assert_eq!(
@@ -113,10 +458,8 @@ mod tests {
     #[test]
     fn test_extract_from_prompt() {
         let request = ChatCompletionRequest {
-            messages: None,
             prompt: Some("Test prompt".to_string()),
-            input: None,
-            text: None,
+            ..Default::default()
         };
 
         assert_eq!(
@@ -133,16 +476,14 @@ mod tests {
             messages: Some(vec![
                 Message {
                     role: "user".to_string(),
-                    content: "Real Input".to_string(),
+                    content: Some(MessageContent::Text("Real Input".to_string())),
                 },
                 Message {
                     role: "assistant".to_string(),
-                    content: "Prefill".to_string(),
+                    content: Some(MessageContent::Text("Prefill".to_string())),
                 },
             ]),
-            prompt: None,
-            input: None,
-            text: None,
+            ..Default::default()
         };
 
         // Should skip "Prefill" and find "Real Input"
@@ -151,4 +492,122 @@ mod tests {
             Some("Real Input".to_string())
         );
     }
+
+    #[test]
+    fn test_extract_from_content_parts() {
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "messages": [
+                {"role": "system", "content": null},
+                {"role": "user", "name": "me", "content": [
+                    {"type": "text", "text": "foo slash"},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}},
+                    {"type": "text", "text": "bar"}
+                ]},
+                {"role": "assistant", "content": null, "tool_calls": [{"id": "call_1", "type": "function"}]}
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            request.extract_user_content(),
+            Some("foo slash\nbar".to_string())
+        );
+
+        // A user message without text falls back to the other fields
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "messages": [{"role": "user", "content": null}],
+            "prompt": "fallback"
+        }))
+        .unwrap();
+        assert_eq!(request.extract_user_content(), Some("fallback".to_string()));
+    }
+
+    #[test]
+    fn test_text_parts_stay_apart() {
+        let content: MessageContent = serde_json::from_value(serde_json::json!([
+            {"type": "text", "text": "hallo"},
+            {"type": "input_text", "text": "welt punkt"}
+        ]))
+        .unwrap();
+        assert_eq!(content.text(), Some("hallo\nwelt punkt".to_string()));
+    }
+
+    #[test]
+    fn test_completion_prompts() {
+        let request: CompletionRequest =
+            serde_json::from_value(serde_json::json!({"prompt": "foo slash bar"})).unwrap();
+        assert_eq!(request.prompts(), Some(vec!["foo slash bar"]));
+
+        let request: CompletionRequest =
+            serde_json::from_value(serde_json::json!({"prompt": ["a", ""]})).unwrap();
+        assert_eq!(request.prompts(), Some(vec!["a", ""]));
+
+        let request: CompletionRequest =
+            serde_json::from_value(serde_json::json!({"prompt": [""]})).unwrap();
+        assert_eq!(request.prompts(), None);
+    }
+
+    #[test]
+    fn test_extract_from_responses_input() {
+        let request: ResponsesRequest =
+            serde_json::from_value(serde_json::json!({"input": "foo slash bar"})).unwrap();
+        assert_eq!(
+            request.extract_user_content(),
+            Some("foo slash bar".to_string())
+        );
+
+        let request: ResponsesRequest = serde_json::from_value(serde_json::json!({
+            "instructions": "Be brief",
+            "input": [
+                {"type": "message", "role": "user", "content": [{"type": "input_text", "text": "first"}]},
+                {"type": "message", "role": "user", "content": [{"type": "input_text", "text": "second"}]},
+                {"type": "message", "role": "assistant", "content": "prefill"}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(request.extract_user_content(), Some("second".to_string()));
+
+        let request: ResponsesRequest =
+            serde_json::from_value(serde_json::json!({"input": ""})).unwrap();
+        assert_eq!(request.extract_user_content(), None);
+    }
+
+    #[test]
+    fn test_log_stream_filter() {
+        let record = |rules: &[&str]| TransformationRecord {
+            id: "id".to_string(),
+            timestamp: chrono::Utc::now(),
+            duration: std::time::Duration::ZERO,
+            input: "in".to_string(),
+            output: "out".to_string(),
+            matches: rules
+                .iter()
+                .map(|rule| RuleMatch {
+                    rule_id: rule.to_string(),
+                    qualified_id: format!("basics:{}", rule),
+                    rule_type: "Regex".to_string(),
+                    group: None,
+                    output: "out".to_string(),
+                })
+                .collect(),
+            skipped: Vec::new(),
+            extractor: None,
+        };
+
+        assert!(LogStreamFilter::default().matches(&record(&[])));
+
+        let matched_only = LogStreamFilter {
+            matched_only: true,
+            ..Default::default()
+        };
+        assert!(!matched_only.matches(&record(&[])));
+        assert!(matched_only.matches(&record(&["slash"])));
+
+        let by_rule = LogStreamFilter {
+            rule: Some("dot".to_string()),
+            ..Default::default()
+        };
+        assert!(!by_rule.matches(&record(&["slash"])));
+        assert!(by_rule.matches(&record(&["slash", "dot"])));
+    }
 }
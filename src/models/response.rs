@@ -1,9 +1,11 @@
 //! OpenAI-compatible response types
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::rules::{PackInfo, Rule, RuleMatch, RuleStep, SkippedRule, TransformationRecord};
+
 /// Namespace UUID for generating deterministic response IDs
 /// This is a custom namespace for handy-local-rules
 const RESPONSE_ID_NAMESPACE: Uuid = Uuid::from_bytes([
@@ -16,8 +18,17 @@ const RESPONSE_ID_NAMESPACE: Uuid = Uuid::from_bytes([
 pub struct ChatCompletionResponse {
     /// Unique response ID
     pub id: String,
-    /// Object type
+    /// Object type (always "chat.completion")
+    #[schema(example = "chat.completion")]
     pub object: String,
+    /// Unix timestamp of the response
+    pub created: i64,
+    /// Model name, as requested
+    #[schema(example = "local-rules")]
+    pub model: String,
+    /// Identifies the server version that produced the response
+    #[schema(example = "handy-local-rules-0.0.1")]
+    pub system_fingerprint: String,
     /// Response choices
     pub choices: Vec<Choice>,
     /// Token usage statistics
@@ -27,14 +38,14 @@ pub struct ChatCompletionResponse {
 impl ChatCompletionResponse {
     /// Create a new response with the given content
     /// Uses deterministic ID based on input content for caching/debugging
-    pub fn new(input: &str, content: String) -> Self {
-        // Generate deterministic UUID based on input content
-        // This helps with client-side caching and debugging
-        let id = Uuid::new_v5(&RESPONSE_ID_NAMESPACE, input.as_bytes());
-
+    pub fn new(input: &str, content: String, model: &str) -> Self {
         Self {
-            id: format!("local-{}", id),
+            id: response_id(input),
             object: "chat.completion".to_string(),
+            created: chrono::Utc::now().timestamp(),
+            model: model.to_string(),
+            system_fingerprint: SYSTEM_FINGERPRINT.to_string(),
+            usage: Usage::estimate(input, &content),
             choices: vec![Choice {
                 index: 0,
                 message: ResponseMessage {
@@ -43,11 +54,285 @@ impl ChatCompletionResponse {
                 },
                 finish_reason: Some("stop".to_string()),
             }],
-            usage: Usage::default(),
         }
     }
 }
 
+/// Legacy completion response (OpenAI-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompletionResponse {
+    /// Unique response ID
+    pub id: String,
+    /// Object type (always "text_completion")
+    #[schema(example = "text_completion")]
+    pub object: String,
+    /// Unix timestamp of the response
+    pub created: i64,
+    /// Model name, as requested
+    #[schema(example = "local-rules")]
+    pub model: String,
+    /// Identifies the server version that produced the response
+    #[schema(example = "handy-local-rules-0.0.1")]
+    pub system_fingerprint: String,
+    /// One choice per prompt
+    pub choices: Vec<CompletionChoice>,
+    /// Token usage statistics
+    pub usage: Usage,
+}
+
+impl CompletionResponse {
+    /// Create a response with one choice per prompt and its output
+    pub fn new(results: Vec<(&str, String)>, model: &str) -> Self {
+        let inputs: Vec<&str> = results.iter().map(|(input, _)| *input).collect();
+        let usage = results
+            .iter()
+            .map(|(input, output)| Usage::estimate(input, output))
+            .fold(Usage::default(), |total, usage| total + usage);
+
+        Self {
+            id: response_id(&inputs.join("\n")),
+            object: "text_completion".to_string(),
+            created: chrono::Utc::now().timestamp(),
+            model: model.to_string(),
+            system_fingerprint: SYSTEM_FINGERPRINT.to_string(),
+            usage,
+            choices: results
+                .into_iter()
+                .zip(0..)
+                .map(|((_, text), index)| CompletionChoice {
+                    text,
+                    index,
+                    finish_reason: Some("stop".to_string()),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A single choice in a legacy completion response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompletionChoice {
+    /// Transformed prompt
+    #[schema(example = "Hello / world")]
+    pub text: String,
+    /// Index of the prompt
+    pub index: u32,
+    /// Reason for completion
+    pub finish_reason: Option<String>,
+}
+
+/// Responses API response (OpenAI-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ResponsesResponse {
+    /// Unique response ID
+    pub id: String,
+    /// Object type (always "response")
+    #[schema(example = "response")]
+    pub object: String,
+    /// Unix timestamp of the response
+    pub created_at: i64,
+    /// Response status (always "completed")
+    #[schema(example = "completed")]
+    pub status: String,
+    /// Model name, as requested
+    #[schema(example = "local-rules")]
+    pub model: String,
+    /// Output items, a single assistant message
+    pub output: Vec<ResponseOutputItem>,
+    /// Token usage statistics
+    pub usage: ResponsesUsage,
+}
+
+impl ResponsesResponse {
+    /// Create a new response with the given content
+    pub fn new(input: &str, content: String, model: &str) -> Self {
+        let id = response_id(input);
+        Self {
+            object: "response".to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            status: "completed".to_string(),
+            model: model.to_string(),
+            usage: Usage::estimate(input, &content).into(),
+            output: vec![ResponseOutputItem {
+                item_type: "message".to_string(),
+                id: format!("{}-msg", id),
+                status: "completed".to_string(),
+                role: "assistant".to_string(),
+                content: vec![ResponseOutputContent {
+                    content_type: "output_text".to_string(),
+                    text: content,
+                    annotations: Vec::new(),
+                }],
+            }],
+            id,
+        }
+    }
+}
+
+/// An output item of a Responses API response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ResponseOutputItem {
+    /// Item type (always "message")
+    #[serde(rename = "type")]
+    #[schema(example = "message")]
+    pub item_type: String,
+    /// Item ID
+    pub id: String,
+    /// Item status (always "completed")
+    #[schema(example = "completed")]
+    pub status: String,
+    /// Message role (always "assistant")
+    #[schema(example = "assistant")]
+    pub role: String,
+    /// Message content
+    pub content: Vec<ResponseOutputContent>,
+}
+
+/// Content of an output message
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ResponseOutputContent {
+    /// Content type (always "output_text")
+    #[serde(rename = "type")]
+    #[schema(example = "output_text")]
+    pub content_type: String,
+    /// Transformed content
+    #[schema(example = "Hello / world")]
+    pub text: String,
+    /// Annotations (always empty)
+    pub annotations: Vec<String>,
+}
+
+/// Token usage in the Responses API format, approximated like [`Usage`]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ResponsesUsage {
+    /// Input tokens (estimated)
+    pub input_tokens: u32,
+    /// Output tokens (estimated)
+    pub output_tokens: u32,
+    /// Total tokens (estimated)
+    pub total_tokens: u32,
+}
+
+impl From<Usage> for ResponsesUsage {
+    fn from(usage: Usage) -> Self {
+        Self {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+/// Deterministic response ID based on the input content
+/// This helps with client-side caching and debugging
+pub(super) fn response_id(input: &str) -> String {
+    format!(
+        "local-{}",
+        Uuid::new_v5(&RESPONSE_ID_NAMESPACE, input.as_bytes())
+    )
+}
+
+/// Model name reported when the request names none
+pub const DEFAULT_MODEL: &str = "local-rules";
+
+/// System fingerprint reported in completions
+const SYSTEM_FINGERPRINT: &str = concat!("handy-local-rules-", env!("CARGO_PKG_VERSION"));
+
+/// Streamed chat completion chunk (OpenAI-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatCompletionChunk {
+    /// Response ID, the same for every chunk of a response
+    pub id: String,
+    /// Object type (always "chat.completion.chunk")
+    #[schema(example = "chat.completion.chunk")]
+    pub object: String,
+    /// Unix timestamp of the response
+    pub created: i64,
+    /// Model name
+    #[schema(example = "local-rules")]
+    pub model: String,
+    /// Identifies the server version that produced the response
+    #[schema(example = "handy-local-rules-0.0.1")]
+    pub system_fingerprint: String,
+    /// Choice deltas (empty in the final usage chunk)
+    pub choices: Vec<ChunkChoice>,
+    /// Token usage, only in the final chunk when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+impl ChatCompletionChunk {
+    /// The chunks of a streamed response: the role, the content, the finish
+    /// reason and (if requested) the usage
+    pub fn stream(input: &str, content: String, model: &str, include_usage: bool) -> Vec<Self> {
+        let id = response_id(input);
+        let created = chrono::Utc::now().timestamp();
+        let usage = Usage::estimate(input, &content);
+        let chunk = |delta: ChunkDelta, finish_reason: Option<&str>| Self {
+            id: id.clone(),
+            object: "chat.completion.chunk".to_string(),
+            created,
+            model: model.to_string(),
+            system_fingerprint: SYSTEM_FINGERPRINT.to_string(),
+            choices: vec![ChunkChoice {
+                index: 0,
+                delta,
+                finish_reason: finish_reason.map(String::from),
+            }],
+            usage: None,
+        };
+
+        let mut chunks = vec![
+            chunk(
+                ChunkDelta {
+                    role: Some("assistant".to_string()),
+                    content: Some(String::new()),
+                },
+                None,
+            ),
+            chunk(
+                ChunkDelta {
+                    role: None,
+                    content: Some(content),
+                },
+                None,
+            ),
+            chunk(ChunkDelta::default(), Some("stop")),
+        ];
+
+        if include_usage {
+            let mut last = chunk(ChunkDelta::default(), None);
+            last.choices.clear();
+            last.usage = Some(usage);
+            chunks.push(last);
+        }
+
+        chunks
+    }
+}
+
+/// A choice delta in a streamed chunk
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChunkChoice {
+    /// Choice index
+    pub index: u32,
+    /// Content added by this chunk
+    pub delta: ChunkDelta,
+    /// Reason for completion, set in the last chunk of the choice
+    pub finish_reason: Option<String>,
+}
+
+/// Part of the response message
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ChunkDelta {
+    /// Message role, only in the first chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Content to append
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
 /// A single choice in the response
 #[derive(Debug, Serialize, ToSchema)]
 pub struct Choice {
@@ -68,17 +353,71 @@ pub struct ResponseMessage {
     pub content: String,
 }
 
-/// Token usage (always zero for rule-based processing)
+/// Token usage, approximated since no tokenizer is involved
 #[derive(Debug, Serialize, Default, ToSchema)]
 pub struct Usage {
-    /// Input tokens (always 0)
+    /// Input tokens (estimated)
     pub prompt_tokens: u32,
-    /// Output tokens (always 0)
+    /// Output tokens (estimated)
     pub completion_tokens: u32,
-    /// Total tokens (always 0)
+    /// Total tokens (estimated)
     pub total_tokens: u32,
 }
 
+impl Usage {
+    /// Estimate usage from the input and output text at roughly four
+    /// characters per token, the usual rule of thumb for OpenAI tokenizers
+    pub fn estimate(prompt: &str, completion: &str) -> Self {
+        let tokens =
+            |text: &str| u32::try_from(text.chars().count().div_ceil(4)).unwrap_or(u32::MAX);
+        let prompt_tokens = tokens(prompt);
+        let completion_tokens = tokens(completion);
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens.saturating_add(completion_tokens),
+        }
+    }
+}
+
+impl std::ops::Add for Usage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            prompt_tokens: self.prompt_tokens.saturating_add(other.prompt_tokens),
+            completion_tokens: self
+                .completion_tokens
+                .saturating_add(other.completion_tokens),
+            total_tokens: self.total_tokens.saturating_add(other.total_tokens),
+        }
+    }
+}
+
+/// Error response (OpenAI-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Error details
+    pub error: ErrorDetail,
+}
+
+/// Error details (OpenAI-compatible)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorDetail {
+    /// Human-readable error message
+    #[schema(example = "Rule 'slash' not found")]
+    pub message: String,
+    /// Error category (invalid_request_error, server_error, ...)
+    #[serde(rename = "type")]
+    #[schema(example = "invalid_request_error")]
+    pub error_type: String,
+    /// Request parameter the error relates to
+    pub param: Option<String>,
+    /// Machine-readable error code
+    #[schema(example = "rule_not_found")]
+    pub code: Option<String>,
+}
+
 /// Health check response
 #[derive(Debug, Serialize, ToSchema)]
 pub struct HealthResponse {
@@ -141,24 +480,75 @@ pub struct TransformationLogResponse {
     pub logs: Vec<TransformationLogEntry>,
 }
 
-/// A single transformation log entry
+/// Transformation history response
 #[derive(Debug, Serialize, ToSchema)]
+pub struct HistoryResponse {
+    /// Number of entries returned
+    pub count: usize,
+    /// Matching requests, oldest first
+    pub entries: Vec<TransformationLogEntry>,
+}
+
+/// A single request in the transformation log
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct TransformationLogEntry {
+    /// Unique id of the request
+    #[schema(example = "6f1c2a9e-3b4d-4e5f-8a7b-9c0d1e2f3a4b")]
+    pub id: String,
+    /// When the request was processed (RFC 3339)
+    #[schema(example = "2026-02-02T10:15:30.123Z")]
+    pub timestamp: String,
+    /// Time spent applying all rules, in milliseconds
+    #[schema(example = 0.42)]
+    pub duration_ms: f64,
+    /// Original input text
+    #[schema(example = "foo slash bar")]
+    pub input: String,
+    /// Final output text
+    #[schema(example = "foo / bar")]
+    pub output: String,
+    /// Rules that changed the text, in the order they were applied
+    pub matches: Vec<RuleMatchEntry>,
+    /// Rules that were not applied because of a group or a condition
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedRuleEntry>,
+    /// Extractor that pulled the input out of the prompt, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "handy-prompt")]
+    pub extractor: Option<String>,
+}
+
+/// A rule that changed the text during a request
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RuleMatchEntry {
     /// Rule ID that was applied
     #[schema(example = "slash")]
     pub rule_id: String,
-    /// Type of rule (Regex, Shell, Function)
+    /// Rule ID with its pack (or rules file) as namespace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "basics:slash")]
+    pub qualified_id: Option<String>,
+    /// Type of rule (Regex, Literal, Shell, Function)
     #[schema(example = "Regex")]
     pub rule_type: String,
-    /// Input text before transformation
-    #[schema(example = "foo slash bar")]
-    pub input: String,
-    /// Output text after transformation
+    /// Group of the rule, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "dev-commands")]
+    pub group: Option<String>,
+    /// Text after the rule was applied
     #[schema(example = "foo / bar")]
     pub output: String,
-    /// Whether the rule matched and changed the text
-    #[schema(example = true)]
-    pub matched: bool,
+}
+
+/// A rule that was skipped during a request
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SkippedRuleEntry {
+    /// Rule ID that was skipped
+    #[schema(example = "dev-ac-lower")]
+    pub rule_id: String,
+    /// Why the rule was skipped, e.g. because another member of its group matched
+    #[schema(example = "group 'dev-commands' already matched by 'dev-ac'")]
+    pub reason: String,
 }
 
 /// Rules list response
@@ -168,6 +558,54 @@ pub struct RulesResponse {
     pub rules: Vec<RuleInfo>,
     /// Total count
     pub count: usize,
+    /// Rules files that rules can be added to
+    pub files: Vec<String>,
+    /// Rule packs declared by the loaded rules files
+    pub packs: Vec<PackSummary>,
+}
+
+/// A rule pack with the files and number of rules it contributes
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PackSummary {
+    /// Pack metadata
+    #[serde(flatten)]
+    pub pack: PackInfo,
+    /// Files declaring the pack
+    pub files: Vec<String>,
+    /// Number of loaded rules in the pack
+    #[schema(example = 29)]
+    pub rule_count: usize,
+}
+
+impl PackSummary {
+    /// Collect the packs of a set of rules, in order of first appearance
+    pub fn collect(rules: &[Rule]) -> Vec<Self> {
+        let mut packs: Vec<Self> = Vec::new();
+        for rule in rules {
+            let Some(pack) = &rule.pack else {
+                continue;
+            };
+            let index = match packs.iter().position(|p| p.pack.name == pack.name) {
+                Some(index) => index,
+                None => {
+                    packs.push(Self {
+                        pack: (**pack).clone(),
+                        files: Vec::new(),
+                        rule_count: 0,
+                    });
+                    packs.len() - 1
+                },
+            };
+            let summary = &mut packs[index];
+            summary.rule_count += 1;
+            if let Some(file) = &rule.source_file {
+                if !summary.files.contains(file) {
+                    summary.files.push(file.clone());
+                }
+            }
+        }
+        packs
+    }
 }
 
 /// Rule info for API response
@@ -176,9 +614,15 @@ pub struct RuleInfo {
     /// Rule ID
     #[schema(example = "slash")]
     pub id: String,
+    /// Rule ID namespaced by pack, unique even if packs share IDs
+    #[schema(example = "de-satzzeichen:slash")]
+    pub qualified_id: String,
     /// Description
     #[schema(example = "spoken 'slash' -> /")]
     pub description: Option<String>,
+    /// Tags
+    #[schema(example = json!(["punctuation"]))]
+    pub tags: Vec<String>,
     /// Rule type (regex, shell, function)
     #[schema(example = "regex")]
     pub rule_type: String,
@@ -194,6 +638,59 @@ pub struct RuleInfo {
     /// Is rule enabled?
     #[schema(example = true)]
     pub enabled: bool,
+    /// Case-insensitive matching (regex and literal rules)
+    #[schema(example = false)]
+    pub ignore_case: bool,
+    /// Stop processing further rules after this rule matches
+    #[schema(example = false)]
+    pub stop_on_match: bool,
+    /// Mutually exclusive group (first match wins)
+    #[schema(example = "dev-commands")]
+    pub group: Option<String>,
+    /// Rules that must have matched earlier in the same request
+    #[schema(example = json!(["dev-ac"]))]
+    pub requires_matched: Vec<String>,
+    /// Rules that must not have matched earlier in the same request
+    #[schema(example = json!([]))]
+    pub requires_not_matched: Vec<String>,
+    /// Timeout in milliseconds (shell rules)
+    #[schema(example = 5000)]
+    pub timeout_ms: u64,
+    /// File the rule was loaded from
+    #[schema(example = "rules.json")]
+    pub source_file: Option<String>,
+    /// Name of the pack the rule belongs to
+    #[schema(example = "de-satzzeichen")]
+    pub pack: Option<String>,
+    /// Fields patched by the overrides file
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!(["enabled"]))]
+    pub overridden: Vec<String>,
+}
+
+impl From<Rule> for RuleInfo {
+    fn from(r: Rule) -> Self {
+        Self {
+            qualified_id: r.qualified_id(),
+            id: r.id,
+            description: r.description,
+            tags: r.tags,
+            rule_type: format!("{:?}", r.rule_type).to_lowercase(),
+            pattern: r.pattern,
+            replacement: r.replacement,
+            priority: r.priority,
+            enabled: r.enabled,
+            ignore_case: r.ignore_case,
+            stop_on_match: r.stop_on_match,
+            group: r.group,
+            requires_matched: r.requires_matched,
+            requires_not_matched: r.requires_not_matched,
+            timeout_ms: r.timeout_ms,
+            source_file: r.source_file,
+            pack: r.pack.map(|p| p.name.clone()),
+            overridden: r.overridden,
+        }
+    }
 }
 
 /// Response for rule toggle/update operations
@@ -209,3 +706,320 @@ pub struct RuleToggleResponse {
     #[schema(example = "Rule 'slash' is now enabled")]
     pub message: String,
 }
+
+/// Response for enabling or disabling rules in bulk
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RulesBulkResponse {
+    /// New enabled state
+    #[schema(example = false)]
+    pub enabled: bool,
+    /// Number of rules whose state changed
+    #[schema(example = 2)]
+    pub count: usize,
+    /// Qualified IDs of the rules whose state changed
+    #[schema(example = json!(["de-satzzeichen:de-punkt", "de-satzzeichen:de-komma"]))]
+    pub rules: Vec<String>,
+}
+
+/// Response for a rule set preview
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RulePreviewResponse {
+    /// Validation errors for draft rules (invalid rules are left out of the preview)
+    pub errors: Vec<RuleValidationError>,
+    /// Preview per input
+    pub results: Vec<RulePreviewEntry>,
+    /// Number of inputs whose output would change
+    #[schema(example = 1)]
+    pub changed: usize,
+}
+
+/// A validation error for a draft rule
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RuleValidationError {
+    /// Rule ID
+    #[schema(example = "slash")]
+    pub id: String,
+    /// What is wrong with the rule
+    #[schema(example = "Invalid regex in rule 'slash': unclosed group")]
+    pub message: String,
+}
+
+/// Output of one input under the current and the draft rules
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RulePreviewEntry {
+    /// Original input
+    #[schema(example = "foo slash bar")]
+    pub input: String,
+    /// Output with the current rules
+    #[schema(example = "foo slash bar")]
+    pub current: String,
+    /// Output with the draft rules
+    #[schema(example = "foo / bar")]
+    pub preview: String,
+    /// Whether the draft changes the output
+    #[schema(example = true)]
+    pub changed: bool,
+}
+
+/// Response for a text transformation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TransformResponse {
+    /// Transformed text
+    #[schema(example = "foo / bar")]
+    pub output: String,
+    /// Time spent applying all rules, in milliseconds
+    #[schema(example = 0.42)]
+    pub duration_ms: f64,
+    /// Every rule evaluated, in order (only with `trace`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<RuleTraceEntry>>,
+}
+
+/// Response for a batch transformation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchTransformResponse {
+    /// Transformed texts, in the order of the request
+    #[schema(example = json!(["foo / bar", "a ."]))]
+    pub outputs: Vec<String>,
+    /// Time spent on the whole batch, in milliseconds
+    #[schema(example = 1.5)]
+    pub duration_ms: f64,
+}
+
+/// A rule evaluated during a traced transformation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RuleTraceEntry {
+    /// Rule ID
+    #[schema(example = "slash")]
+    pub rule_id: String,
+    /// Type of rule (Regex, Literal, Shell, Function)
+    #[schema(example = "Regex")]
+    pub rule_type: String,
+    /// Group of the rule, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// "applied" if the rule changed the text, "unchanged" or "skipped"
+    #[schema(example = "applied")]
+    pub status: String,
+    /// Why the rule was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Text before the rule
+    #[schema(example = "foo slash bar")]
+    pub before: String,
+    /// Text after the rule
+    #[schema(example = "foo / bar")]
+    pub after: String,
+    /// Matches of the pattern in `before` (regex and literal rules)
+    pub spans: Vec<MatchSpan>,
+    /// Time spent applying the rule, in milliseconds
+    #[schema(example = 0.01)]
+    pub duration_ms: f64,
+}
+
+/// Byte range of a pattern match
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MatchSpan {
+    /// Start offset (inclusive)
+    #[schema(example = 4)]
+    pub start: usize,
+    /// End offset (exclusive)
+    #[schema(example = 9)]
+    pub end: usize,
+}
+
+impl From<TransformationRecord> for TransformationLogEntry {
+    fn from(record: TransformationRecord) -> Self {
+        Self {
+            id: record.id,
+            timestamp: record
+                .timestamp
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            duration_ms: record.duration.as_secs_f64() * 1000.0,
+            input: record.input,
+            output: record.output,
+            matches: record
+                .matches
+                .into_iter()
+                .map(RuleMatchEntry::from)
+                .collect(),
+            skipped: record
+                .skipped
+                .into_iter()
+                .map(SkippedRuleEntry::from)
+                .collect(),
+            extractor: record.extractor,
+        }
+    }
+}
+
+impl RuleMatchEntry {
+    /// Whether the rule is addressed by `id`, either bare or namespaced
+    /// (entries written before namespaces were recorded only match bare ids)
+    pub fn has_id(&self, id: &str) -> bool {
+        self.rule_id == id || self.qualified_id.as_deref() == Some(id)
+    }
+}
+
+impl From<RuleMatch> for RuleMatchEntry {
+    fn from(m: RuleMatch) -> Self {
+        Self {
+            rule_id: m.rule_id,
+            qualified_id: Some(m.qualified_id),
+            rule_type: m.rule_type,
+            group: m.group,
+            output: m.output,
+        }
+    }
+}
+
+impl From<SkippedRule> for SkippedRuleEntry {
+    fn from(skipped: SkippedRule) -> Self {
+        Self {
+            rule_id: skipped.rule_id,
+            reason: skipped.reason,
+        }
+    }
+}
+
+impl From<RuleStep> for RuleTraceEntry {
+    fn from(step: RuleStep) -> Self {
+        let status = match &step.skipped {
+            Some(_) => "skipped",
+            None if step.before != step.after => "applied",
+            None => "unchanged",
+        };
+        Self {
+            rule_id: step.rule_id,
+            rule_type: step.rule_type,
+            group: step.group,
+            status: status.to_string(),
+            reason: step.skipped,
+            before: step.before,
+            after: step.after,
+            spans: step
+                .spans
+                .into_iter()
+                .map(|span| MatchSpan {
+                    start: span.start,
+                    end: span.end,
+                })
+                .collect(),
+            duration_ms: step.duration.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_chunks() {
+        let chunks = ChatCompletionChunk::stream("a slash b", "a / b".to_string(), "m", true);
+        let json: Vec<serde_json::Value> = chunks
+            .iter()
+            .map(|c| serde_json::to_value(c).unwrap())
+            .collect();
+
+        assert_eq!(json.len(), 4);
+        assert!(json.iter().all(|c| c["object"] == "chat.completion.chunk"));
+        assert!(
+            json.iter()
+                .all(|c| c["id"] == json[0]["id"] && c["model"] == "m")
+        );
+
+        assert_eq!(
+            json[0]["choices"][0]["delta"],
+            serde_json::json!({"role": "assistant", "content": ""})
+        );
+        assert!(json[0]["choices"][0]["finish_reason"].is_null());
+        assert_eq!(
+            json[1]["choices"][0]["delta"],
+            serde_json::json!({"content": "a / b"})
+        );
+        assert_eq!(json[2]["choices"][0]["delta"], serde_json::json!({}));
+        assert_eq!(json[2]["choices"][0]["finish_reason"], "stop");
+        assert!(json[2].get("usage").is_none());
+
+        assert_eq!(json[3]["choices"], serde_json::json!([]));
+        assert_eq!(json[3]["usage"]["prompt_tokens"], 3);
+        assert_eq!(json[3]["usage"]["completion_tokens"], 2);
+        assert_eq!(json[3]["usage"]["total_tokens"], 5);
+
+        let chunks = ChatCompletionChunk::stream("a", "a".to_string(), "m", false);
+        assert_eq!(chunks.len(), 3);
+    }
+
+    #[test]
+    fn test_completion_response_schema() {
+        let response = ChatCompletionResponse::new("a slash b", "a / b".to_string(), "gpt-4o");
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(json["object"], "chat.completion");
+        assert_eq!(json["model"], "gpt-4o");
+        assert!(json["created"].as_i64().unwrap() > 0);
+        assert!(
+            json["system_fingerprint"]
+                .as_str()
+                .unwrap()
+                .starts_with("handy-local-rules-")
+        );
+        assert_eq!(json["choices"][0]["message"]["content"], "a / b");
+        assert_eq!(
+            json["usage"],
+            serde_json::json!({"prompt_tokens": 3, "completion_tokens": 2, "total_tokens": 5})
+        );
+    }
+
+    #[test]
+    fn test_completion_response() {
+        let response = CompletionResponse::new(
+            vec![("a slash b", "a / b".to_string()), ("c", "c".to_string())],
+            "gpt-3.5-turbo-instruct",
+        );
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(json["object"], "text_completion");
+        assert_eq!(json["model"], "gpt-3.5-turbo-instruct");
+        assert_eq!(json["choices"][0]["text"], "a / b");
+        assert_eq!(json["choices"][1]["text"], "c");
+        assert_eq!(json["choices"][1]["index"], 1);
+        assert_eq!(json["choices"][1]["finish_reason"], "stop");
+        assert_eq!(json["usage"]["prompt_tokens"], 4);
+        assert_eq!(json["usage"]["completion_tokens"], 3);
+        assert_eq!(json["usage"]["total_tokens"], 7);
+    }
+
+    #[test]
+    fn test_responses_response() {
+        let response = ResponsesResponse::new("a slash b", "a / b".to_string(), "gpt-4o");
+        let json = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(json["object"], "response");
+        assert_eq!(json["status"], "completed");
+        assert_eq!(json["model"], "gpt-4o");
+        assert_eq!(json["output"][0]["type"], "message");
+        assert_eq!(json["output"][0]["role"], "assistant");
+        assert_eq!(
+            json["output"][0]["content"],
+            serde_json::json!([{"type": "output_text", "text": "a / b", "annotations": []}])
+        );
+        assert_eq!(
+            json["usage"],
+            serde_json::json!({"input_tokens": 3, "output_tokens": 2, "total_tokens": 5})
+        );
+    }
+
+    #[test]
+    fn test_usage_estimate() {
+        let usage = Usage::estimate("", "");
+        assert_eq!(usage.total_tokens, 0);
+
+        // Counted in characters, not bytes
+        let usage = Usage::estimate("äöüß", "abcde");
+        assert_eq!(usage.prompt_tokens, 1);
+        assert_eq!(usage.completion_tokens, 2);
+        assert_eq!(usage.total_tokens, 3);
+    }
+}
@@ -0,0 +1,247 @@
+//! Privacy modes for logged text
+//!
+//! Dictated text may contain anything from passwords spoken by mistake to
+//! medical notes. The privacy mode decides what of it reaches the in-memory
+//! log, tracing output and the on-disk history; the transformed text returned
+//! to the client is never affected.
+
+use crate::error::AppError;
+use crate::rules::TransformationRecord;
+use hmac::{Hmac, KeyInit, Mac};
+use regex::Regex;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::fs;
+use std::io::{ErrorKind, Write as _};
+use std::path::Path;
+
+/// Replacement for text matched by a redaction pattern
+const REDACTED: &str = "[redacted]";
+
+/// File of the hash key, kept in the history directory
+pub const HASH_KEY_FILE: &str = ".privacy-key";
+
+/// Length of the hash key in bytes
+const HASH_KEY_LEN: usize = 32;
+
+/// Patterns redacted when none are configured: passwords and PINs with the
+/// word that follows, email addresses and numbers of four or more digits
+const DEFAULT_REDACT_PATTERNS: &[&str] = &[
+    r"(?i)\b(?:password|passwort|kennwort|pin)\b(?:\W+(?:is|ist|lautet)\b)?\W*\S+",
+    r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+",
+    r"\d(?:[\s./-]?\d){3,}",
+];
+
+/// What of the dictated text gets logged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyMode {
+    /// The full text
+    #[default]
+    Full,
+    /// A keyed hash (HMAC-SHA-256), so repeated dictations can still be
+    /// recognized but not guessed without the key
+    Hashed,
+    /// The text with matches of the redaction patterns replaced
+    Redacted,
+    /// Only the length of the text (rule ids and timings are kept)
+    Metadata,
+}
+
+/// Privacy mode with its compiled redaction patterns
+#[derive(Debug, Clone, Default)]
+pub struct Privacy {
+    mode: PrivacyMode,
+    patterns: Vec<Regex>,
+    /// Key of the hashes in hashed mode
+    hash_key: Vec<u8>,
+}
+
+impl Privacy {
+    /// Compile the redaction patterns; the defaults are used if none are given
+    pub fn new(mode: PrivacyMode, redact_patterns: &[String]) -> Result<Self, AppError> {
+        let patterns = if redact_patterns.is_empty() {
+            DEFAULT_REDACT_PATTERNS
+                .iter()
+                .map(|p| Regex::new(p))
+                .collect::<Result<_, _>>()?
+        } else {
+            redact_patterns
+                .iter()
+                .map(|p| Regex::new(p))
+                .collect::<Result<_, _>>()?
+        };
+
+        Ok(Self {
+            mode,
+            patterns,
+            hash_key: Vec::new(),
+        })
+    }
+
+    /// Use the key at `path` for the hashes, creating a random one if the file
+    /// does not exist yet. Without a key, texts are hashed with an empty one.
+    pub fn with_hash_key(mut self, path: &Path) -> Result<Self, AppError> {
+        self.hash_key = load_or_create_key(path)?;
+        Ok(self)
+    }
+
+    /// The privacy mode
+    pub fn mode(&self) -> PrivacyMode {
+        self.mode
+    }
+
+    /// The text as it may be logged
+    pub fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self.mode {
+            PrivacyMode::Full => Cow::Borrowed(text),
+            PrivacyMode::Hashed => Cow::Owned(hash(&self.hash_key, text)),
+            PrivacyMode::Redacted => {
+                let mut text = Cow::Borrowed(text);
+                for pattern in &self.patterns {
+                    if pattern.is_match(&text) {
+                        text = Cow::Owned(pattern.replace_all(&text, REDACTED).into_owned());
+                    }
+                }
+                text
+            },
+            PrivacyMode::Metadata => Cow::Owned(format!("[{} chars]", text.chars().count())),
+        }
+    }
+
+    /// Apply the mode to every text of a record
+    pub fn apply(&self, record: &mut TransformationRecord) {
+        if self.mode == PrivacyMode::Full {
+            return;
+        }
+
+        record.input = self.text(&record.input).into_owned();
+        record.output = self.text(&record.output).into_owned();
+        for rule_match in &mut record.matches {
+            rule_match.output = self.text(&rule_match.output).into_owned();
+        }
+    }
+}
+
+/// Short HMAC-SHA-256 of a text
+fn hash(key: &[u8], text: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(text.as_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let mut hex = String::from("hmac:");
+    for byte in &digest[..8] {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+/// Read the hash key, or create the file with a random key (readable by the
+/// owner only). A key written by a concurrent process wins.
+fn load_or_create_key(path: &Path) -> Result<Vec<u8>, AppError> {
+    match fs::read(path) {
+        Ok(key) if key.len() == HASH_KEY_LEN => return Ok(key),
+        Ok(_) => {
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "{} does not hold a {}-byte key",
+                path.display(),
+                HASH_KEY_LEN
+            ))));
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => {},
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut key = vec![0; HASH_KEY_LEN];
+    getrandom::fill(&mut key).map_err(|e| AppError::IoError(std::io::Error::other(e)))?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::File::options();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    match options.open(path) {
+        Ok(mut file) => {
+            file.write_all(&key)?;
+            file.sync_all()?;
+            tracing::info!("Created the privacy hash key {}", path.display());
+            Ok(key)
+        },
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => load_or_create_key(path),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn privacy(mode: PrivacyMode) -> Privacy {
+        Privacy::new(mode, &[]).unwrap()
+    }
+
+    #[test]
+    fn test_modes() {
+        let text = "mein Passwort ist geheim123";
+
+        assert_eq!(privacy(PrivacyMode::Full).text(text), text);
+        assert_eq!(privacy(PrivacyMode::Redacted).text(text), "mein [redacted]");
+        assert_eq!(privacy(PrivacyMode::Metadata).text(text), "[27 chars]");
+
+        let hashed = privacy(PrivacyMode::Hashed).text(text).into_owned();
+        assert!(
+            hashed.starts_with("hmac:") && hashed.len() == 21,
+            "{}",
+            hashed
+        );
+        assert_eq!(privacy(PrivacyMode::Hashed).text(text), hashed);
+        assert_ne!(privacy(PrivacyMode::Hashed).text("other"), hashed);
+    }
+
+    #[test]
+    fn test_hash_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history").join(HASH_KEY_FILE);
+        let keyed = || {
+            privacy(PrivacyMode::Hashed)
+                .with_hash_key(&path)
+                .unwrap()
+                .text("ja")
+                .into_owned()
+        };
+
+        // The key is created once and then reused
+        let hashed = keyed();
+        assert_eq!(fs::read(&path).unwrap().len(), HASH_KEY_LEN);
+        assert_eq!(keyed(), hashed);
+        assert_ne!(privacy(PrivacyMode::Hashed).text("ja"), hashed);
+
+        // Another install has another key
+        fs::remove_file(&path).unwrap();
+        assert_ne!(keyed(), hashed);
+
+        fs::write(&path, "short").unwrap();
+        assert!(privacy(PrivacyMode::Hashed).with_hash_key(&path).is_err());
+    }
+
+    #[test]
+    fn test_redaction_patterns() {
+        let defaults = privacy(PrivacyMode::Redacted);
+        assert_eq!(
+            defaults.text("mail max@example.com or call 0171 234 5678, room 12"),
+            "mail [redacted] or call [redacted], room 12"
+        );
+
+        let custom = Privacy::new(PrivacyMode::Redacted, &["(?i)diagnose \\w+".to_string()]);
+        assert_eq!(
+            custom.unwrap().text("Diagnose Grippe, PIN 1234"),
+            "[redacted], PIN 1234"
+        );
+
+        assert!(Privacy::new(PrivacyMode::Redacted, &["(".to_string()]).is_err());
+    }
+}
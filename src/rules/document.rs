@@ -0,0 +1,801 @@
+//! Format-preserving edits of JSON rules files
+//!
+//! Rules files are edited in place: only the bytes of the touched value or rule
+//! change, everything else (key order, indentation, spacing, comments) stays
+//! byte-identical. The scanner accepts JSONC/JSON5 comments, trailing commas,
+//! single-quoted strings and unquoted keys.
+
+use super::types::Rule;
+use serde_json::Value;
+
+/// A parsed JSON value with its byte span in the source text
+#[derive(Debug)]
+enum Node {
+    Object {
+        start: usize,
+        end: usize,
+        members: Vec<Member>,
+    },
+    Array {
+        start: usize,
+        end: usize,
+        items: Vec<Node>,
+    },
+    Scalar {
+        start: usize,
+        end: usize,
+    },
+}
+
+/// An object member (`"key": value`)
+#[derive(Debug)]
+struct Member {
+    key: String,
+    key_start: usize,
+    value: Node,
+}
+
+impl Node {
+    fn start(&self) -> usize {
+        match self {
+            Node::Object { start, .. } | Node::Array { start, .. } | Node::Scalar { start, .. } => {
+                *start
+            },
+        }
+    }
+
+    fn end(&self) -> usize {
+        match self {
+            Node::Object { end, .. } | Node::Array { end, .. } | Node::Scalar { end, .. } => *end,
+        }
+    }
+
+    fn member(&self, key: &str) -> Option<&Member> {
+        match self {
+            Node::Object { members, .. } => members.iter().find(|m| m.key == key),
+            _ => None,
+        }
+    }
+}
+
+/// Minimal JSON scanner that records spans instead of building values
+struct Parser<'a> {
+    text: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            bytes: text.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn parse_document(mut self) -> Result<Node, String> {
+        self.skip_ws();
+        let node = self.parse_value()?;
+        self.skip_ws();
+        if self.pos != self.bytes.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(node)
+    }
+
+    fn error(&self, message: &str) -> String {
+        let consumed = &self.text[..self.pos.min(self.text.len())];
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed.len() - consumed.rfind('\n').map_or(0, |i| i + 1) + 1;
+        format!("{} at line {} column {}", message, line, column)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// Skip whitespace and comments
+    fn skip_ws(&mut self) {
+        loop {
+            match (self.peek(), self.bytes.get(self.pos + 1)) {
+                (Some(b' ' | b'\t' | b'\n' | b'\r'), _) => self.pos += 1,
+                (Some(b'/'), Some(b'/')) => {
+                    while !matches!(self.peek(), Some(b'\n') | None) {
+                        self.pos += 1;
+                    }
+                },
+                (Some(b'/'), Some(b'*')) => {
+                    self.pos = self.text[self.pos + 2..]
+                        .find("*/")
+                        .map_or(self.bytes.len(), |p| self.pos + 2 + p + 2);
+                },
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"' | b'\'') => {
+                let start = self.pos;
+                self.parse_string()?;
+                Ok(Node::Scalar {
+                    start,
+                    end: self.pos,
+                })
+            },
+            Some(_) => {
+                let start = self.pos;
+                while let Some(b) = self.peek() {
+                    if matches!(b, b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r' | b'/') {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return Err(self.error("expected value"));
+                }
+                Ok(Node::Scalar {
+                    start,
+                    end: self.pos,
+                })
+            },
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        let quote = match self.peek() {
+            Some(q @ (b'"' | b'\'')) => q,
+            _ => return Err(self.error("expected string")),
+        };
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'\\') => self.pos += 2,
+                Some(q) if q == quote => {
+                    self.pos += 1;
+                    break;
+                },
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+        decode_string(&self.text[start..self.pos]).ok_or_else(|| self.error("invalid string"))
+    }
+
+    /// Parse an object key: a string or a bare JSON5 identifier
+    fn parse_key(&mut self) -> Result<String, String> {
+        if matches!(self.peek(), Some(b'"' | b'\'')) {
+            return self.parse_string();
+        }
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if !(b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80) {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("expected key"));
+        }
+        Ok(self.text[start..self.pos].to_string())
+    }
+
+    fn parse_object(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                break;
+            }
+            let key_start = self.pos;
+            let key = self.parse_key()?;
+            self.skip_ws();
+            self.expect(b':')?;
+            self.skip_ws();
+            let value = self.parse_value()?;
+            members.push(Member {
+                key,
+                key_start,
+                value,
+            });
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {},
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+        Ok(Node::Object {
+            start,
+            end: self.pos,
+            members,
+        })
+    }
+
+    fn parse_array(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.peek() == Some(b']') {
+                self.pos += 1;
+                break;
+            }
+            items.push(self.parse_value()?);
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {},
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+        Ok(Node::Array {
+            start,
+            end: self.pos,
+            items,
+        })
+    }
+}
+
+/// Decode a double- or single-quoted string literal
+fn decode_string(raw: &str) -> Option<String> {
+    if !raw.starts_with('\'') {
+        return serde_json::from_str(raw).ok();
+    }
+
+    // Rewrite as a double-quoted JSON string
+    let inner = raw.get(1..raw.len().checked_sub(1)?)?;
+    let mut json = String::with_capacity(raw.len() + 2);
+    json.push('"');
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\'' => json.push('\''),
+                other => {
+                    json.push('\\');
+                    json.push(other);
+                },
+            },
+            '"' => json.push_str("\\\""),
+            other => json.push(other),
+        }
+    }
+    json.push('"');
+    serde_json::from_str(&json).ok()
+}
+
+/// Start of the line containing `pos`
+fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Whether a line holds nothing but a comment
+fn is_comment_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with("//") || (trimmed.starts_with("/*") && trimmed.ends_with("*/"))
+}
+
+/// Indentation of the line containing `pos`
+fn line_indent(text: &str, pos: usize) -> &str {
+    let line = &text[line_start(text, pos)..];
+    let width = line.len() - line.trim_start_matches([' ', '\t']).len();
+    &line[..width]
+}
+
+/// Pretty-print a value, indenting continuation lines with `indent`
+fn pretty_value(value: &Value, indent: &str) -> String {
+    let pretty = serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string());
+    pretty.replace('\n', &format!("\n{}", indent))
+}
+
+/// Print a value on one line, with a space after `:` and `,`
+fn inline_value(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let members: Vec<String> = map
+                .iter()
+                .map(|(key, value)| {
+                    format!("{}: {}", Value::from(key.as_str()), inline_value(value))
+                })
+                .collect();
+            format!("{{{}}}", members.join(", "))
+        },
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(inline_value).collect();
+            format!("[{}]", items.join(", "))
+        },
+        _ => value.to_string(),
+    }
+}
+
+/// Serialize a rule for writing to a rules file
+fn rule_value(rule: &Rule) -> Result<Value, String> {
+    serde_json::to_value(rule).map_err(|e| format!("Failed to serialize rule: {}", e))
+}
+
+/// Apply a single text replacement
+fn splice(text: &str, start: usize, end: usize, replacement: &str) -> String {
+    let mut out = String::with_capacity(text.len() + replacement.len());
+    out.push_str(&text[..start]);
+    out.push_str(replacement);
+    out.push_str(&text[end..]);
+    out
+}
+
+/// Targeted edits of a parsed rules file, implemented once per file format.
+/// Each edit returns the full new file content.
+pub trait RulesEditor {
+    /// Set a field on a rule, adding it if it is missing
+    fn set_rule_field(&self, rule_id: &str, key: &str, value: &Value) -> Result<String, String>;
+
+    /// Replace a rule with a new one
+    fn replace_rule(&self, rule_id: &str, rule: &Rule) -> Result<String, String>;
+
+    /// Append a rule to the end of the file
+    fn insert_rule(&self, rule: &Rule) -> Result<String, String>;
+
+    /// Remove a rule
+    fn remove_rule(&self, rule_id: &str) -> Result<String, String>;
+
+    /// Set `overrides.<rule_id>.<key>` in an overrides file
+    fn set_override_field(
+        &self,
+        _rule_id: &str,
+        _key: &str,
+        _value: &Value,
+    ) -> Result<String, String> {
+        Err("overrides in this format can't be edited in place".to_string())
+    }
+}
+
+/// A rules file parsed for targeted edits
+#[derive(Debug)]
+pub struct RulesDocument<'a> {
+    text: &'a str,
+    root: Node,
+}
+
+impl<'a> RulesDocument<'a> {
+    /// Parse the text of a rules file
+    pub fn parse(text: &'a str) -> Result<Self, String> {
+        let root = Parser::new(text).parse_document()?;
+        Ok(Self { text, root })
+    }
+
+    /// The array holding the rules (the root, or `rules` in the envelope form)
+    fn rules_array(&self) -> Result<(&Node, &[Node]), String> {
+        let array = match self.root.member("rules") {
+            Some(member) => &member.value,
+            None => &self.root,
+        };
+        match array {
+            node @ Node::Array { items, .. } => Ok((node, items)),
+            _ => Err("rules file must contain an array of rules".to_string()),
+        }
+    }
+
+    /// Position of the rule with the given id in the rules array
+    fn find_rule(&self, rule_id: &str) -> Result<usize, String> {
+        let (_, items) = self.rules_array()?;
+        items
+            .iter()
+            .position(|item| {
+                item.member("id").is_some_and(|m| {
+                    let raw = &self.text[m.value.start()..m.value.end()];
+                    decode_string(raw).is_some_and(|id| id == rule_id)
+                })
+            })
+            .ok_or_else(|| format!("rule '{}' not found", rule_id))
+    }
+
+    /// Set a field on a rule, adding it after the last field if it is missing
+    pub fn set_rule_field(
+        &self,
+        rule_id: &str,
+        key: &str,
+        value: &Value,
+    ) -> Result<String, String> {
+        let (_, items) = self.rules_array()?;
+        let rule = &items[self.find_rule(rule_id)?];
+        self.set_member(rule, key, &value.to_string())
+            .map_err(|_| format!("rule '{}' is not an object", rule_id))
+    }
+
+    /// Set `overrides.<rule_id>.<key>` in an overrides file, adding missing levels
+    pub fn set_override_field(
+        &self,
+        rule_id: &str,
+        key: &str,
+        value: &Value,
+    ) -> Result<String, String> {
+        let Some(overrides) = self.root.member("overrides") else {
+            let patch = serde_json::json!({ rule_id: { key: value } });
+            return self.set_member(&self.root, "overrides", &inline_value(&patch));
+        };
+        let Some(entry) = overrides.value.member(rule_id) else {
+            let patch = serde_json::json!({ key: value });
+            return self.set_member(&overrides.value, rule_id, &inline_value(&patch));
+        };
+        self.set_member(&entry.value, key, &value.to_string())
+    }
+
+    /// Set a member of an object to already rendered JSON, adding it after the
+    /// last member if it is missing
+    fn set_member(&self, object: &Node, key: &str, rendered: &str) -> Result<String, String> {
+        if let Some(member) = object.member(key) {
+            return Ok(splice(
+                self.text,
+                member.value.start(),
+                member.value.end(),
+                rendered,
+            ));
+        }
+
+        let Node::Object { start, members, .. } = object else {
+            return Err(format!(
+                "can't set '{}' on a value that is not an object",
+                key
+            ));
+        };
+        let key_json = Value::String(key.to_string()).to_string();
+
+        match members.last() {
+            Some(last) => {
+                let multiline = self.text[*start..members[0].key_start].contains('\n');
+                let separator = if multiline {
+                    format!(",\n{}", line_indent(self.text, last.key_start))
+                } else {
+                    ", ".to_string()
+                };
+                let insertion = format!("{}{}: {}", separator, key_json, rendered);
+                let at = last.value.end();
+                Ok(splice(self.text, at, at, &insertion))
+            },
+            None => {
+                let insertion = format!("{}: {}", key_json, rendered);
+                Ok(splice(self.text, start + 1, start + 1, &insertion))
+            },
+        }
+    }
+
+    /// Replace a rule with a new value
+    pub fn replace_rule(&self, rule_id: &str, rule: &Value) -> Result<String, String> {
+        let (_, items) = self.rules_array()?;
+        let item = &items[self.find_rule(rule_id)?];
+        let indent = line_indent(self.text, item.start());
+        Ok(splice(
+            self.text,
+            item.start(),
+            item.end(),
+            &pretty_value(rule, indent),
+        ))
+    }
+
+    /// Append a rule to the end of the rules array
+    pub fn insert_rule(&self, rule: &Value) -> Result<String, String> {
+        let (array, items) = self.rules_array()?;
+
+        match items.last() {
+            Some(last) => {
+                let text = self.text;
+                let indent = line_indent(text, last.start());
+
+                // Keep the trailing-comma style and any comment after the last rule
+                let rest = &text[last.end()..];
+                let spaces = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+                let has_comma = rest[spaces..].starts_with(',');
+                let after = last.end() + if has_comma { spaces + 1 } else { 0 };
+                let line_end = after + text[after..].find('\n').unwrap_or(text.len() - after);
+                let at = if text[after..line_end].trim().starts_with("//") {
+                    line_end
+                } else {
+                    after
+                };
+
+                let insertion = format!(
+                    "\n{}{}{}",
+                    indent,
+                    pretty_value(rule, indent),
+                    if has_comma { "," } else { "" }
+                );
+                let output = splice(text, at, at, &insertion);
+                if has_comma {
+                    Ok(output)
+                } else {
+                    Ok(splice(&output, last.end(), last.end(), ","))
+                }
+            },
+            None => {
+                let outer = line_indent(self.text, array.start());
+                let indent = format!("{}  ", outer);
+                let inner = format!("\n{}{}\n{}", indent, pretty_value(rule, &indent), outer);
+                Ok(splice(
+                    self.text,
+                    array.start() + 1,
+                    array.end() - 1,
+                    &inner,
+                ))
+            },
+        }
+    }
+
+    /// Remove a rule together with its separating comma, its own lines and
+    /// the comments directly above it
+    pub fn remove_rule(&self, rule_id: &str) -> Result<String, String> {
+        let (array, items) = self.rules_array()?;
+        let index = self.find_rule(rule_id)?;
+        let item = &items[index];
+        let text = self.text;
+
+        // Start: beginning of the line if the rule starts it, plus comment lines above
+        let mut start = item.start();
+        let own_line = line_start(text, start);
+        if text[own_line..start].trim().is_empty() {
+            start = own_line;
+            while start > 0 {
+                let previous = line_start(text, start - 1);
+                if !is_comment_line(&text[previous..start]) {
+                    break;
+                }
+                start = previous;
+            }
+        }
+
+        // End: the separating comma, then the rest of the line if the rule owns it
+        // (including a trailing comment), otherwise just the following spaces
+        let mut end = item.end();
+        let rest = &text[end..];
+        let spaces = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+        let has_comma = rest[spaces..].starts_with(',');
+        if has_comma {
+            end += spaces + 1;
+        }
+        let rest = &text[end..];
+        let line_len = rest.find('\n').map_or(rest.len(), |i| i + 1);
+        let tail = rest[..line_len].trim();
+        if start <= own_line && (tail.is_empty() || tail.starts_with("//")) {
+            end += line_len;
+        } else {
+            end += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+        }
+
+        // Only rule without comments: empty the array
+        let inner = (array.start() + 1, array.end() - 1);
+        if items.len() == 1
+            && !text[inner.0..inner.1]
+                .replace(&text[start..end], "")
+                .contains('/')
+        {
+            return Ok(splice(text, inner.0, inner.1, ""));
+        }
+
+        let mut output = splice(text, start, end, "");
+
+        // Last rule without trailing comma: drop the comma after the previous rule
+        if !has_comma && index > 0 {
+            let previous_end = items[index - 1].end();
+            if let Some(offset) = text[previous_end..start].find(',') {
+                output = splice(
+                    &output,
+                    previous_end + offset,
+                    previous_end + offset + 1,
+                    "",
+                );
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+impl RulesEditor for RulesDocument<'_> {
+    fn set_rule_field(&self, rule_id: &str, key: &str, value: &Value) -> Result<String, String> {
+        RulesDocument::set_rule_field(self, rule_id, key, value)
+    }
+
+    fn replace_rule(&self, rule_id: &str, rule: &Rule) -> Result<String, String> {
+        RulesDocument::replace_rule(self, rule_id, &rule_value(rule)?)
+    }
+
+    fn insert_rule(&self, rule: &Rule) -> Result<String, String> {
+        RulesDocument::insert_rule(self, &rule_value(rule)?)
+    }
+
+    fn remove_rule(&self, rule_id: &str) -> Result<String, String> {
+        RulesDocument::remove_rule(self, rule_id)
+    }
+
+    fn set_override_field(
+        &self,
+        rule_id: &str,
+        key: &str,
+        value: &Value,
+    ) -> Result<String, String> {
+        RulesDocument::set_override_field(self, rule_id, key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const RULES: &str = r#"[
+    {"id": "a", "pattern": "x",   "enabled": true},
+    {
+        "id": "b",
+        "pattern": "y"
+    }
+]
+"#;
+
+    #[test]
+    fn test_set_existing_and_missing_field() {
+        let doc = RulesDocument::parse(RULES).unwrap();
+        let out = doc.set_rule_field("a", "enabled", &json!(false)).unwrap();
+        assert_eq!(
+            out,
+            RULES.replace(r#""enabled": true"#, r#""enabled": false"#)
+        );
+
+        let out = doc.set_rule_field("b", "enabled", &json!(false)).unwrap();
+        assert!(out.contains("\"pattern\": \"y\",\n        \"enabled\": false\n    }"));
+        assert!(out.starts_with(
+            r#"[
+    {"id": "a", "pattern": "x",   "enabled": true},"#
+        ));
+    }
+
+    #[test]
+    fn test_insert_replace_remove_rule() {
+        let doc = RulesDocument::parse(RULES).unwrap();
+        let inserted = doc
+            .insert_rule(&json!({"id": "c", "pattern": "z"}))
+            .unwrap();
+        assert!(
+            inserted.contains(
+                "    },\n    {\n      \"id\": \"c\",\n      \"pattern\": \"z\"\n    }\n]"
+            )
+        );
+
+        let removed = RulesDocument::parse(&inserted)
+            .unwrap()
+            .remove_rule("c")
+            .unwrap();
+        assert_eq!(removed, RULES);
+
+        let replaced = doc
+            .replace_rule("a", &json!({"id": "a", "pattern": "q"}))
+            .unwrap();
+        assert!(replaced.contains("    {\n      \"id\": \"a\",\n      \"pattern\": \"q\"\n    },\n    {\n        \"id\": \"b\""));
+
+        let first_removed = doc.remove_rule("a").unwrap();
+        assert!(first_removed.starts_with("[\n    {\n        \"id\": \"b\""));
+    }
+
+    #[test]
+    fn test_empty_array_and_errors() {
+        let doc = RulesDocument::parse("[]\n").unwrap();
+        let out = doc.insert_rule(&json!({"id": "a"})).unwrap();
+        assert_eq!(out, "[\n  {\n    \"id\": \"a\"\n  }\n]\n");
+        assert_eq!(
+            RulesDocument::parse(&out)
+                .unwrap()
+                .remove_rule("a")
+                .unwrap(),
+            "[]\n"
+        );
+
+        assert!(doc.remove_rule("missing").is_err());
+        assert!(RulesDocument::parse("[{]").unwrap_err().contains("line 1"));
+    }
+
+    const COMMENTED: &str = r#"[
+  // Spoken slash
+  {
+    "id": "slash", // keep me
+    "pattern": "slash",
+  },
+  /* JSON5 style */
+  {id: 'dot', pattern: 'dot'}, // trailing
+]
+"#;
+
+    #[test]
+    fn test_edits_keep_comments() {
+        let doc = RulesDocument::parse(COMMENTED).unwrap();
+
+        let out = doc
+            .set_rule_field("slash", "enabled", &json!(false))
+            .unwrap();
+        assert_eq!(
+            out,
+            COMMENTED.replace(
+                "\"pattern\": \"slash\",\n",
+                "\"pattern\": \"slash\",\n    \"enabled\": false,\n"
+            )
+        );
+        assert!(out.contains("// keep me"));
+
+        let out = doc.set_rule_field("dot", "enabled", &json!(false)).unwrap();
+        assert!(out.contains("{id: 'dot', pattern: 'dot', \"enabled\": false}, // trailing"));
+
+        // Removing a rule takes its own comments with it
+        let out = doc.remove_rule("slash").unwrap();
+        assert_eq!(
+            out,
+            "[\n  /* JSON5 style */\n  {id: 'dot', pattern: 'dot'}, // trailing\n]\n"
+        );
+        let out = doc.remove_rule("dot").unwrap();
+        assert!(out.ends_with("  },\n]\n"));
+        assert!(!out.contains("JSON5"));
+
+        // New rules go after the trailing comment and keep the trailing comma
+        let out = doc.insert_rule(&json!({"id": "x"})).unwrap();
+        assert!(out.ends_with("// trailing\n  {\n    \"id\": \"x\"\n  },\n]\n"));
+    }
+
+    #[test]
+    fn test_envelope_rules() {
+        let envelope =
+            "{\n  \"pack\": {\"name\": \"de\", \"enabled\": true},\n  \"rules\": []\n}\n";
+        let doc = RulesDocument::parse(envelope).unwrap();
+        let out = doc.insert_rule(&json!({"id": "a"})).unwrap();
+        assert_eq!(
+            out,
+            "{\n  \"pack\": {\"name\": \"de\", \"enabled\": true},\n  \"rules\": [\n    {\n      \"id\": \"a\"\n    }\n  ]\n}\n"
+        );
+
+        // The pack's own `enabled` is not mistaken for a rule field
+        let out = RulesDocument::parse(&out)
+            .unwrap()
+            .set_rule_field("a", "enabled", &json!(false))
+            .unwrap();
+        assert!(out.contains("\"enabled\": true},"));
+        assert!(out.contains("\"id\": \"a\",\n      \"enabled\": false\n"));
+    }
+
+    #[test]
+    fn test_set_override_field() {
+        let doc = RulesDocument::parse("{}\n").unwrap();
+        let out = doc
+            .set_override_field("a", "enabled", &json!(false))
+            .unwrap();
+        assert_eq!(out, "{\"overrides\": {\"a\": {\"enabled\": false}}}\n");
+
+        let out = RulesDocument::parse(&out)
+            .unwrap()
+            .set_override_field("b", "priority", &json!(5))
+            .unwrap();
+        assert_eq!(
+            out,
+            "{\"overrides\": {\"a\": {\"enabled\": false}, \"b\": {\"priority\": 5}}}\n"
+        );
+
+        let out = RulesDocument::parse(&out)
+            .unwrap()
+            .set_override_field("a", "enabled", &json!(true))
+            .unwrap();
+        assert!(out.contains("{\"a\": {\"enabled\": true}, "));
+    }
+}
@@ -1,25 +1,188 @@
 //! Rule application engine
 
 use super::loader;
-use super::types::{BuiltinFunction, Rule, RuleType};
+use super::overrides;
+use super::types::{BuiltinFunction, PackInfo, Rule, RuleFilter, RuleSelector, RuleType};
 use crate::error::AppError;
+use crate::privacy::{Privacy, PrivacyMode};
+use chrono::{DateTime, Utc};
 use notify::RecommendedWatcher;
-use regex::Regex;
-use std::collections::{HashMap, VecDeque};
+use regex::{NoExpand, Regex};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex, RwLock};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::broadcast;
 
-/// Record of a single transformation
+/// Number of records buffered for slow subscribers of the transformation stream
+const EVENT_CAPACITY: usize = 256;
+
+/// Record of one request: the input, the final output and every rule that
+/// changed the text on the way
 #[derive(Debug, Clone)]
-pub struct TransformationLog {
+pub struct TransformationRecord {
+    /// Unique id of the request
+    pub id: String,
+    /// When processing started
+    pub timestamp: DateTime<Utc>,
+    /// Time spent applying all rules
+    pub duration: Duration,
+    pub input: String,
+    pub output: String,
+    /// Rules that changed the text, in the order they were applied
+    pub matches: Vec<RuleMatch>,
+    /// Rules that were not applied because of a group or a condition
+    pub skipped: Vec<SkippedRule>,
+    /// Extractor that pulled the input out of a prompt, if any
+    pub extractor: Option<String>,
+}
+
+impl TransformationRecord {
+    fn start(input: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            duration: Duration::ZERO,
+            input: input.to_string(),
+            output: input.to_string(),
+            matches: Vec::new(),
+            skipped: Vec::new(),
+            extractor: None,
+        }
+    }
+}
+
+/// A rule that changed the text, with the text after it was applied
+#[derive(Debug, Clone)]
+pub struct RuleMatch {
     pub rule_id: String,
+    /// `pack:id` (or `file:id`) of the rule, as addressed by `Rule::has_id`
+    pub qualified_id: String,
     pub rule_type: String,
-    pub input: String,
+    /// Group of the rule, if any
+    pub group: Option<String>,
     pub output: String,
-    pub matched: bool,
+}
+
+impl RuleMatch {
+    /// Whether the rule is addressed by `id`, either bare or namespaced
+    pub fn has_id(&self, id: &str) -> bool {
+        self.rule_id == id || self.qualified_id == id
+    }
+}
+
+/// A rule that was not applied, and why
+#[derive(Debug, Clone)]
+pub struct SkippedRule {
+    pub rule_id: String,
+    /// e.g. another member of its group matched
+    pub reason: String,
+}
+
+/// A rule evaluated during a traced request, with the text before and after it
+#[derive(Debug, Clone)]
+pub struct RuleStep {
+    pub rule_id: String,
+    pub rule_type: String,
+    /// Group of the rule, if any
+    pub group: Option<String>,
+    /// Why the rule was not applied, if it was skipped
+    pub skipped: Option<String>,
+    pub before: String,
+    pub after: String,
+    /// Byte ranges of the pattern matches in `before` (regex and literal rules)
+    pub spans: Vec<Range<usize>>,
+    /// Time spent applying the rule
+    pub duration: Duration,
+}
+
+impl RuleStep {
+    fn new(rule: &Rule, before: &str, after: &str) -> Self {
+        Self {
+            rule_id: rule.id.clone(),
+            rule_type: format!("{:?}", rule.rule_type),
+            group: rule.group.clone(),
+            skipped: None,
+            before: before.to_string(),
+            after: after.to_string(),
+            spans: Vec::new(),
+            duration: Duration::ZERO,
+        }
+    }
+
+    fn skipped(rule: &Rule, text: &str, reason: &str) -> Self {
+        Self {
+            skipped: Some(reason.to_string()),
+            ..Self::new(rule, text, text)
+        }
+    }
+}
+
+/// Result of a traced request
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub output: String,
+    /// Time spent applying all rules
+    pub duration: Duration,
+    /// Every rule evaluated, in order
+    pub steps: Vec<RuleStep>,
+}
+
+/// Output of one input under the current and the draft rule set
+#[derive(Debug, Clone)]
+pub struct PreviewResult {
+    pub input: String,
+    pub current: String,
+    pub preview: String,
+}
+
+/// Result of previewing a draft rule set
+#[derive(Debug, Clone, Default)]
+pub struct PreviewOutcome {
+    /// Validation errors as (rule id, message); invalid rules are left out of the preview
+    pub errors: Vec<(String, String)>,
+    pub results: Vec<PreviewResult>,
+}
+
+/// Options for loading and running rules
+#[derive(Debug, Clone)]
+pub struct EngineOptions {
+    /// Whether shell rules are enabled (security feature)
+    pub enable_shell_rules: bool,
+
+    /// Overrides file patching the loaded rules by id
+    pub overrides_path: Option<String>,
+
+    /// Load rules with duplicate ids (with a warning) instead of failing
+    pub allow_duplicate_ids: bool,
+
+    /// Number of requests kept in the transformation log
+    pub max_log_entries: usize,
+
+    /// What of the text reaches the transformation log and tracing output
+    pub privacy: Privacy,
+
+    /// Named rule selections that requests can refer to
+    pub profiles: BTreeMap<String, RuleSelector>,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            enable_shell_rules: false,
+            overrides_path: None,
+            allow_duplicate_ids: false,
+            max_log_entries: 1000,
+            privacy: Privacy::default(),
+            profiles: BTreeMap::new(),
+        }
+    }
 }
 
 /// The rule engine that applies transformation rules to text
@@ -30,29 +193,33 @@ pub struct RuleEngine {
     /// Currently loaded rules
     rules: RwLock<Vec<Rule>>,
 
-    /// Compiled regex cache
+    /// Compiled regex cache, keyed by effective pattern
     regex_cache: RwLock<HashMap<String, Regex>>,
 
-    /// Transformation log (most recent transformations)
+    /// Transformation log (most recent requests)
     /// Uses Mutex (not RwLock) since every request writes to the log
     /// VecDeque for efficient FIFO operations without memory fragmentation
-    transformation_log: Mutex<VecDeque<TransformationLog>>,
+    transformation_log: Mutex<VecDeque<TransformationRecord>>,
 
-    /// Maximum log entries to keep
-    max_log_entries: usize,
+    /// Every logged record, for live subscribers (e.g. `/v1/logs/stream`)
+    events: broadcast::Sender<TransformationRecord>,
 
-    /// Whether shell rules are enabled (security feature)
-    enable_shell_rules: bool,
+    /// Load and runtime options
+    options: EngineOptions,
 
     /// File watchers (kept alive for the lifetime of the engine)
     #[allow(dead_code)]
     watchers: Mutex<Vec<RecommendedWatcher>>,
+
+    /// Rules files written by the engine itself, with their modification time
+    /// after the write (None while the write is in progress)
+    own_writes: Mutex<HashMap<PathBuf, Option<SystemTime>>>,
 }
 
 impl RuleEngine {
     /// Create a new rule engine and load rules from multiple paths
-    pub fn new_from_paths(paths: &[String], enable_shell_rules: bool) -> Result<Self, AppError> {
-        let rules = loader::load_rules_from_paths(paths)?;
+    pub fn with_options(paths: &[String], options: EngineOptions) -> Result<Self, AppError> {
+        let rules = Self::load_rules(paths, &options)?;
 
         // Count and warn about shell rules
         let shell_rule_count = rules
@@ -60,7 +227,7 @@ impl RuleEngine {
             .filter(|r| matches!(r.rule_type, RuleType::Shell))
             .count();
         if shell_rule_count > 0 {
-            if enable_shell_rules {
+            if options.enable_shell_rules {
                 tracing::warn!(
                     "⚠️  {} shell rule(s) loaded. Shell rules can execute arbitrary commands!",
                     shell_rule_count
@@ -80,9 +247,10 @@ impl RuleEngine {
             rules: RwLock::new(rules),
             regex_cache: RwLock::new(HashMap::new()),
             transformation_log: Mutex::new(VecDeque::new()),
-            max_log_entries: 1000,
-            enable_shell_rules,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            options,
             watchers: Mutex::new(Vec::new()),
+            own_writes: Mutex::new(HashMap::new()),
         };
 
         // Pre-compile all regexes
@@ -91,6 +259,74 @@ impl RuleEngine {
         Ok(engine)
     }
 
+    /// Load rules from all paths and apply the overrides file, if any
+    fn load_rules(paths: &[String], options: &EngineOptions) -> Result<Vec<Rule>, AppError> {
+        let mut rules = loader::load_rules_from_paths(paths)?;
+
+        let duplicates = loader::duplicate_ids(&rules);
+        if !duplicates.is_empty() {
+            if !options.allow_duplicate_ids {
+                return Err(AppError::RulesLoadError(format!(
+                    "{} (set allow_duplicate_ids to load them anyway)",
+                    duplicates.join("; ")
+                )));
+            }
+            for duplicate in &duplicates {
+                tracing::warn!("{}, address these rules as pack:id", duplicate);
+            }
+        }
+
+        if let Some(path) = &options.overrides_path {
+            let file = overrides::load_overrides(Path::new(path))?;
+            overrides::apply_overrides(&mut rules, file, path)?;
+        }
+
+        Self::check_requirements(&rules)?;
+
+        Ok(rules)
+    }
+
+    /// Check the conditions of a changed rule set before it is written
+    fn check_draft(mut draft: Vec<Rule>) -> Result<(), AppError> {
+        draft.sort_by_key(|r| std::cmp::Reverse(r.priority));
+        Self::check_requirements(&draft)
+    }
+
+    /// Fail if rule conditions reference unknown rules or rules applied later
+    fn check_requirements(rules: &[Rule]) -> Result<(), AppError> {
+        let errors = loader::requirement_errors(rules);
+        if errors.is_empty() {
+            return Ok(());
+        }
+        let messages: Vec<String> = errors.into_iter().map(|(_, message)| message).collect();
+        Err(AppError::InvalidRule(messages.join("; ")))
+    }
+
+    /// Position of the rule addressed by `rule_id`, which may be namespaced by pack
+    /// (`pack:id`). A bare id shared by several rules is ambiguous.
+    fn find_rule(rules: &[Rule], rule_id: &str) -> Result<Option<usize>, AppError> {
+        let matches: Vec<usize> = rules
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.has_id(rule_id))
+            .map(|(index, _)| index)
+            .collect();
+
+        match matches.as_slice() {
+            [] => Ok(None),
+            [index] => Ok(Some(*index)),
+            _ => Err(AppError::AmbiguousRuleId(format!(
+                "'{}' matches {}",
+                rule_id,
+                matches
+                    .iter()
+                    .map(|&i| rules[i].qualified_id())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
     /// Get the number of loaded rules
     pub fn rules_count(&self) -> usize {
         self.rules.read().unwrap().len()
@@ -105,25 +341,16 @@ impl RuleEngine {
     /// Returns the new enabled state, or None if rule not found
     /// Returns Err if persistence fails (to avoid "gaslighting" the user)
     pub fn toggle_rule(&self, rule_id: &str) -> Result<Option<bool>, AppError> {
-        let (new_state, source_file) = {
-            let mut rules = self.rules.write().unwrap();
+        let mut rules = self.rules.write().unwrap();
 
-            let mut result = None;
-            for rule in rules.iter_mut() {
-                if rule.id == rule_id {
-                    rule.enabled = !rule.enabled;
-                    result = Some((rule.enabled, rule.source_file.clone()));
-                    break;
-                }
-            }
-            match result {
-                Some((state, source)) => (state, source),
-                None => {
-                    tracing::warn!("Rule '{}' not found", rule_id);
-                    return Ok(None);
-                },
-            }
+        let Some(index) = Self::find_rule(&rules, rule_id)? else {
+            tracing::warn!("Rule '{}' not found", rule_id);
+            return Ok(None);
         };
+        let new_state = !rules[index].enabled;
+
+        // Persist change to file - propagate errors to caller
+        self.write_enabled(&mut rules, &[index], new_state)?;
 
         tracing::info!(
             "Rule '{}' is now {}",
@@ -131,19 +358,335 @@ impl RuleEngine {
             if new_state { "enabled" } else { "disabled" }
         );
 
-        // Persist change to file - propagate errors to caller
-        if let Some(ref path) = source_file {
+        Ok(Some(new_state))
+    }
+
+    /// Enable or disable every rule matching the filter and persist the change
+    /// Returns the qualified ids of the rules whose state changed
+    pub fn set_enabled(&self, filter: &RuleFilter, enabled: bool) -> Result<Vec<String>, AppError> {
+        let mut rules = self.rules.write().unwrap();
+
+        let indices: Vec<usize> = rules
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.enabled != enabled && filter.matches(r))
+            .map(|(index, _)| index)
+            .collect();
+        let changed: Vec<String> = indices.iter().map(|&i| rules[i].qualified_id()).collect();
+
+        self.write_enabled(&mut rules, &indices, enabled)?;
+
+        tracing::info!(
+            "{} {} rule(s) matching {:?}",
+            if enabled { "Enabled" } else { "Disabled" },
+            changed.len(),
+            filter
+        );
+
+        Ok(changed)
+    }
+
+    /// Persist the enabled state of the rules at the given positions, one write
+    /// per file, and set it in memory once the rule's file is written.
+    /// Rules from packs are saved in the overrides file, if one is configured,
+    /// so the change survives updates of the pack.
+    fn write_enabled(
+        &self,
+        rules: &mut [Rule],
+        indices: &[usize],
+        enabled: bool,
+    ) -> Result<(), AppError> {
+        // (path, is overrides file) -> (position, id to write)
+        let mut writes: BTreeMap<(String, bool), Vec<(usize, String)>> = BTreeMap::new();
+        let mut unsaved = Vec::new();
+
+        for &index in indices {
+            let rule = &rules[index];
+            let overrides_path = self
+                .options
+                .overrides_path
+                .as_ref()
+                .filter(|path| rule.source_file.as_ref() != Some(*path));
+            if let Some(path) = overrides_path {
+                // Overrides are keyed by the bare id unless other packs use it too
+                let shared = rules.iter().filter(|r| r.id == rule.id).count() > 1;
+                let id = if shared {
+                    rule.qualified_id()
+                } else {
+                    rule.id.clone()
+                };
+                writes
+                    .entry((path.clone(), true))
+                    .or_default()
+                    .push((index, id));
+            } else if let Some(path) = &rule.source_file {
+                writes
+                    .entry((path.clone(), false))
+                    .or_default()
+                    .push((index, rule.id.clone()));
+            } else {
+                tracing::warn!("Rule '{}' has no source file, cannot persist", rule.id);
+                unsaved.push(index);
+            }
+        }
+
+        for ((path, is_overrides), entries) in writes {
+            let ids: Vec<String> = entries.iter().map(|(_, id)| id.clone()).collect();
+            self.persist(&path, || {
+                if is_overrides {
+                    loader::save_overrides(&path, &ids, "enabled", enabled.into())
+                } else {
+                    loader::save_rules_enabled(&path, &ids, enabled)
+                }
+            })?;
+
+            for (index, _) in entries {
+                let rule = &mut rules[index];
+                rule.enabled = enabled;
+                if is_overrides && !rule.overridden.iter().any(|f| f == "enabled") {
+                    rule.overridden.push("enabled".to_string());
+                }
+            }
+        }
+
+        for index in unsaved {
+            rules[index].enabled = enabled;
+        }
+        Ok(())
+    }
+
+    /// Rules files that new rules can be written to:
+    /// configured file paths plus every file rules were loaded from
+    pub fn rule_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self
+            .rules_paths
+            .iter()
+            .filter(|p| Path::new(p).is_file())
+            .cloned()
+            .collect();
+
+        for rule in self.rules.read().unwrap().iter() {
+            if let Some(ref file) = rule.source_file {
+                if !files.contains(file) {
+                    files.push(file.clone());
+                }
+            }
+        }
+
+        files
+    }
+
+    /// Add a new rule to a rules file and reload
+    /// If no file is given, the rule goes into the only loaded rules file
+    pub fn create_rule(&self, mut rule: Rule, file: Option<&str>) -> Result<Rule, AppError> {
+        Self::validate_draft_rule(&rule).map_err(AppError::InvalidRule)?;
+
+        if self.rules.read().unwrap().iter().any(|r| r.id == rule.id) {
+            return Err(AppError::RuleExists(rule.id));
+        }
+
+        let files = self.rule_files();
+        let target = match file {
+            Some(file) => files
+                .into_iter()
+                .find(|f| f == file)
+                .ok_or_else(|| AppError::InvalidRule(format!("Unknown rules file: {}", file)))?,
+            None if files.len() == 1 => files[0].clone(),
+            None => {
+                return Err(AppError::InvalidRule(
+                    "Several rules files are loaded, specify the target file".to_string(),
+                ));
+            },
+        };
+
+        let mut draft = self.get_rules();
+        draft.push(rule.clone());
+        Self::check_draft(draft)?;
+
+        self.persist(&target, || loader::insert_rule_into_file(&target, &rule))?;
+        self.reload()?;
+
+        tracing::info!("Rule '{}' created in {}", rule.id, target);
+
+        rule.pack = self.loaded_pack(&rule.id, &target);
+        rule.source_file = Some(target);
+        Ok(rule)
+    }
+
+    /// Replace an existing rule in its source file and reload
+    /// Returns None if the rule was not found
+    pub fn update_rule(&self, rule_id: &str, mut rule: Rule) -> Result<Option<Rule>, AppError> {
+        Self::validate_draft_rule(&rule).map_err(AppError::InvalidRule)?;
+
+        let (source_file, file_id) = {
             let rules = self.rules.read().unwrap();
-            loader::save_rules_to_file(path, &rules)?;
-        } else {
-            tracing::warn!("Rule '{}' has no source file, cannot persist", rule_id);
+            let Some(index) = Self::find_rule(&rules, rule_id)? else {
+                return Ok(None);
+            };
+            let existing = &rules[index];
+            if rule.id != existing.id && rules.iter().any(|r| r.id == rule.id) {
+                return Err(AppError::RuleExists(rule.id));
+            }
+
+            let mut draft = rules.clone();
+            draft[index] = Rule {
+                source_file: existing.source_file.clone(),
+                pack: existing.pack.clone(),
+                ..rule.clone()
+            };
+            Self::check_draft(draft)?;
+
+            (existing.source_file.clone(), existing.id.clone())
+        };
+
+        let path = source_file.ok_or_else(|| {
+            AppError::InvalidRule(format!(
+                "Rule '{}' has no source file, cannot persist",
+                rule_id
+            ))
+        })?;
+
+        self.persist(&path, || {
+            loader::replace_rule_in_file(&path, &file_id, &rule)
+        })?;
+        self.reload()?;
+
+        tracing::info!("Rule '{}' updated in {}", rule_id, path);
+
+        rule.pack = self.loaded_pack(&rule.id, &path);
+        rule.source_file = Some(path);
+        Ok(Some(rule))
+    }
+
+    /// Validate a rule written or previewed through the API. Shell rules can
+    /// only come from rules files: over the API they would let any caller run
+    /// commands.
+    fn validate_draft_rule(rule: &Rule) -> Result<(), String> {
+        if rule.rule_type == RuleType::Shell {
+            return Err(format!(
+                "Rule '{}': shell rules can only be added in a rules file",
+                rule.id
+            ));
         }
+        rule.validate()
+    }
 
-        Ok(Some(new_state))
+    /// Pack of a rule loaded from the given file
+    fn loaded_pack(&self, rule_id: &str, file: &str) -> Option<Arc<PackInfo>> {
+        self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .find(|r| r.id == rule_id && r.source_file.as_deref() == Some(file))
+            .and_then(|r| r.pack.clone())
+    }
+
+    /// Remove a rule from its source file and reload
+    /// Returns false if the rule was not found
+    pub fn delete_rule(&self, rule_id: &str) -> Result<bool, AppError> {
+        let (source_file, file_id) = {
+            let rules = self.rules.read().unwrap();
+            let Some(index) = Self::find_rule(&rules, rule_id)? else {
+                return Ok(false);
+            };
+
+            let mut draft = rules.clone();
+            draft.remove(index);
+            Self::check_draft(draft)?;
+
+            (rules[index].source_file.clone(), rules[index].id.clone())
+        };
+
+        let path = source_file.ok_or_else(|| {
+            AppError::InvalidRule(format!(
+                "Rule '{}' has no source file, cannot persist",
+                rule_id
+            ))
+        })?;
+
+        self.persist(&path, || loader::remove_rule_from_file(&path, &file_id))?;
+        self.reload()?;
+
+        tracing::info!("Rule '{}' deleted from {}", rule_id, path);
+
+        Ok(true)
+    }
+
+    /// Run inputs through the current rules and a draft rule set without logging.
+    /// The draft is the current rule set with `remove` ids dropped and `upsert`
+    /// rules replacing (by id) or extending it.
+    pub fn preview(
+        &self,
+        upsert: Vec<Rule>,
+        remove: &[String],
+        inputs: &[String],
+    ) -> PreviewOutcome {
+        let (current_rules, current_cache) = self.snapshot();
+
+        let mut errors = Vec::new();
+        let mut draft = self.get_rules();
+        draft.retain(|r| !remove.iter().any(|id| r.has_id(id)));
+
+        for rule in upsert {
+            if let Err(e) = Self::validate_draft_rule(&rule) {
+                errors.push((rule.id.clone(), e));
+                continue;
+            }
+            match draft.iter_mut().find(|r| r.id == rule.id) {
+                Some(existing) => *existing = rule,
+                None => draft.push(rule),
+            }
+        }
+
+        draft.sort_by_key(|r| std::cmp::Reverse(r.priority));
+        errors.extend(loader::requirement_errors(&draft));
+        draft.retain(|r| r.enabled);
+
+        let draft_cache: HashMap<String, Regex> = draft
+            .iter()
+            .filter(|r| r.uses_regex())
+            .filter_map(|r| {
+                let pattern = r.effective_pattern();
+                Regex::new(&pattern).ok().map(|re| (pattern, re))
+            })
+            .collect();
+
+        let results = inputs
+            .iter()
+            .map(|input| PreviewResult {
+                input: input.clone(),
+                current: self.apply_rules(&current_rules, &current_cache, input, None, None),
+                preview: self.apply_rules(&draft, &draft_cache, input, None, None),
+            })
+            .collect();
+
+        PreviewOutcome { errors, results }
+    }
+
+    /// Original inputs of the most recent requests (oldest first, without duplicates)
+    /// Only available with the full privacy mode, other modes don't log the input
+    pub fn recent_inputs(&self, limit: usize) -> Vec<String> {
+        if self.options.privacy.mode() != PrivacyMode::Full {
+            return Vec::new();
+        }
+
+        let logs = self.transformation_log.lock().unwrap();
+
+        let mut seen = HashSet::new();
+        let mut recent: Vec<String> = logs
+            .iter()
+            .rev()
+            .map(|record| record.input.as_str())
+            .filter(|input| seen.insert(*input))
+            .take(limit)
+            .map(String::from)
+            .collect();
+        recent.reverse();
+        recent
     }
 
-    /// Get recent transformation logs
-    pub fn get_transformation_log(&self) -> Vec<TransformationLog> {
+    /// Get the records of recent requests (oldest first)
+    pub fn get_transformation_log(&self) -> Vec<TransformationRecord> {
         self.transformation_log
             .lock()
             .unwrap()
@@ -152,6 +695,11 @@ impl RuleEngine {
             .collect()
     }
 
+    /// Receive every request logged from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<TransformationRecord> {
+        self.events.subscribe()
+    }
+
     /// Clear transformation log
     pub fn clear_transformation_log(&self) {
         self.transformation_log.lock().unwrap().clear();
@@ -160,50 +708,249 @@ impl RuleEngine {
     /// Apply all enabled rules to the input text
     /// Rules are pre-sorted by priority during load, so this is O(N) not O(N log N)
     pub fn apply(&self, text: &str) -> String {
-        // Clone rules and cache to release locks before processing
-        // This prevents slow shell commands from blocking other requests
-        let (active_rules, cache) = {
-            let rules = self.rules.read().unwrap();
-            let cache = self.regex_cache.read().unwrap();
-            let filtered: Vec<Rule> = rules.iter().filter(|r| r.enabled).cloned().collect();
-            (filtered, cache.clone())
-        };
+        self.transform(text).0
+    }
+
+    /// Apply all enabled rules and log the request
+    /// Returns the output and the record as it was logged, i.e. with the
+    /// privacy mode applied to the input and the intermediate outputs
+    pub fn transform(&self, text: &str) -> (String, TransformationRecord) {
+        self.transform_extracted(text, None)
+    }
+
+    /// Like [`Self::transform`], for text pulled out of a prompt by an extractor
+    /// whose name is recorded in the log
+    pub fn transform_extracted(
+        &self,
+        text: &str,
+        extractor: Option<String>,
+    ) -> (String, TransformationRecord) {
+        let (active_rules, cache) = self.snapshot();
+        self.transform_with(&active_rules, &cache, text, extractor)
+    }
+
+    /// Apply the rules that every selector selects without logging the request
+    pub fn transform_selected(&self, text: &str, selectors: &[&RuleSelector]) -> String {
+        let (active_rules, cache) = self.snapshot_selected(selectors);
+        self.apply_rules(&active_rules, &cache, text, None, None)
+    }
+
+    /// Apply the selected rules without logging the request, and record every
+    /// rule evaluated with the text before and after it
+    pub fn trace(&self, text: &str, selectors: &[&RuleSelector]) -> Trace {
+        let started = Instant::now();
+        let (active_rules, cache) = self.snapshot_selected(selectors);
+
+        let mut steps = Vec::new();
+        let output = self.apply_rules(&active_rules, &cache, text, None, Some(&mut steps));
 
+        Trace {
+            output,
+            duration: started.elapsed(),
+            steps,
+        }
+    }
+
+    /// Profile of the given name
+    pub fn profile(&self, name: &str) -> Option<&RuleSelector> {
+        self.options.profiles.get(name)
+    }
+
+    /// Like [`Self::transform_selected`] for many texts, spread over all cores
+    /// The outputs are in the order of the texts. Batches are not logged: a
+    /// large one would push every other request out of the log.
+    /// A panicking rule fails the batch with the position of its text; release
+    /// builds are compiled with `panic = "abort"` and end the process instead.
+    pub fn transform_batch(
+        &self,
+        texts: &[String],
+        selectors: &[&RuleSelector],
+    ) -> Result<Vec<String>, AppError> {
+        let (active_rules, cache) = self.snapshot_selected(selectors);
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = texts.len().div_ceil(threads).max(1);
+
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = texts
+                .chunks(chunk_size)
+                .enumerate()
+                .map(|(number, chunk)| {
+                    let (rules, cache) = (&active_rules, &cache);
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .enumerate()
+                            .map(|(i, text)| {
+                                panic::catch_unwind(AssertUnwindSafe(|| {
+                                    self.apply_rules(rules, cache, text, None, None)
+                                }))
+                                .map_err(|_| {
+                                    AppError::TransformFailed(format!(
+                                        "text {} of the batch panicked",
+                                        number * chunk_size + i
+                                    ))
+                                })
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                })
+                .collect();
+
+            let mut outputs = Vec::with_capacity(texts.len());
+            for worker in workers {
+                let chunk = worker
+                    .join()
+                    .unwrap_or_else(|payload| panic::resume_unwind(payload))?;
+                outputs.extend(chunk);
+            }
+            Ok(outputs)
+        })
+    }
+
+    /// Apply the given rules and log the request
+    fn transform_with(
+        &self,
+        rules: &[Rule],
+        cache: &HashMap<String, Regex>,
+        text: &str,
+        extractor: Option<String>,
+    ) -> (String, TransformationRecord) {
+        let started = Instant::now();
+
+        let mut record = TransformationRecord::start(text);
+        record.extractor = extractor;
+        let output = self.apply_rules(rules, cache, text, Some(&mut record), None);
+        record.output = output.clone();
+        record.duration = started.elapsed();
+
+        self.options.privacy.apply(&mut record);
+        self.log_transformation(record.clone());
+        (output, record)
+    }
+
+    /// Privacy mode for text logged about requests
+    pub fn privacy(&self) -> &Privacy {
+        &self.options.privacy
+    }
+
+    /// Clone the enabled rules and the regex cache
+    /// Releasing the locks before processing prevents slow shell commands
+    /// from blocking other requests
+    fn snapshot(&self) -> (Vec<Rule>, HashMap<String, Regex>) {
+        let rules = self.rules.read().unwrap();
+        let cache = self.regex_cache.read().unwrap();
+        let filtered: Vec<Rule> = rules.iter().filter(|r| r.enabled).cloned().collect();
+        (filtered, cache.clone())
+    }
+
+    /// Like [`Self::snapshot`], keeping only the rules that every selector selects
+    fn snapshot_selected(
+        &self,
+        selectors: &[&RuleSelector],
+    ) -> (Vec<Rule>, HashMap<String, Regex>) {
+        let (mut rules, cache) = self.snapshot();
+        rules.retain(|rule| selectors.iter().all(|selector| selector.matches(rule)));
+        (rules, cache)
+    }
+
+    /// Apply the given (sorted, enabled) rules to the text
+    /// Matching and skipped rules are added to `trace`, if given, and every
+    /// evaluated rule to `steps`, if given
+    fn apply_rules(
+        &self,
+        rules: &[Rule],
+        cache: &HashMap<String, Regex>,
+        text: &str,
+        mut trace: Option<&mut TransformationRecord>,
+        mut steps: Option<&mut Vec<RuleStep>>,
+    ) -> String {
         let mut result = text.to_string();
 
+        // Rules that changed the text so far, for `requires_*` conditions
+        let mut matched_rules: Vec<&Rule> = Vec::new();
+
+        // Groups that already had a matching rule, with the winning rule
+        let mut group_winners: HashMap<&str, &str> = HashMap::new();
+
         // Rules are pre-sorted by priority (descending) during load
-        for rule in active_rules.iter() {
+        for rule in rules.iter() {
             // Skip shell rules if not enabled (security)
-            if matches!(rule.rule_type, RuleType::Shell) && !self.enable_shell_rules {
+            if matches!(rule.rule_type, RuleType::Shell) && !self.options.enable_shell_rules {
                 tracing::trace!("Skipping shell rule '{}' (shell rules disabled)", rule.id);
+                if let Some(steps) = steps.as_deref_mut() {
+                    steps.push(RuleStep::skipped(rule, &result, "shell rules disabled"));
+                }
+                continue;
+            }
+
+            // Rules whose conditions don't hold and group members after the
+            // group's first match are skipped
+            let skip_reason = rule.unmet_requirement(&matched_rules).or_else(|| {
+                let group = rule.group.as_deref()?;
+                let winner = group_winners.get(group)?;
+                Some(format!("group '{}' already matched by '{}'", group, winner))
+            });
+            if let Some(reason) = skip_reason {
+                tracing::trace!("Skipping rule '{}': {}", rule.id, reason);
+                if let Some(steps) = steps.as_deref_mut() {
+                    steps.push(RuleStep::skipped(rule, &result, &reason));
+                }
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.skipped.push(SkippedRule {
+                        rule_id: rule.id.clone(),
+                        reason,
+                    });
+                }
                 continue;
             }
 
             let before = result.clone();
+            let rule_started = Instant::now();
 
             result = match rule.rule_type {
-                RuleType::Regex => Self::apply_regex_rule(rule, &result, &cache),
+                RuleType::Regex | RuleType::Literal => Self::apply_regex_rule(rule, &result, cache),
                 RuleType::Shell => self.apply_shell_rule(rule, &result),
                 RuleType::Function => Self::apply_function_rule(rule, &result),
             };
 
-            // Log transformation
-            let matched = before != result;
-            self.log_transformation(TransformationLog {
-                rule_id: rule.id.clone(),
-                rule_type: format!("{:?}", rule.rule_type),
-                input: before.clone(),
-                output: result.clone(),
-                matched,
-            });
+            if let Some(steps) = steps.as_deref_mut() {
+                let duration = rule_started.elapsed();
+                let spans = match rule.rule_type {
+                    RuleType::Regex | RuleType::Literal => cache
+                        .get(&rule.effective_pattern())
+                        .map(|regex| regex.find_iter(&before).map(|m| m.range()).collect())
+                        .unwrap_or_default(),
+                    _ => Vec::new(),
+                };
+                steps.push(RuleStep {
+                    spans,
+                    duration,
+                    ..RuleStep::new(rule, &before, &result)
+                });
+            }
+
+            if before != result {
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.matches.push(RuleMatch {
+                        rule_id: rule.id.clone(),
+                        qualified_id: rule.qualified_id(),
+                        rule_type: format!("{:?}", rule.rule_type),
+                        group: rule.group.clone(),
+                        output: result.clone(),
+                    });
+                }
+
+                matched_rules.push(rule);
+                if let Some(group) = rule.group.as_deref() {
+                    group_winners.insert(group, &rule.id);
+                }
 
-            if matched {
                 tracing::debug!(
                     "Rule '{}' ({:?}) transformed: '{}' -> '{}'",
                     rule.id,
                     rule.rule_type,
-                    before,
-                    result
+                    self.options.privacy.text(&before),
+                    self.options.privacy.text(&result)
                 );
 
                 // Stop processing if rule has stop_on_match flag
@@ -220,10 +967,15 @@ impl RuleEngine {
         result
     }
 
-    /// Apply a regex-based rule
+    /// Apply a regex-based (or literal) rule
     fn apply_regex_rule(rule: &Rule, text: &str, cache: &HashMap<String, Regex>) -> String {
-        if let Some(regex) = cache.get(&rule.id) {
-            regex.replace_all(text, &rule.replacement).to_string()
+        if let Some(regex) = cache.get(&rule.effective_pattern()) {
+            match rule.rule_type {
+                RuleType::Literal => regex
+                    .replace_all(text, NoExpand(&rule.replacement))
+                    .to_string(),
+                _ => regex.replace_all(text, &rule.replacement).to_string(),
+            }
         } else {
             tracing::warn!(
                 "Regex cache miss for rule '{}' - check compilation logs",
@@ -298,21 +1050,26 @@ impl RuleEngine {
         }
     }
 
-    /// Log a transformation
-    fn log_transformation(&self, log: TransformationLog) {
+    /// Log a request and pass it on to subscribers
+    fn log_transformation(&self, record: TransformationRecord) {
+        if self.events.receiver_count() > 0 {
+            // Only fails when the last subscriber just went away
+            let _ = self.events.send(record.clone());
+        }
+
         let mut logs = self.transformation_log.lock().unwrap();
 
-        logs.push_back(log);
+        logs.push_back(record);
 
         // Trim oldest entries if over limit (efficient with VecDeque)
-        while logs.len() > self.max_log_entries {
+        while logs.len() > self.options.max_log_entries {
             logs.pop_front();
         }
     }
 
     /// Reload rules from all paths
     pub fn reload(&self) -> Result<(), AppError> {
-        let new_rules = loader::load_rules_from_paths(&self.rules_paths)?;
+        let new_rules = Self::load_rules(&self.rules_paths, &self.options)?;
 
         tracing::info!(
             "Reloading {} rules from {:?}",
@@ -336,58 +1093,82 @@ impl RuleEngine {
     /// Watchers are stored in the engine to keep them alive
     pub fn watch_for_changes(self: Arc<Self>) -> Result<(), AppError> {
         let mut watchers_guard = self.watchers.lock().unwrap();
-        let mut watched_paths = std::collections::HashSet::new();
 
-        for path in &self.rules_paths {
-            // Resolve glob patterns to actual paths to watch
-            let paths_to_watch = Self::resolve_watch_paths(path);
+        // Directory -> file names to react to (None = every rules file in it)
+        let mut targets: HashMap<PathBuf, Option<HashSet<OsString>>> = HashMap::new();
 
-            for watch_path in paths_to_watch {
-                // Avoid watching the same path twice
-                if watched_paths.contains(&watch_path) {
-                    continue;
+        for path in &self.rules_paths {
+            // Resolve glob patterns to actual directories to watch
+            for (dir, file) in Self::resolve_watch_paths(path) {
+                let entry = targets.entry(dir).or_insert_with(|| Some(HashSet::new()));
+                match (entry.as_mut(), file) {
+                    (Some(names), Some(name)) => {
+                        names.insert(name);
+                    },
+                    _ => *entry = None,
                 }
-                watched_paths.insert(watch_path.clone());
+            }
+        }
 
-                match loader::watch_rules_file(watch_path.clone(), self.clone()) {
-                    Ok(watcher) => {
-                        tracing::debug!("Watching: {}", watch_path.display());
-                        watchers_guard.push(watcher);
-                    },
-                    Err(e) => {
-                        tracing::warn!("Could not watch {}: {}", watch_path.display(), e);
-                    },
+        // The overrides file may not exist yet, so watch its directory for it
+        if let Some(path) = &self.options.overrides_path {
+            let path = Path::new(path);
+            let dir = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            if let (true, Some(name)) = (dir.is_dir(), path.file_name()) {
+                let entry = targets.entry(dir).or_insert_with(|| Some(HashSet::new()));
+                if let Some(names) = entry.as_mut() {
+                    names.insert(name.to_os_string());
                 }
             }
         }
 
+        for (dir, files) in targets {
+            match loader::watch_rules_dir(dir.clone(), files, self.clone()) {
+                Ok(watcher) => {
+                    tracing::debug!("Watching: {}", dir.display());
+                    watchers_guard.push(watcher);
+                },
+                Err(e) => {
+                    tracing::warn!("Could not watch {}: {}", dir.display(), e);
+                },
+            }
+        }
+
         Ok(())
     }
 
-    /// Resolve a path (which may be a glob pattern) to actual paths to watch
-    fn resolve_watch_paths(path: &str) -> Vec<PathBuf> {
+    /// Resolve a path (which may be a glob pattern) to directories to watch,
+    /// each with the file name to react to (None = every rules file)
+    fn resolve_watch_paths(path: &str) -> Vec<(PathBuf, Option<OsString>)> {
         let path_buf = PathBuf::from(path);
 
-        // If it's an existing file, watch it directly
+        // If it's an existing file, watch its directory for this file
         if path_buf.is_file() {
-            return vec![path_buf];
+            let dir = match path_buf.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            return vec![(dir, path_buf.file_name().map(OsString::from))];
         }
 
         // If it's an existing directory, watch it
         if path_buf.is_dir() {
-            return vec![path_buf];
+            return vec![(path_buf, None)];
         }
 
         // Try as glob pattern - watch matching files' parent directories
         if let Ok(entries) = glob::glob(path) {
-            let mut dirs = std::collections::HashSet::new();
+            let mut dirs = HashSet::new();
             for entry in entries.flatten() {
                 if let Some(parent) = entry.parent() {
                     dirs.insert(parent.to_path_buf());
                 }
             }
             if !dirs.is_empty() {
-                return dirs.into_iter().collect();
+                return dirs.into_iter().map(|dir| (dir, None)).collect();
             }
         }
 
@@ -396,13 +1177,60 @@ impl RuleEngine {
         if let Some(base) = path.split("**").next() {
             let base_path = PathBuf::from(base.trim_end_matches('/'));
             if base_path.is_dir() {
-                return vec![base_path];
+                return vec![(base_path, None)];
             }
         }
 
         vec![]
     }
 
+    /// Run a write to a rules file, remembering it as our own write
+    /// so the file watcher does not reload because of it
+    fn persist<F>(&self, path: &str, write: F) -> Result<(), AppError>
+    where
+        F: FnOnce() -> Result<(), AppError>,
+    {
+        let real_path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+
+        // Mark as pending first: watcher events may arrive before the write returns
+        self.own_writes
+            .lock()
+            .unwrap()
+            .insert(real_path.clone(), None);
+
+        let result = write();
+
+        let modified = fs::metadata(&real_path).and_then(|m| m.modified()).ok();
+        let mut own_writes = self.own_writes.lock().unwrap();
+        match modified {
+            Some(modified) if result.is_ok() => {
+                own_writes.insert(real_path, Some(modified));
+            },
+            _ => {
+                own_writes.remove(&real_path);
+            },
+        }
+
+        result
+    }
+
+    /// Whether the file's current contents were written by this engine
+    pub fn is_own_write(&self, path: &Path) -> bool {
+        let Ok(real_path) = fs::canonicalize(path) else {
+            return false;
+        };
+
+        match self.own_writes.lock().unwrap().get(&real_path) {
+            // Write in progress
+            Some(None) => true,
+            // Unchanged since our write
+            Some(Some(written)) => fs::metadata(&real_path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified == *written),
+            None => false,
+        }
+    }
+
     /// Compile all regex patterns and cache them
     fn compile_regexes(&self) -> Result<(), AppError> {
         let rules = self.rules.read().unwrap();
@@ -411,11 +1239,14 @@ impl RuleEngine {
         cache.clear();
 
         for rule in rules.iter() {
-            if matches!(rule.rule_type, RuleType::Regex) {
+            if rule.uses_regex() {
                 let pattern = rule.effective_pattern();
+                if cache.contains_key(&pattern) {
+                    continue;
+                }
                 match Regex::new(&pattern) {
                     Ok(regex) => {
-                        cache.insert(rule.id.clone(), regex);
+                        cache.insert(pattern, regex);
                     },
                     Err(e) => {
                         tracing::error!("Invalid regex in rule '{}': {}", rule.id, e);
@@ -449,6 +1280,7 @@ mod tests {
         let rules = vec![Rule {
             id: "slash".to_string(),
             description: Some("slash -> /".to_string()),
+            tags: Vec::new(),
             rule_type: RuleType::Regex,
             pattern: r"(?i)\bslash\b".to_string(),
             replacement: "/".to_string(),
@@ -457,13 +1289,20 @@ mod tests {
             ignore_case: false,
             timeout_ms: 5000,
             stop_on_match: false,
+            group: None,
+            requires_matched: Vec::new(),
+            requires_not_matched: Vec::new(),
             source_file: None,
+            pack: None,
+            overridden: Vec::new(),
         }];
 
         let file = create_test_rules_file(&rules);
-        let engine =
-            RuleEngine::new_from_paths(&[file.path().to_str().unwrap().to_string()], false)
-                .unwrap();
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
 
         assert_eq!(engine.apply("foo slash bar"), "foo / bar");
     }
@@ -473,6 +1312,7 @@ mod tests {
         let rules = vec![Rule {
             id: "upper".to_string(),
             description: Some("Convert to uppercase".to_string()),
+            tags: Vec::new(),
             rule_type: RuleType::Function,
             pattern: "uppercase".to_string(),
             replacement: String::new(),
@@ -481,13 +1321,20 @@ mod tests {
             ignore_case: false,
             timeout_ms: 5000,
             stop_on_match: false,
+            group: None,
+            requires_matched: Vec::new(),
+            requires_not_matched: Vec::new(),
             source_file: None,
+            pack: None,
+            overridden: Vec::new(),
         }];
 
         let file = create_test_rules_file(&rules);
-        let engine =
-            RuleEngine::new_from_paths(&[file.path().to_str().unwrap().to_string()], false)
-                .unwrap();
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
 
         assert_eq!(engine.apply("hello world"), "HELLO WORLD");
     }
@@ -497,6 +1344,7 @@ mod tests {
         let rules = vec![Rule {
             id: "echo".to_string(),
             description: Some("Echo with prefix".to_string()),
+            tags: Vec::new(),
             rule_type: RuleType::Shell,
             pattern: "cat | tr 'a-z' 'A-Z'".to_string(),
             replacement: String::new(),
@@ -505,13 +1353,24 @@ mod tests {
             ignore_case: false,
             timeout_ms: 5000,
             stop_on_match: false,
+            group: None,
+            requires_matched: Vec::new(),
+            requires_not_matched: Vec::new(),
             source_file: None,
+            pack: None,
+            overridden: Vec::new(),
         }];
 
         let file = create_test_rules_file(&rules);
         // Shell rules need enable_shell_rules=true
-        let engine =
-            RuleEngine::new_from_paths(&[file.path().to_str().unwrap().to_string()], true).unwrap();
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions {
+                enable_shell_rules: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
 
         assert_eq!(engine.apply("hello"), "HELLO");
     }
@@ -521,6 +1380,7 @@ mod tests {
         let rules = vec![Rule {
             id: "test".to_string(),
             description: None,
+            tags: Vec::new(),
             rule_type: RuleType::Regex,
             pattern: r"foo".to_string(),
             replacement: "bar".to_string(),
@@ -529,19 +1389,592 @@ mod tests {
             ignore_case: false,
             timeout_ms: 5000,
             stop_on_match: false,
+            group: None,
+            requires_matched: Vec::new(),
+            requires_not_matched: Vec::new(),
             source_file: None,
+            pack: None,
+            overridden: Vec::new(),
         }];
 
         let file = create_test_rules_file(&rules);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
+
+        engine.apply("foo test");
+        engine.apply("no match");
+
+        let logs = engine.get_transformation_log();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].input, "foo test");
+        assert_eq!(logs[0].output, "bar test");
+        assert_eq!(logs[0].matches.len(), 1);
+        assert_eq!(logs[0].matches[0].rule_id, "test");
+        assert_eq!(logs[0].matches[0].output, "bar test");
+        assert!(logs[1].matches.is_empty());
+        assert_ne!(logs[0].id, logs[1].id);
+    }
+
+    #[test]
+    fn test_transformation_log_is_bounded() {
+        let file = create_test_rules_file(&[test_rule("slash", r"\bslash\b", "/")]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions {
+                max_log_entries: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for input in ["a", "b slash", "c"] {
+            engine.apply(input);
+        }
+
+        let inputs: Vec<String> = engine
+            .get_transformation_log()
+            .into_iter()
+            .map(|r| r.input)
+            .collect();
+        assert_eq!(inputs, ["b slash", "c"]);
+    }
+
+    #[test]
+    fn test_privacy_mode_applies_to_log_only() {
+        let file = create_test_rules_file(&[test_rule("slash", r"\bslash\b", "/")]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions {
+                privacy: Privacy::new(PrivacyMode::Metadata, &[]).unwrap(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let (output, record) = engine.transform("a slash b");
+        assert_eq!(output, "a / b");
+        assert_eq!(record.input, "[9 chars]");
+        assert_eq!(record.matches[0].rule_id, "slash");
+        assert_eq!(record.matches[0].output, "[5 chars]");
+        assert_eq!(engine.get_transformation_log()[0].output, "[5 chars]");
+
+        // Inputs for previews are not kept either
+        assert!(engine.recent_inputs(10).is_empty());
+    }
+
+    #[test]
+    fn test_subscribe_receives_each_request() {
+        let file = create_test_rules_file(&[test_rule("slash", r"\bslash\b", "/")]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
+
+        engine.apply("before subscribing");
+        let mut events = engine.subscribe();
+        engine.apply("a slash b");
+        engine.apply("a slash b");
+
+        let first = events.try_recv().unwrap();
+        let second = events.try_recv().unwrap();
+        assert_eq!(first.output, "a / b");
+        assert_eq!(second.input, "a slash b");
+        assert_ne!(first.id, second.id);
+        assert!(events.try_recv().is_err());
+    }
+
+    fn test_rule(id: &str, pattern: &str, replacement: &str) -> Rule {
+        Rule {
+            id: id.to_string(),
+            description: None,
+            tags: Vec::new(),
+            rule_type: RuleType::Regex,
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            priority: 100,
+            enabled: true,
+            ignore_case: false,
+            timeout_ms: 5000,
+            stop_on_match: false,
+            group: None,
+            requires_matched: Vec::new(),
+            requires_not_matched: Vec::new(),
+            source_file: None,
+            pack: None,
+            overridden: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_apply_literal_rule() {
+        let mut rule = test_rule("price", "$1.00", "$2");
+        rule.rule_type = RuleType::Literal;
+
+        let file = create_test_rules_file(&[rule]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(engine.apply("costs $1.00 now"), "costs $2 now");
+    }
+
+    #[test]
+    fn test_create_update_delete_rule() {
+        let file = create_test_rules_file(&[test_rule("slash", r"\bslash\b", "/")]);
+        let path = file.path().to_str().unwrap().to_string();
         let engine =
-            RuleEngine::new_from_paths(&[file.path().to_str().unwrap().to_string()], false)
+            RuleEngine::with_options(std::slice::from_ref(&path), EngineOptions::default())
                 .unwrap();
 
-        engine.apply("foo test");
+        // Create: a single loaded file is used as the default target
+        let created = engine
+            .create_rule(test_rule("dot", r"\bdot\b", "."), None)
+            .unwrap();
+        assert_eq!(created.source_file.as_deref(), Some(path.as_str()));
+        assert_eq!(engine.apply("a slash b dot c"), "a / b . c");
+        assert!(matches!(
+            engine.create_rule(test_rule("dot", "x", "y"), None),
+            Err(AppError::RuleExists(_))
+        ));
+
+        // Update
+        engine
+            .update_rule("dot", test_rule("dot", r"\bdot\b", "[.]"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(engine.apply("a dot b"), "a [.] b");
+        assert!(matches!(
+            engine.update_rule("dot", test_rule("dot", "(", "")),
+            Err(AppError::InvalidRule(_))
+        ));
+
+        // Shell rules cannot be written through the API
+        let mut shell = test_rule("shell", "echo hi", "");
+        shell.rule_type = RuleType::Shell;
+        assert!(matches!(
+            engine.create_rule(shell.clone(), None),
+            Err(AppError::InvalidRule(_))
+        ));
+        shell.id = "dot".to_string();
+        assert!(matches!(
+            engine.update_rule("dot", shell),
+            Err(AppError::InvalidRule(_))
+        ));
+
+        // Delete
+        assert!(engine.delete_rule("dot").unwrap());
+        assert!(!engine.delete_rule("dot").unwrap());
+        assert_eq!(engine.apply("a dot b"), "a dot b");
+
+        // Changes are persisted
+        let saved = loader::load_rules(&PathBuf::from(&path)).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].id, "slash");
+    }
+
+    #[test]
+    fn test_preview_draft_rules() {
+        let file = create_test_rules_file(&[test_rule("slash", r"\bslash\b", "/")]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
+
+        engine.apply("foo slash bar");
+        engine.apply("hello dot world");
+        assert_eq!(
+            engine.recent_inputs(10),
+            vec!["foo slash bar", "hello dot world"]
+        );
+
+        let mut shell = test_rule("shell", "echo shell", "");
+        shell.rule_type = RuleType::Shell;
+        let outcome = engine.preview(
+            vec![
+                test_rule("dot", r"\bdot\b", "."),
+                test_rule("bad", "(", ""),
+                shell,
+            ],
+            &["slash".to_string()],
+            &engine.recent_inputs(10),
+        );
+
+        // Invalid and shell rules are reported and left out of the draft
+        assert_eq!(outcome.errors.len(), 2);
+        assert_eq!(outcome.errors[0].0, "bad");
+        assert_eq!(outcome.errors[1].0, "shell");
+        assert_eq!(outcome.results[0].current, "foo / bar");
+        assert_eq!(outcome.results[0].preview, "foo slash bar");
+        assert_eq!(outcome.results[1].current, "hello dot world");
+        assert_eq!(outcome.results[1].preview, "hello . world");
+
+        // Previews are not logged
+        assert_eq!(engine.get_transformation_log().len(), 2);
+    }
+
+    #[test]
+    fn test_toggle_preserves_file_formatting() {
+        let original = r#"[
+  { "id": "slash",   "pattern": "slash", "replacement": "/" },
+  {
+    "id": "dot",
+    "pattern": "dot",
+    "replacement": ".",
+    "enabled": true
+  }
+]
+"#;
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(original.as_bytes()).unwrap();
+        let path = file.path().to_str().unwrap().to_string();
+        let engine =
+            RuleEngine::with_options(std::slice::from_ref(&path), EngineOptions::default())
+                .unwrap();
+
+        assert_eq!(engine.toggle_rule("dot").unwrap(), Some(false));
+        assert_eq!(engine.toggle_rule("slash").unwrap(), Some(false));
+
+        let saved = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            saved,
+            original
+                .replace(
+                    r#""replacement": "/" }"#,
+                    r#""replacement": "/", "enabled": false }"#
+                )
+                .replace(r#""enabled": true"#, r#""enabled": false"#)
+        );
+
+        // The watcher must not reload for our own write, but does for external edits
+        assert!(engine.is_own_write(file.path()));
+        std::thread::sleep(Duration::from_millis(20));
+        fs::write(&path, &saved).unwrap();
+        assert!(!engine.is_own_write(file.path()));
+    }
+
+    #[test]
+    fn test_overrides_patch_rules_and_receive_toggles() {
+        let dir = tempfile::tempdir().unwrap();
+        let rules_path = dir.path().join("rules.json");
+        let overrides_path = dir.path().join("overrides.json");
+        let rules = r#"[{ "id": "dot", "pattern": "dot", "replacement": "." }]"#;
+        fs::write(&rules_path, rules).unwrap();
+        fs::write(
+            &overrides_path,
+            r#"{ "overrides": { "dot": { "replacement": "!" } } }"#,
+        )
+        .unwrap();
+
+        let options = EngineOptions {
+            overrides_path: Some(overrides_path.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let engine =
+            RuleEngine::with_options(&[rules_path.to_str().unwrap().to_string()], options).unwrap();
+        assert_eq!(engine.apply("a dot"), "a !");
+
+        // Toggling a pack rule leaves the pack untouched
+        assert_eq!(engine.toggle_rule("dot").unwrap(), Some(false));
+        assert_eq!(fs::read_to_string(&rules_path).unwrap(), rules);
+        assert_eq!(
+            fs::read_to_string(&overrides_path).unwrap(),
+            r#"{ "overrides": { "dot": { "replacement": "!", "enabled": false } } }"#
+        );
+        assert_eq!(engine.get_rules()[0].overridden, ["replacement", "enabled"]);
+
+        engine.reload().unwrap();
+        assert_eq!(engine.apply("a dot"), "a dot");
+    }
+
+    #[test]
+    fn test_toggle_changes_memory_only_after_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let rules_path = dir.path().join("rules.json");
+        let overrides_path = dir.path().join("overrides.yaml");
+        fs::write(
+            &rules_path,
+            r#"[{ "id": "dot", "pattern": "dot", "replacement": "." }]"#,
+        )
+        .unwrap();
+        fs::write(&overrides_path, "# Local changes\n").unwrap();
+
+        let options = EngineOptions {
+            overrides_path: Some(overrides_path.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let engine =
+            RuleEngine::with_options(&[rules_path.to_str().unwrap().to_string()], options).unwrap();
+
+        // YAML overrides files are edited in place
+        assert_eq!(engine.toggle_rule("dot").unwrap(), Some(false));
+        assert_eq!(
+            fs::read_to_string(&overrides_path).unwrap(),
+            "# Local changes\noverrides:\n  dot:\n    enabled: false\n"
+        );
+
+        // A failed write leaves the rule as it was
+        fs::write(&overrides_path, "overrides: {dot: {enabled: false}}\n").unwrap();
+        assert!(engine.toggle_rule("dot").is_err());
+        assert!(!engine.get_rules()[0].enabled);
+        assert!(engine.set_enabled(&RuleFilter::default(), true).is_err());
+        assert!(!engine.get_rules()[0].enabled);
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.json");
+        let second = dir.path().join("second.yaml");
+        fs::write(
+            &first,
+            r#"[{ "id": "dot", "pattern": "dot", "replacement": "." }]"#,
+        )
+        .unwrap();
+        fs::write(&second, "- id: dot\n  pattern: point\n  replacement: '!'\n").unwrap();
+        let paths = [
+            first.to_str().unwrap().to_string(),
+            second.to_str().unwrap().to_string(),
+        ];
+
+        let err = RuleEngine::with_options(&paths, EngineOptions::default())
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("Duplicate rule id 'dot'"),
+            "{}",
+            err
+        );
+
+        // Lenient mode loads both; each rule keeps its own regex
+        let options = EngineOptions {
+            allow_duplicate_ids: true,
+            ..Default::default()
+        };
+        let engine = RuleEngine::with_options(&paths, options).unwrap();
+        assert_eq!(engine.apply("dot point"), ". !");
+
+        assert!(matches!(
+            engine.toggle_rule("dot"),
+            Err(AppError::AmbiguousRuleId(_))
+        ));
+        assert_eq!(engine.toggle_rule("second:dot").unwrap(), Some(false));
+        assert_eq!(engine.apply("dot point"), ". point");
+        assert!(
+            fs::read_to_string(&second)
+                .unwrap()
+                .contains("enabled: false")
+        );
+        assert!(!fs::read_to_string(&first).unwrap().contains("enabled"));
+    }
+
+    #[test]
+    fn test_overlapping_paths_load_files_once() {
+        let file = create_test_rules_file(&[test_rule("dot", "dot", ".")]);
+        let path = file.path().to_str().unwrap().to_string();
+        let engine =
+            RuleEngine::with_options(&[path.clone(), path], EngineOptions::default()).unwrap();
+        assert_eq!(engine.rules_count(), 1);
+    }
+
+    #[test]
+    fn test_set_enabled_by_tag() {
+        let original = r#"[
+  { "id": "dot", "pattern": "dot", "replacement": ".", "tags": ["punctuation"] },
+  { "id": "comma", "pattern": "comma", "replacement": ",", "tags": ["punctuation"] },
+  { "id": "one", "pattern": "one", "replacement": "1", "enabled": false }
+]
+"#;
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(original.as_bytes()).unwrap();
+        let path = file.path().to_str().unwrap().to_string();
+        let engine =
+            RuleEngine::with_options(std::slice::from_ref(&path), EngineOptions::default())
+                .unwrap();
+
+        let filter = RuleFilter {
+            tag: Some("punctuation".to_string()),
+            ..Default::default()
+        };
+        let changed = engine.set_enabled(&filter, false).unwrap();
+        assert_eq!(changed.len(), 2);
+        assert_eq!(engine.apply("dot comma one"), "dot comma one");
+        assert!(engine.set_enabled(&filter, false).unwrap().is_empty());
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            original.replace(r#"] }"#, r#"], "enabled": false }"#)
+        );
+    }
+
+    #[test]
+    fn test_group_first_match_wins() {
+        let mut first = test_rule("ac", "^ac$", "add and commit");
+        first.priority = 20;
+        first.group = Some("commands".to_string());
+        let mut second = test_rule("ac-lower", "^a", "x");
+        second.priority = 10;
+        second.group = Some("commands".to_string());
+        let mut cleanup = test_rule("cleanup", "commit", "commit!");
+        cleanup.priority = 0;
+
+        let file = create_test_rules_file(&[first, second, cleanup]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
+
+        // The winner applies, the other member is skipped, cleanup still runs
+        assert_eq!(engine.apply("ac"), "add and commit!");
+        let record = &engine.get_transformation_log()[0];
+        let matched: Vec<&str> = record.matches.iter().map(|m| m.rule_id.as_str()).collect();
+        assert_eq!(matched, ["ac", "cleanup"]);
+        assert_eq!(record.matches[0].output, "add and commit");
+        assert_eq!(record.skipped[0].rule_id, "ac-lower");
+        assert_eq!(
+            record.skipped[0].reason,
+            "group 'commands' already matched by 'ac'"
+        );
+
+        // Without a winner, the next member gets its turn
+        assert_eq!(engine.apply("abc"), "xbc");
+    }
+
+    #[test]
+    fn test_trace_selected_rules() {
+        let mut first = test_rule("ac", "^ac$", "add and commit");
+        first.priority = 20;
+        first.group = Some("commands".to_string());
+        let mut second = test_rule("ac-lower", "^a", "x");
+        second.priority = 10;
+        second.group = Some("commands".to_string());
+        let mut cleanup = test_rule("cleanup", "m", "M");
+        cleanup.priority = 0;
+        cleanup.tags = vec!["cleanup".to_string()];
+
+        let file = create_test_rules_file(&[first, second, cleanup]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
+
+        let trace = engine.trace("ac", &[]);
+        assert_eq!(trace.output, "add and coMMit");
+        let ids: Vec<&str> = trace.steps.iter().map(|s| s.rule_id.as_str()).collect();
+        assert_eq!(ids, ["ac", "ac-lower", "cleanup"]);
+        assert_eq!(trace.steps[0].before, "ac");
+        assert_eq!(trace.steps[0].after, "add and commit");
+        assert_eq!(trace.steps[0].spans, vec![Range { start: 0, end: 2 }]);
+        assert!(trace.steps[1].skipped.is_some());
+        assert_eq!(trace.steps[2].spans, [10..11, 11..12]);
+
+        // Tracing does not log the request
+        assert!(engine.get_transformation_log().is_empty());
+
+        // Unchanged rules are evaluated too; selectors leave rules out
+        let selector = RuleSelector {
+            rules_exclude: vec!["cleanup".to_string()],
+            ..Default::default()
+        };
+        let trace = engine.trace("b", &[&selector]);
+        assert_eq!(trace.output, "b");
+        assert_eq!(trace.steps.len(), 2);
+        assert!(
+            trace
+                .steps
+                .iter()
+                .all(|s| s.skipped.is_none() && s.spans.is_empty())
+        );
+
+        // Selected transforms are not logged either
+        assert_eq!(
+            engine.transform_selected("ac", &[&selector]),
+            "add and commit"
+        );
+        assert!(engine.get_transformation_log().is_empty());
+    }
+
+    #[test]
+    fn test_transform_batch_keeps_order() {
+        let file = create_test_rules_file(&[test_rule("slash", r"\bslash\b", "/")]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
+
+        let texts: Vec<String> = (0..100).map(|i| format!("{} slash {}", i, i)).collect();
+        let outputs = engine.transform_batch(&texts, &[]).unwrap();
+        assert_eq!(outputs.len(), 100);
+        for (i, output) in outputs.iter().enumerate() {
+            assert_eq!(output, &format!("{} / {}", i, i));
+        }
+        assert!(engine.transform_batch(&[], &[]).unwrap().is_empty());
+
+        // Batches are not logged
+        assert!(engine.get_transformation_log().is_empty());
+    }
 
+    #[test]
+    fn test_requires_matched() {
+        let mut command = test_rule("command", "^ac", "add and commit");
+        command.priority = 20;
+        let mut strip = test_rule("strip-period", "\\.$", "");
+        strip.priority = 10;
+        strip.requires_matched = vec!["command".to_string()];
+        let mut fallback = test_rule("fallback", "^", "> ");
+        fallback.priority = 0;
+        fallback.requires_not_matched = vec!["command".to_string()];
+
+        let file = create_test_rules_file(&[command, strip, fallback]);
+        let engine = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(engine.apply("ac."), "add and commit");
+        assert_eq!(engine.apply("hello."), "> hello.");
         let logs = engine.get_transformation_log();
-        assert_eq!(logs.len(), 1);
-        assert!(logs[0].matched);
-        assert_eq!(logs[0].rule_id, "test");
+        let strip = logs[1].skipped.iter().find(|s| s.rule_id == "strip-period");
+        assert_eq!(strip.unwrap().reason, "requires 'command' to have matched");
+    }
+
+    #[test]
+    fn test_requirements_are_checked_at_load() {
+        let mut early = test_rule("early", "a", "b");
+        early.priority = 200;
+        early.requires_matched = vec!["late".to_string()];
+        let late = test_rule("late", "b", "c");
+        let file = create_test_rules_file(&[early, late]);
+        let paths = [file.path().to_str().unwrap().to_string()];
+
+        let err = RuleEngine::with_options(&paths, EngineOptions::default())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("applied after it"), "{}", err);
+
+        let mut unknown = test_rule("unknown", "a", "b");
+        unknown.requires_not_matched = vec!["missing".to_string()];
+        let file = create_test_rules_file(&[unknown]);
+        let err = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            EngineOptions::default(),
+        )
+        .err()
+        .unwrap();
+        assert!(
+            err.to_string().contains("unknown rule 'missing'"),
+            "{}",
+            err
+        );
     }
 }
@@ -0,0 +1,363 @@
+//! Rules file formats
+//!
+//! The format is chosen by file extension. JSONC and JSON5 allow comments and
+//! trailing commas so rules can be annotated right next to their patterns.
+//! YAML and TOML additionally avoid the double escaping of regex backslashes.
+//!
+//! A file holds either a bare list of rules or an envelope with pack metadata:
+//! `{ "pack": { "name": ..., "version": ... }, "rules": [...] }`.
+
+use super::document::{RulesDocument, RulesEditor};
+use super::toml_document::TomlDocument;
+use super::types::{PackInfo, Rule};
+use super::yaml_document::YamlDocument;
+use serde::de::{self, DeserializeOwned, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::Path;
+
+/// Supported rules file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesFormat {
+    /// Plain JSON (`.json`)
+    Json,
+    /// JSON with comments and trailing commas (`.jsonc`)
+    Jsonc,
+    /// JSON5 (`.json5`)
+    Json5,
+    /// YAML (`.yaml`, `.yml`), a top-level list of rules
+    Yaml,
+    /// TOML (`.toml`), rules as a `[[rules]]` array of tables
+    Toml,
+}
+
+/// Contents of a rules file
+#[derive(Debug, Default)]
+pub struct RulesFile {
+    /// Pack metadata (only in the envelope form)
+    pub pack: Option<PackInfo>,
+    pub rules: Vec<Rule>,
+}
+
+/// Envelope form of a rules file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Envelope {
+    #[serde(default)]
+    pack: Option<PackInfo>,
+    #[serde(default)]
+    rules: Vec<PackRule>,
+}
+
+/// A rule inside an envelope; `enabled` is kept apart to tell whether the
+/// rule sets it or inherits the pack default
+#[derive(Deserialize)]
+struct PackRule {
+    #[serde(flatten)]
+    rule: Rule,
+    enabled: Option<bool>,
+}
+
+impl<'de> Deserialize<'de> for RulesFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RulesFileVisitor;
+
+        impl<'de> Visitor<'de> for RulesFileVisitor {
+            type Value = RulesFile;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a list of rules or an object with `pack` and `rules`")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<RulesFile, A::Error> {
+                let rules = Vec::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+                Ok(RulesFile { pack: None, rules })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<RulesFile, A::Error> {
+                let envelope = Envelope::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                let default_enabled = envelope.pack.as_ref().map_or(true, |p| p.enabled);
+                let rules = envelope
+                    .rules
+                    .into_iter()
+                    .map(|PackRule { mut rule, enabled }| {
+                        rule.enabled = enabled.unwrap_or(default_enabled);
+                        rule
+                    })
+                    .collect();
+                Ok(RulesFile {
+                    pack: envelope.pack,
+                    rules,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(RulesFileVisitor)
+    }
+}
+
+impl Serialize for RulesFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.pack {
+            None => self.rules.serialize(serializer),
+            Some(pack) => {
+                let mut envelope = serializer.serialize_struct("RulesFile", 2)?;
+                envelope.serialize_field("pack", pack)?;
+                envelope.serialize_field("rules", &self.rules)?;
+                envelope.end()
+            },
+        }
+    }
+}
+
+/// TOML files can't have a top-level array, so rules always live under a `rules` key
+#[derive(Serialize)]
+struct TomlRules<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pack: Option<&'a PackInfo>,
+    rules: &'a [Rule],
+}
+
+impl RulesFormat {
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_name(path.extension()?.to_str()?)
+    }
+
+    /// Look up a format by name or file extension (e.g. `yaml`, `yml`, `toml`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "jsonc" => Some(Self::Jsonc),
+            "json5" => Some(Self::Json5),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// Parse a rules file, reporting errors with line and column
+    pub fn parse(self, content: &str) -> Result<RulesFile, String> {
+        self.deserialize(content)
+    }
+
+    /// Deserialize any file of this format (e.g. an overrides file)
+    pub fn deserialize<T: DeserializeOwned>(self, content: &str) -> Result<T, String> {
+        match self {
+            Self::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            Self::Jsonc => serde_json::from_str(&strip_jsonc(content)).map_err(|e| e.to_string()),
+            Self::Json5 => json5::from_str(content).map_err(|e| match e {
+                json5::Error::Message {
+                    msg,
+                    location: Some(loc),
+                } => format!("{} at line {} column {}", msg, loc.line, loc.column),
+                json5::Error::Message { msg, .. } => msg,
+            }),
+            Self::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str(content).map_err(|e| e.to_string().trim_end().to_string()),
+        }
+    }
+
+    /// Parse only the rules of a rules file
+    #[cfg(test)]
+    pub fn parse_rules(self, content: &str) -> Result<Vec<Rule>, String> {
+        self.parse(content).map(|file| file.rules)
+    }
+
+    /// Serialize a rules file into a new file of this format.
+    /// JSON is written for all JSON flavours, since it is valid JSONC and JSON5.
+    pub fn serialize(self, file: &RulesFile) -> Result<String, String> {
+        let output = match self {
+            Self::Json | Self::Jsonc | Self::Json5 => {
+                serde_json::to_string_pretty(file).map_err(|e| e.to_string())? + "\n"
+            },
+            Self::Yaml => serde_yaml::to_string(file).map_err(|e| e.to_string())?,
+            Self::Toml => toml::to_string(&TomlRules {
+                pack: file.pack.as_ref(),
+                rules: &file.rules,
+            })
+            .map_err(|e| e.to_string())?,
+        };
+        Ok(output)
+    }
+
+    /// Parse the text of a rules file for targeted edits
+    pub fn open_document(self, content: &str) -> Result<Box<dyn RulesEditor + '_>, String> {
+        Ok(match self {
+            Self::Json | Self::Jsonc | Self::Json5 => Box::new(RulesDocument::parse(content)?),
+            Self::Yaml => Box::new(YamlDocument::parse(content)?),
+            Self::Toml => Box::new(TomlDocument::parse(content)?),
+        })
+    }
+}
+
+/// Turn JSONC into plain JSON by blanking out comments and trailing commas.
+/// Removed characters are replaced with spaces (newlines are kept), so line and
+/// column numbers in parse errors still point into the original file.
+pub fn strip_jsonc(text: &str) -> String {
+    let mut out: Vec<u8> = text.as_bytes().to_vec();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    let mut in_string = false;
+    // Position of the last comma outside strings that may turn out to be trailing
+    let mut pending_comma: Option<usize> = None;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        if in_string {
+            match b {
+                b'\\' => i += 1,
+                b'"' => in_string = false,
+                _ => {},
+            }
+            i += 1;
+            continue;
+        }
+
+        match (b, bytes.get(i + 1)) {
+            (b'/', Some(b'/')) => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    out[i] = b' ';
+                    i += 1;
+                }
+                continue;
+            },
+            (b'/', Some(b'*')) => {
+                let end = text[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |p| i + 2 + p + 2);
+                for byte in &mut out[i..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                i = end;
+                continue;
+            },
+            (b'"', _) => {
+                in_string = true;
+                pending_comma = None;
+            },
+            (b',', _) => pending_comma = Some(i),
+            (b']' | b'}', _) => {
+                if let Some(comma) = pending_comma.take() {
+                    out[comma] = b' ';
+                }
+            },
+            (b' ' | b'\t' | b'\n' | b'\r', _) => {},
+            _ => pending_comma = None,
+        }
+        i += 1;
+    }
+
+    // Only ASCII bytes were replaced, so the result is still valid UTF-8
+    String::from_utf8(out).unwrap_or_else(|_| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_jsonc_keeps_positions() {
+        let input = "[\n  // comment\n  {\"id\": \"a//b\", /* inline */ \"pattern\": \"x\",},\n]";
+        let stripped = strip_jsonc(input);
+        assert_eq!(stripped.len(), input.len());
+        assert_eq!(stripped.lines().count(), input.lines().count());
+
+        let rules = RulesFormat::Jsonc.parse_rules(input).unwrap();
+        assert_eq!(rules[0].id, "a//b");
+    }
+
+    #[test]
+    fn test_parse_json5() {
+        let input = "[\n  // comment\n  {id: 'a', pattern: 'x', priority: 0x10,},\n]";
+        let rules = RulesFormat::Json5.parse_rules(input).unwrap();
+        assert_eq!(rules[0].id, "a");
+        assert_eq!(rules[0].priority, 16);
+
+        let err = RulesFormat::Json5.parse_rules("[\n  {id: }]").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            RulesFormat::from_path(Path::new("a/b.jsonc")),
+            Some(RulesFormat::Jsonc)
+        );
+        assert_eq!(
+            RulesFormat::from_path(Path::new("rules.YML")),
+            Some(RulesFormat::Yaml)
+        );
+        assert_eq!(RulesFormat::from_path(Path::new("README.md")), None);
+    }
+
+    #[test]
+    fn test_parse_yaml_and_toml() {
+        let yaml = "- id: slash\n  pattern: \\bslash\\b\n  replacement: /\n";
+        let rules = RulesFormat::Yaml.parse_rules(yaml).unwrap();
+        assert_eq!(rules[0].pattern, "\\bslash\\b");
+
+        let toml = "[[rules]]\nid = \"slash\"\npattern = '\\bslash\\b'\nreplacement = \"/\"\n";
+        let rules = RulesFormat::Toml.parse_rules(toml).unwrap();
+        assert_eq!(rules[0].pattern, "\\bslash\\b");
+
+        let err = RulesFormat::Yaml
+            .parse_rules("- id: a\n  pattern: [\n")
+            .unwrap_err();
+        assert!(err.contains("line"), "{}", err);
+        let err = RulesFormat::Toml
+            .parse_rules("[[rules]]\nid = \"a\"\npattern = \n")
+            .unwrap_err();
+        assert!(err.contains("line 3"), "{}", err);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let rules = RulesFormat::Json
+            .parse_rules(r##"[{"id": "a", "pattern": "\\d+", "replacement": "#", "priority": 5}]"##)
+            .unwrap();
+        let file = RulesFile {
+            pack: Some(PackInfo {
+                name: "numbers".to_string(),
+                version: Some("1.0".to_string()),
+                ..Default::default()
+            }),
+            rules,
+        };
+        for format in [RulesFormat::Json, RulesFormat::Yaml, RulesFormat::Toml] {
+            let text = format.serialize(&file).unwrap();
+            let parsed = format.parse(&text).unwrap();
+            assert_eq!(parsed.rules[0].pattern, "\\d+", "{:?}", format);
+            assert_eq!(parsed.rules[0].priority, 5);
+            assert_eq!(parsed.pack.unwrap().version.as_deref(), Some("1.0"));
+        }
+    }
+
+    #[test]
+    fn test_parse_envelope() {
+        let input = r#"{
+            "pack": {"name": "de", "version": "1.2.0", "language": "de", "enabled": false},
+            "rules": [
+                {"id": "a", "pattern": "x"},
+                {"id": "b", "pattern": "y", "enabled": true}
+            ]
+        }"#;
+        let file = RulesFormat::Json.parse(input).unwrap();
+        let pack = file.pack.unwrap();
+        assert_eq!(pack.name, "de");
+        assert_eq!(pack.language.as_deref(), Some("de"));
+        assert!(!file.rules[0].enabled, "inherits the pack default");
+        assert!(file.rules[1].enabled);
+
+        let toml = "[pack]\nname = \"de\"\n\n[[rules]]\nid = \"a\"\npattern = \"x\"\n";
+        let file = RulesFormat::Toml.parse(toml).unwrap();
+        assert_eq!(file.pack.unwrap().name, "de");
+        assert!(file.rules[0].enabled);
+
+        let err = RulesFormat::Json.parse(r#"{"rulez": []}"#).unwrap_err();
+        assert!(err.contains("rulez"), "{}", err);
+    }
+}
@@ -1,73 +1,126 @@
 //! Rules file loading and hot-reload
 
+use super::document::RulesEditor;
 use super::engine::RuleEngine;
+use super::format::{RulesFile, RulesFormat};
 use super::types::Rule;
 use crate::error::AppError;
+use fs4::fs_std::FileExt;
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::fs;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-/// Load rules from a JSON file
-pub fn load_rules(path: &PathBuf) -> Result<Vec<Rule>, AppError> {
+/// Read and parse a rules file
+/// The format is picked by extension (`.json`, `.jsonc`, `.json5`, `.yaml`/`.yml`, `.toml`);
+/// other files are read as JSON
+pub fn load_rules_file(path: &Path) -> Result<RulesFile, AppError> {
     let content = fs::read_to_string(path).map_err(|e| {
         AppError::RulesLoadError(format!("Failed to read {}: {}", path.display(), e))
     })?;
 
-    let mut rules: Vec<Rule> = serde_json::from_str(&content).map_err(|e| {
-        AppError::RulesLoadError(format!("Failed to parse {}: {}", path.display(), e))
-    })?;
+    let format = RulesFormat::from_path(path).unwrap_or(RulesFormat::Json);
+    format
+        .parse(&content)
+        .map_err(|e| AppError::RulesLoadError(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+/// Load rules from a rules file, recording their source file and pack
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>, AppError> {
+    let RulesFile { pack, mut rules } = load_rules_file(path)?;
+
+    // Packs without a name are named after their file
+    let pack = pack.map(|mut pack| {
+        if pack.name.is_empty() {
+            pack.name = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+        }
+        Arc::new(pack)
+    });
 
-    // Set source file path for each rule
+    // Set source file path and pack for each rule
     let source_path = path.to_string_lossy().to_string();
     for rule in &mut rules {
         rule.source_file = Some(source_path.clone());
+        rule.pack = pack.clone();
     }
 
     Ok(rules)
 }
 
-/// Save rules to their source file
-/// Only saves rules that belong to the specified file
-pub fn save_rules_to_file(path: &str, rules: &[Rule]) -> Result<(), AppError> {
-    // Filter rules that belong to this file
-    let rules_for_file: Vec<&Rule> = rules
-        .iter()
-        .filter(|r| r.source_file.as_deref() == Some(path))
-        .collect();
-
-    if rules_for_file.is_empty() {
-        return Err(AppError::RulesLoadError(format!(
-            "No rules found for file: {}",
-            path
-        )));
+/// Set the enabled state of several rules in one write of a rules file
+pub fn save_rules_enabled(path: &str, rule_ids: &[String], enabled: bool) -> Result<(), AppError> {
+    let value = serde_json::Value::Bool(enabled);
+    edit_rules_file_each(path, rule_ids, |doc, rule_id| {
+        doc.set_rule_field(rule_id, "enabled", &value)
+    })
+}
+
+/// Set a field of rule overrides, creating the overrides file if needed
+pub fn save_overrides(
+    path: &str,
+    rule_ids: &[String],
+    key: &str,
+    value: serde_json::Value,
+) -> Result<(), AppError> {
+    if !Path::new(path).exists() {
+        let empty = match RulesFormat::from_path(Path::new(path)) {
+            Some(RulesFormat::Toml | RulesFormat::Yaml) => "",
+            _ => "{}\n",
+        };
+        fs::write(path, empty)
+            .map_err(|e| AppError::RulesLoadError(format!("Failed to create {}: {}", path, e)))?;
     }
 
-    // Read the original file to preserve formatting as much as possible
-    let content = fs::read_to_string(path)
+    edit_rules_file_each(path, rule_ids, |doc, rule_id| {
+        doc.set_override_field(rule_id, key, &value)
+    })
+}
+
+/// Append a new rule to a rules file
+pub fn insert_rule_into_file(path: &str, rule: &Rule) -> Result<(), AppError> {
+    edit_rules_file(path, |doc| doc.insert_rule(rule))
+}
+
+/// Replace the rule with the given id in a rules file
+pub fn replace_rule_in_file(path: &str, rule_id: &str, rule: &Rule) -> Result<(), AppError> {
+    edit_rules_file(path, |doc| doc.replace_rule(rule_id, rule))
+}
+
+/// Remove the rule with the given id from a rules file
+pub fn remove_rule_from_file(path: &str, rule_id: &str) -> Result<(), AppError> {
+    edit_rules_file(path, |doc| doc.remove_rule(rule_id))
+}
+
+/// Apply a targeted edit to a rules file.
+/// The file is locked for the whole read-modify-write cycle and replaced atomically,
+/// so a crash never leaves a half-written rules file behind.
+fn edit_rules_file<F>(path: &str, edit: F) -> Result<(), AppError>
+where
+    F: FnOnce(&dyn RulesEditor) -> Result<String, String>,
+{
+    // Resolve symlinks so the rename replaces the real file, not the link
+    let real_path = fs::canonicalize(path)
         .map_err(|e| AppError::RulesLoadError(format!("Failed to read {}: {}", path, e)))?;
 
-    // Parse the original JSON to get the structure
-    let mut original: Vec<serde_json::Value> = serde_json::from_str(&content)
-        .map_err(|e| AppError::RulesLoadError(format!("Failed to parse {}: {}", path, e)))?;
+    let _lock = lock_rules_file(&real_path)?;
 
-    // Update the enabled field for each rule
-    for rule in rules_for_file {
-        if let Some(json_rule) = original
-            .iter_mut()
-            .find(|r| r.get("id").and_then(|v| v.as_str()) == Some(&rule.id))
-        {
-            if let Some(obj) = json_rule.as_object_mut() {
-                obj.insert("enabled".to_string(), serde_json::Value::Bool(rule.enabled));
-            }
-        }
-    }
+    let content = fs::read_to_string(&real_path)
+        .map_err(|e| AppError::RulesLoadError(format!("Failed to read {}: {}", path, e)))?;
 
-    // Write back with pretty formatting
-    let output = serde_json::to_string_pretty(&original)
-        .map_err(|e| AppError::RulesLoadError(format!("Failed to serialize rules: {}", e)))?;
+    let format = RulesFormat::from_path(&real_path).unwrap_or(RulesFormat::Json);
+    let output = format
+        .open_document(&content)
+        .and_then(|doc| edit(doc.as_ref()))
+        .map_err(|e| AppError::RulesLoadError(format!("Failed to edit {}: {}", path, e)))?;
 
-    fs::write(path, output + "\n")
+    write_atomic(&real_path, &output)
         .map_err(|e| AppError::RulesLoadError(format!("Failed to write {}: {}", path, e)))?;
 
     tracing::info!("Saved rules to {}", path);
@@ -75,40 +128,124 @@ pub fn save_rules_to_file(path: &str, rules: &[Rule]) -> Result<(), AppError> {
     Ok(())
 }
 
+/// Apply the same kind of edit for several rules within one locked write
+fn edit_rules_file_each<F>(path: &str, rule_ids: &[String], edit: F) -> Result<(), AppError>
+where
+    F: Fn(&dyn RulesEditor, &str) -> Result<String, String>,
+{
+    let format = RulesFormat::from_path(Path::new(path)).unwrap_or(RulesFormat::Json);
+    edit_rules_file(path, |doc| {
+        let Some((first, rest)) = rule_ids.split_first() else {
+            return Err("no rules to edit".to_string());
+        };
+        let mut content = edit(doc, first)?;
+        for rule_id in rest {
+            let next = edit(format.open_document(&content)?.as_ref(), rule_id)?;
+            content = next;
+        }
+        Ok(content)
+    })
+}
+
+/// Hidden sibling path used for lock and temp files (e.g. `.rules.json.lock`)
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// Take an advisory lock on a rules file (released when the returned file is dropped)
+/// A sidecar lock file is used because the rules file itself is replaced on write.
+fn lock_rules_file(path: &Path) -> Result<File, AppError> {
+    let lock_path = sibling_path(path, "lock");
+    let lock_file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| {
+            AppError::RulesLoadError(format!("Failed to open {}: {}", lock_path.display(), e))
+        })?;
+
+    lock_file.lock_exclusive().map_err(|e| {
+        AppError::RulesLoadError(format!("Failed to lock {}: {}", path.display(), e))
+    })?;
+
+    Ok(lock_file)
+}
+
+/// Write a file atomically: write a temp file next to it, sync, then rename over it
+fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let tmp_path = sibling_path(path, &format!("{}.tmp", std::process::id()));
+
+    let result = (|| {
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(content.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(path) {
+            tmp.set_permissions(metadata.permissions())?;
+        }
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+/// Whether a path is one of our own lock or temp files
+pub fn is_internal_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.') && (n.ends_with(".lock") || n.ends_with(".tmp")))
+}
+
 /// Load rules from multiple sources (files, directories, or glob patterns)
 /// Rules are pre-sorted by priority (descending) for optimal apply() performance
 pub fn load_rules_from_paths(paths: &[String]) -> Result<Vec<Rule>, AppError> {
     let mut all_rules = Vec::new();
 
+    // Paths may overlap (e.g. `-r rules/**/*` on top of the configured paths),
+    // so every file is loaded only once
+    let mut loaded = HashSet::new();
+    let mut load_once = |file: &Path| -> Result<Vec<Rule>, AppError> {
+        let real_path = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        if !loaded.insert(real_path) {
+            tracing::debug!("Skipping {:?}, already loaded", file);
+            return Ok(Vec::new());
+        }
+        tracing::debug!("Loading rules from {:?}", file);
+        load_rules(file)
+    };
+
     for path_str in paths {
         let path = Path::new(path_str);
 
         if path.is_dir() {
-            // Load all .json files from directory
+            // Load all rules files (JSON, JSONC, JSON5, YAML, TOML) from directory
             let entries = fs::read_dir(path).map_err(|e| {
                 AppError::RulesLoadError(format!("Failed to read directory {}: {}", path_str, e))
             })?;
 
             for entry in entries.flatten() {
                 let file_path = entry.path();
-                if file_path.extension().map(|e| e == "json").unwrap_or(false) {
-                    tracing::debug!("Loading rules from {:?}", file_path);
-                    let rules = load_rules(&file_path)?;
-                    all_rules.extend(rules);
+                if is_rules_file(&file_path) {
+                    all_rules.extend(load_once(&file_path)?);
                 }
             }
         } else if path.exists() {
             // Load single file
-            tracing::debug!("Loading rules from {:?}", path);
-            let rules = load_rules(&path.to_path_buf())?;
-            all_rules.extend(rules);
+            all_rules.extend(load_once(path)?);
         } else {
             // Try as glob pattern
             if let Ok(entries) = glob::glob(path_str) {
                 for entry in entries.flatten() {
-                    tracing::debug!("Loading rules from {:?}", entry);
-                    let rules = load_rules(&entry)?;
-                    all_rules.extend(rules);
+                    // Skip directories, non-rules files (e.g. READMEs) and our lock/temp files
+                    if !entry.is_file() || !is_rules_file(&entry) {
+                        continue;
+                    }
+                    all_rules.extend(load_once(&entry)?);
                 }
             } else {
                 return Err(AppError::RulesLoadError(format!(
@@ -120,21 +257,82 @@ pub fn load_rules_from_paths(paths: &[String]) -> Result<Vec<Rule>, AppError> {
     }
 
     // Pre-sort by priority (descending) for O(N) apply() instead of O(N log N) per request
-    all_rules.sort_by(|a, b| b.priority.cmp(&a.priority));
+    all_rules.sort_by_key(|r| std::cmp::Reverse(r.priority));
 
     Ok(all_rules)
 }
 
-/// Watch the rules file for changes and reload when modified
+/// Describe every rule id used by more than one rule, with the files using it
+pub fn duplicate_ids(rules: &[Rule]) -> Vec<String> {
+    let mut seen: Vec<(&str, Vec<&str>)> = Vec::new();
+    for rule in rules {
+        let file = rule.source_file.as_deref().unwrap_or("<unknown>");
+        match seen.iter_mut().find(|(id, _)| *id == rule.id) {
+            Some((_, files)) => files.push(file),
+            None => seen.push((&rule.id, vec![file])),
+        }
+    }
+
+    seen.into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(id, files)| format!("Duplicate rule id '{}' in {}", id, files.join(" and ")))
+        .collect()
+}
+
+/// Check the `requires_matched`/`requires_not_matched` conditions of rules in
+/// application order: every referenced rule must exist and be applied earlier.
+/// Returns (rule id, message) for each problem.
+pub fn requirement_errors(rules: &[Rule]) -> Vec<(String, String)> {
+    let mut errors = Vec::new();
+
+    for (index, rule) in rules.iter().enumerate() {
+        for id in rule
+            .requires_matched
+            .iter()
+            .chain(&rule.requires_not_matched)
+        {
+            let positions: Vec<usize> = rules
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.has_id(id))
+                .map(|(position, _)| position)
+                .collect();
+
+            let message = match positions.first() {
+                None => format!("Rule '{}' requires unknown rule '{}'", rule.id, id),
+                Some(&first) if first >= index => format!(
+                    "Rule '{}' requires '{}', which is applied after it; \
+                     give '{}' a higher priority than {}",
+                    rule.id, id, id, rule.priority
+                ),
+                Some(_) => continue,
+            };
+            errors.push((rule.id.clone(), message));
+        }
+    }
+
+    errors
+}
+
+/// Whether a path looks like a rules file that directory and glob loading pick up
+pub fn is_rules_file(path: &Path) -> bool {
+    RulesFormat::from_path(path).is_some() && !is_internal_file(path)
+}
+
+/// Watch a directory for rules file changes and reload when modified.
+/// Directories are watched instead of files because atomic writes replace the file
+/// (and with it a file-level watch). `files` limits which file names trigger a
+/// reload; `None` accepts every rules file in the directory.
 /// Returns the watcher so it can be stored (dropping it stops watching)
-pub fn watch_rules_file(
-    path: PathBuf,
+pub fn watch_rules_dir(
+    dir: PathBuf,
+    files: Option<HashSet<OsString>>,
     engine: Arc<RuleEngine>,
 ) -> Result<RecommendedWatcher, AppError> {
     use std::sync::Mutex;
     use std::time::{Duration, Instant};
 
-    let path_clone = path.clone();
+    let dir_clone = dir.clone();
 
     // Debounce: track last reload time using monotonic clock (immune to NTP jumps)
     let last_reload = Arc::new(Mutex::new(Instant::now()));
@@ -149,6 +347,19 @@ pub fn watch_rules_file(
             match res {
                 Ok(event) => {
                     if matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_)) {
+                        // Ignore unrelated files, our temp/lock files and our own writes
+                        let relevant = event.paths.iter().any(|p| {
+                            let wanted = match &files {
+                                Some(names) => p.file_name().is_some_and(|n| names.contains(n)),
+                                None => is_rules_file(p),
+                            };
+                            wanted && !is_internal_file(p) && !engine.is_own_write(p)
+                        });
+                        if !relevant {
+                            tracing::trace!("Ignoring file event for {:?}", event.paths);
+                            return;
+                        }
+
                         // Debounce: check if enough time has passed since last reload
                         let mut last = last_reload_clone.lock().unwrap();
                         if last.elapsed() < DEBOUNCE {
@@ -170,10 +381,10 @@ pub fn watch_rules_file(
             }
         })?;
 
-    // Watch the rules file
-    watcher.watch(&path_clone, RecursiveMode::NonRecursive)?;
+    // Watch the rules directory
+    watcher.watch(&dir_clone, RecursiveMode::NonRecursive)?;
 
-    tracing::info!("Watching {:?} for changes", path_clone);
+    tracing::info!("Watching {:?} for changes", dir_clone);
 
     Ok(watcher)
 }
@@ -1,7 +1,17 @@
 //! Rule engine module
 
+mod document;
 mod engine;
+mod format;
 mod loader;
+mod overrides;
+mod toml_document;
 mod types;
+mod yaml_document;
 
-pub use engine::RuleEngine;
+pub use engine::{
+    EngineOptions, RuleEngine, RuleMatch, RuleStep, SkippedRule, TransformationRecord,
+};
+pub use format::RulesFormat;
+pub use loader::{is_rules_file, load_rules_file};
+pub use types::{PackInfo, Rule, RuleFilter, RuleSelector, RuleType};
@@ -0,0 +1,212 @@
+//! Local overrides of shipped rules
+//!
+//! An overrides file patches loaded rules by id and may add rules of its own.
+//! It lives outside the rule packs, so local changes survive `setup --force`
+//! replacing the shipped files:
+//!
+//! ```json
+//! {
+//!   "overrides": {
+//!     "de-punkt": { "enabled": false },
+//!     "cleanup-double-space": { "priority": 10, "replacement": " " }
+//!   },
+//!   "rules": [{ "id": "my-rule", "pattern": "foo", "replacement": "bar" }]
+//! }
+//! ```
+
+use super::format::RulesFormat;
+use super::types::{Rule, RuleType};
+use crate::error::AppError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Contents of an overrides file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OverridesFile {
+    /// Patches by rule id
+    #[serde(default)]
+    pub overrides: BTreeMap<String, RulePatch>,
+
+    /// Additional local rules
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// Fields of a rule to replace; unset fields keep the loaded value
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RulePatch {
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    #[serde(rename = "type")]
+    pub rule_type: Option<RuleType>,
+    pub pattern: Option<String>,
+    pub replacement: Option<String>,
+    pub priority: Option<i32>,
+    pub enabled: Option<bool>,
+    pub ignore_case: Option<bool>,
+    pub timeout_ms: Option<u64>,
+    pub stop_on_match: Option<bool>,
+    pub group: Option<String>,
+    pub requires_matched: Option<Vec<String>>,
+    pub requires_not_matched: Option<Vec<String>>,
+}
+
+impl RulePatch {
+    /// Apply the patch to a rule, returning the names of the patched fields
+    pub fn apply(&self, rule: &mut Rule) -> Vec<&'static str> {
+        let mut patched = Vec::new();
+
+        macro_rules! patch {
+            ($field:ident, $name:literal) => {
+                if let Some(value) = &self.$field {
+                    rule.$field = value.clone();
+                    patched.push($name);
+                }
+            };
+        }
+
+        if let Some(description) = &self.description {
+            rule.description = Some(description.clone());
+            patched.push("description");
+        }
+        if let Some(rule_type) = &self.rule_type {
+            rule.rule_type = rule_type.clone();
+            patched.push("type");
+        }
+        patch!(tags, "tags");
+        patch!(pattern, "pattern");
+        patch!(replacement, "replacement");
+        patch!(priority, "priority");
+        patch!(enabled, "enabled");
+        patch!(ignore_case, "ignore_case");
+        patch!(timeout_ms, "timeout_ms");
+        patch!(stop_on_match, "stop_on_match");
+        if let Some(group) = &self.group {
+            rule.group = Some(group.clone());
+            patched.push("group");
+        }
+        patch!(requires_matched, "requires_matched");
+        patch!(requires_not_matched, "requires_not_matched");
+
+        patched
+    }
+}
+
+/// Read an overrides file; a missing file means no overrides
+pub fn load_overrides(path: &Path) -> Result<OverridesFile, AppError> {
+    if !path.exists() {
+        tracing::debug!("Overrides file {} does not exist yet", path.display());
+        return Ok(OverridesFile::default());
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| {
+        AppError::RulesLoadError(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+
+    let format = RulesFormat::from_path(path).unwrap_or(RulesFormat::Json);
+    format
+        .deserialize(&content)
+        .map_err(|e| AppError::RulesLoadError(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+/// Apply an overrides file to the loaded rules and re-sort them by priority.
+/// Patched rules record which fields were overridden; added rules get the
+/// overrides file as their source file.
+pub fn apply_overrides(
+    rules: &mut Vec<Rule>,
+    overrides: OverridesFile,
+    path: &str,
+) -> Result<(), AppError> {
+    for (rule_id, patch) in &overrides.overrides {
+        // A bare id patches every rule using it, `pack:id` a single one
+        let mut found = false;
+        for rule in rules.iter_mut().filter(|r| r.has_id(rule_id)) {
+            found = true;
+            for field in patch.apply(rule) {
+                if !rule.overridden.iter().any(|f| f == field) {
+                    rule.overridden.push(field.to_string());
+                }
+            }
+        }
+        if !found {
+            tracing::warn!("Override for unknown rule '{}' in {}", rule_id, path);
+        }
+    }
+
+    for mut rule in overrides.rules {
+        if rules.iter().any(|r| r.id == rule.id) {
+            return Err(AppError::RulesLoadError(format!(
+                "Rule '{}' in {} already exists, patch it under \"overrides\" instead",
+                rule.id, path
+            )));
+        }
+        rule.source_file = Some(path.to_string());
+        rules.push(rule);
+    }
+
+    rules.sort_by_key(|r| std::cmp::Reverse(r.priority));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, priority: i32) -> Rule {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "pattern": "x", "priority": priority, "description": "shipped"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let overrides: OverridesFile = RulesFormat::Json
+            .deserialize(
+                r#"{
+                    "overrides": {
+                        "a": {"enabled": false, "priority": 1, "replacement": "y"},
+                        "missing": {"enabled": false}
+                    },
+                    "rules": [{"id": "local", "pattern": "z", "priority": 5}]
+                }"#,
+            )
+            .unwrap();
+
+        let mut rules = vec![rule("a", 10), rule("b", 3)];
+        apply_overrides(&mut rules, overrides, "overrides.json").unwrap();
+
+        let ids: Vec<&str> = rules.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["local", "b", "a"]);
+
+        let a = &rules[2];
+        assert!(!a.enabled);
+        assert_eq!(a.replacement, "y");
+        assert_eq!(a.description.as_deref(), Some("shipped"));
+        assert_eq!(a.overridden, ["replacement", "priority", "enabled"]);
+        assert!(rules[1].overridden.is_empty());
+        assert_eq!(rules[0].source_file.as_deref(), Some("overrides.json"));
+    }
+
+    #[test]
+    fn test_added_rule_must_not_shadow_loaded_rule() {
+        let overrides = OverridesFile {
+            rules: vec![rule("a", 0)],
+            ..Default::default()
+        };
+        let err = apply_overrides(&mut vec![rule("a", 0)], overrides, "o.json").unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+    }
+
+    #[test]
+    fn test_unknown_patch_field_is_rejected() {
+        let result: Result<OverridesFile, _> =
+            RulesFormat::Yaml.deserialize("overrides:\n  a:\n    enabeld: false\n");
+        assert!(result.unwrap_err().contains("enabeld"));
+    }
+}
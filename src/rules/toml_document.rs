@@ -0,0 +1,260 @@
+//! Format-preserving edits of TOML rules files
+//!
+//! Backed by `toml_edit`, which keeps comments, whitespace and key order of
+//! everything that isn't touched.
+
+use super::document::RulesEditor;
+use super::types::Rule;
+use serde_json::Value;
+use toml_edit::{ArrayOfTables, DocumentMut, InlineTable, Item, Table};
+
+/// A TOML rules file (`[[rules]]` tables) parsed for targeted edits
+#[derive(Debug)]
+pub struct TomlDocument {
+    doc: DocumentMut,
+}
+
+impl TomlDocument {
+    /// Parse the text of a rules file
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let doc = text
+            .parse::<DocumentMut>()
+            .map_err(|e| e.to_string().trim_end().to_string())?;
+
+        match doc.get("rules") {
+            None => {},
+            Some(item) if item.is_array_of_tables() => {},
+            Some(_) => return Err("rules must be written as [[rules]] tables".to_string()),
+        }
+
+        Ok(Self { doc })
+    }
+
+    /// The table of the rule with the given id
+    fn find_rule<'d>(doc: &'d mut DocumentMut, rule_id: &str) -> Result<&'d mut Table, String> {
+        doc.get_mut("rules")
+            .and_then(Item::as_array_of_tables_mut)
+            .and_then(|rules| {
+                rules
+                    .iter_mut()
+                    .find(|table| table.get("id").and_then(Item::as_str) == Some(rule_id))
+            })
+            .ok_or_else(|| format!("rule '{}' not found", rule_id))
+    }
+}
+
+/// Convert a rule into TOML key/value pairs, keeping its field order
+fn rule_items(rule: &Rule) -> Result<Vec<(String, Item)>, String> {
+    let text = toml::to_string(rule).map_err(|e| e.to_string())?;
+    let doc = text.parse::<DocumentMut>().map_err(|e| e.to_string())?;
+    Ok(doc
+        .iter()
+        .map(|(key, item)| (key.to_string(), item.clone()))
+        .collect())
+}
+
+/// Convert a JSON value into a TOML value
+fn toml_value(value: &Value) -> Result<toml_edit::Value, String> {
+    Ok(match value {
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().unwrap_or_default().into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => items
+            .iter()
+            .map(toml_value)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<toml_edit::Array>()
+            .into(),
+        Value::Object(map) => {
+            let mut table = InlineTable::new();
+            for (key, value) in map {
+                table.insert(key, toml_value(value)?);
+            }
+            table.into()
+        },
+        Value::Null => return Err("TOML has no null value".to_string()),
+    })
+}
+
+impl RulesEditor for TomlDocument {
+    fn set_rule_field(&self, rule_id: &str, key: &str, value: &Value) -> Result<String, String> {
+        let mut doc = self.doc.clone();
+        let table = Self::find_rule(&mut doc, rule_id)?;
+
+        // Keep spacing and a trailing comment of the old value
+        let mut new_value = toml_value(value)?;
+        if let Some(old) = table.get(key).and_then(Item::as_value) {
+            *new_value.decor_mut() = old.decor().clone();
+        }
+        table.insert(key, Item::Value(new_value));
+
+        Ok(doc.to_string())
+    }
+
+    fn replace_rule(&self, rule_id: &str, rule: &Rule) -> Result<String, String> {
+        let mut doc = self.doc.clone();
+        let table = Self::find_rule(&mut doc, rule_id)?;
+
+        // Clearing keeps the table header and the comments above it
+        table.clear();
+        for (key, item) in rule_items(rule)? {
+            table.insert(&key, item);
+        }
+
+        Ok(doc.to_string())
+    }
+
+    fn insert_rule(&self, rule: &Rule) -> Result<String, String> {
+        let mut doc = self.doc.clone();
+
+        let mut table = Table::new();
+        for (key, item) in rule_items(rule)? {
+            table.insert(&key, item);
+        }
+
+        doc.entry("rules")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+            .ok_or("rules must be written as [[rules]] tables")?
+            .push(table);
+
+        Ok(doc.to_string())
+    }
+
+    fn remove_rule(&self, rule_id: &str) -> Result<String, String> {
+        let mut doc = self.doc.clone();
+        let rules = doc
+            .get_mut("rules")
+            .and_then(Item::as_array_of_tables_mut)
+            .ok_or_else(|| format!("rule '{}' not found", rule_id))?;
+
+        let index = rules
+            .iter()
+            .position(|table| table.get("id").and_then(Item::as_str) == Some(rule_id))
+            .ok_or_else(|| format!("rule '{}' not found", rule_id))?;
+        rules.remove(index);
+
+        Ok(doc.to_string())
+    }
+
+    fn set_override_field(
+        &self,
+        rule_id: &str,
+        key: &str,
+        value: &Value,
+    ) -> Result<String, String> {
+        let mut doc = self.doc.clone();
+
+        // `[overrides.<id>]` tables, without an empty `[overrides]` header
+        let overrides = doc
+            .entry("overrides")
+            .or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_like_mut()
+            .ok_or("overrides must be a table")?;
+        let entry = overrides
+            .entry(rule_id)
+            .or_insert(Item::Table(Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| format!("override of rule '{}' must be a table", rule_id))?;
+
+        let mut new_value = toml_value(value)?;
+        if let Some(old) = entry.get(key).and_then(Item::as_value) {
+            *new_value.decor_mut() = old.decor().clone();
+        }
+        entry.insert(key, Item::Value(new_value));
+
+        Ok(doc.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"# Punctuation
+[[rules]]
+id = "slash"
+pattern = '\bslash\b'
+replacement = "/"
+enabled = true # toggled from the dashboard
+
+# Numbers
+[[rules]]
+id = "digits"
+pattern = '\d+'
+"#;
+
+    fn rule(id: &str) -> Rule {
+        serde_json::from_value(serde_json::json!({"id": id, "pattern": "x"})).unwrap()
+    }
+
+    #[test]
+    fn test_set_field_keeps_comments() {
+        let doc = TomlDocument::parse(RULES).unwrap();
+        let out = doc
+            .set_rule_field("slash", "enabled", &Value::Bool(false))
+            .unwrap();
+        assert_eq!(out, RULES.replace("enabled = true #", "enabled = false #"));
+
+        let out = doc
+            .set_rule_field("digits", "enabled", &Value::Bool(false))
+            .unwrap();
+        assert!(
+            out.ends_with("pattern = '\\d+'\nenabled = false\n"),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn test_insert_replace_remove() {
+        let doc = TomlDocument::parse(RULES).unwrap();
+
+        let out = doc.insert_rule(&rule("new")).unwrap();
+        assert!(out.starts_with(RULES));
+        assert!(out.contains("[[rules]]\nid = \"new\"\n"), "{}", out);
+
+        let out = doc.replace_rule("digits", &rule("digits")).unwrap();
+        assert!(
+            out.contains("# Numbers\n[[rules]]\nid = \"digits\"\n"),
+            "{}",
+            out
+        );
+        assert!(!out.contains("\\d+"));
+
+        let out = doc.remove_rule("digits").unwrap();
+        assert!(
+            !out.contains("digits") && !out.contains("# Numbers"),
+            "{}",
+            out
+        );
+        assert!(out.contains("toggled from the dashboard"));
+
+        let empty = TomlDocument::parse("").unwrap();
+        let out = empty.insert_rule(&rule("first")).unwrap();
+        assert!(out.contains("[[rules]]\nid = \"first\""), "{}", out);
+    }
+
+    #[test]
+    fn test_set_override_field() {
+        let doc = TomlDocument::parse("").unwrap();
+        let out = doc
+            .set_override_field("de-punkt", "enabled", &Value::Bool(false))
+            .unwrap();
+        assert_eq!(out, "[overrides.de-punkt]\nenabled = false\n");
+
+        let out = TomlDocument::parse(&out)
+            .unwrap()
+            .set_override_field("de-punkt", "priority", &Value::from(5))
+            .unwrap();
+        assert_eq!(out, "[overrides.de-punkt]\nenabled = false\npriority = 5\n");
+    }
+}
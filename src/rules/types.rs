@@ -1,10 +1,12 @@
 //! Rule data structures
 
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use std::path::Path;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 
 /// Type of transformation to apply
-#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum RuleType {
     /// Regex-based replacement (default)
@@ -16,6 +18,9 @@ pub enum RuleType {
 
     /// Built-in function (uppercase, lowercase, trim, etc.)
     Function,
+
+    /// Plain text replacement (pattern and replacement are taken literally)
+    Literal,
 }
 
 /// A single transformation rule
@@ -25,22 +30,29 @@ pub struct Rule {
     pub id: String,
 
     /// Human-readable description
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 
+    /// Tags for enabling or disabling related rules together
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!(["punctuation"]))]
+    pub tags: Vec<String>,
+
     /// Type of rule (regex, shell, function)
     #[serde(default, rename = "type")]
     pub rule_type: RuleType,
 
     /// For regex: pattern to match
+    /// For literal: exact text to match
     /// For shell: command to execute
     /// For function: function name
     pub pattern: String,
 
     /// For regex: replacement string (supports backreferences like $1, $2)
+    /// For literal: replacement text (no backreferences)
     /// For shell: not used (output is from stdout)
     /// For function: optional arguments
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub replacement: String,
 
     /// Priority (higher = applied first)
@@ -51,22 +63,174 @@ pub struct Rule {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
 
-    /// Case-insensitive matching (for regex rules)
-    #[serde(default)]
+    /// Case-insensitive matching (for regex and literal rules)
+    #[serde(default, skip_serializing_if = "is_false")]
     pub ignore_case: bool,
 
     /// Timeout in milliseconds for shell commands
-    #[serde(default = "default_timeout")]
+    #[serde(
+        default = "default_timeout",
+        skip_serializing_if = "is_default_timeout"
+    )]
     pub timeout_ms: u64,
 
     /// Stop processing further rules after this rule matches
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_false")]
     pub stop_on_match: bool,
 
+    /// Mutually exclusive group: only the highest-priority matching rule of a
+    /// group applies, processing then continues with rules outside the group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "dev-commands")]
+    pub group: Option<String>,
+
+    /// Only apply if all of these rules (applied earlier) changed the text
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!(["dev-ac"]))]
+    pub requires_matched: Vec<String>,
+
+    /// Only apply if none of these rules (applied earlier) changed the text
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires_not_matched: Vec<String>,
+
     /// Source file path (internal, not serialized to JSON output)
     #[serde(skip)]
     #[schema(hidden)]
     pub source_file: Option<String>,
+
+    /// Pack the rule was loaded from (internal, set from the file's `pack` section)
+    #[serde(skip)]
+    #[schema(hidden)]
+    pub pack: Option<Arc<PackInfo>>,
+
+    /// Fields patched by the overrides file (internal)
+    #[serde(skip)]
+    #[schema(hidden)]
+    pub overridden: Vec<String>,
+}
+
+/// Selects rules by tag, pack, source file, type and state; unset fields match every rule
+#[derive(Debug, Clone, Default, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RuleFilter {
+    /// Rules with this tag
+    #[serde(default)]
+    #[param(example = "punctuation")]
+    pub tag: Option<String>,
+
+    /// Rules of this pack
+    #[serde(default)]
+    #[param(example = "de-satzzeichen")]
+    pub pack: Option<String>,
+
+    /// Rules from this file (full path or trailing path components, e.g. `git.json`)
+    #[serde(default)]
+    #[param(example = "de/satzzeichen.json")]
+    pub file: Option<String>,
+
+    /// Rules of this type
+    #[serde(default, rename = "type")]
+    #[param(inline)]
+    pub rule_type: Option<RuleType>,
+
+    /// Only enabled or only disabled rules
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+impl RuleFilter {
+    /// Whether the filter selects by tag, pack or file rather than matching everything
+    pub fn is_selective(&self) -> bool {
+        self.tag.is_some() || self.pack.is_some() || self.file.is_some()
+    }
+
+    /// Whether a rule passes the filter
+    pub fn matches(&self, rule: &Rule) -> bool {
+        if let Some(tag) = &self.tag {
+            if !rule.tags.iter().any(|t| t == tag) {
+                return false;
+            }
+        }
+        if let Some(pack) = &self.pack {
+            if rule.pack.as_ref().map(|p| p.name.as_str()) != Some(pack.as_str()) {
+                return false;
+            }
+        }
+        if let Some(file) = &self.file {
+            let from_file = rule
+                .source_file
+                .as_deref()
+                .is_some_and(|source| source == file || Path::new(source).ends_with(file));
+            if !from_file {
+                return false;
+            }
+        }
+        if let Some(rule_type) = &self.rule_type {
+            if *rule_type != rule.rule_type {
+                return false;
+            }
+        }
+        self.enabled.map_or(true, |enabled| rule.enabled == enabled)
+    }
+}
+
+/// Selects the rules applied to a request; entries are rule ids (bare or
+/// `pack:id`), tags or pack names
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct RuleSelector {
+    /// Only apply these rules (all rules if empty)
+    #[serde(default)]
+    #[schema(example = json!(["punctuation"]))]
+    pub rules_include: Vec<String>,
+
+    /// Never apply these rules
+    #[serde(default)]
+    #[schema(example = json!(["dev-kommandos"]))]
+    pub rules_exclude: Vec<String>,
+}
+
+impl RuleSelector {
+    /// Whether a rule is selected
+    pub fn matches(&self, rule: &Rule) -> bool {
+        let refers_to = |entry: &String| {
+            rule.has_id(entry)
+                || rule.tags.contains(entry)
+                || rule.pack.as_ref().is_some_and(|p| &p.name == entry)
+        };
+        (self.rules_include.is_empty() || self.rules_include.iter().any(refers_to))
+            && !self.rules_exclude.iter().any(refers_to)
+    }
+}
+
+/// Metadata of a rule pack, declared in the `pack` section of a rules file
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PackInfo {
+    /// Pack name (defaults to the file name)
+    #[serde(default)]
+    #[schema(example = "de-satzzeichen")]
+    pub name: String,
+
+    /// Pack version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "1.0.0")]
+    pub version: Option<String>,
+
+    /// Language the rules are written for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "de")]
+    pub language: Option<String>,
+
+    /// Pack author
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    /// What the pack does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Enabled state of rules that don't set `enabled` themselves
+    #[serde(default = "default_enabled", skip_serializing_if = "is_true")]
+    pub enabled: bool,
 }
 
 fn default_enabled() -> bool {
@@ -77,13 +241,99 @@ fn default_timeout() -> u64 {
     5000 // 5 seconds
 }
 
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_default_timeout(value: &u64) -> bool {
+    *value == default_timeout()
+}
+
 impl Rule {
-    /// Get the effective pattern, adding (?i) if ignore_case is set
+    /// Id namespaced by the rule's pack (e.g. `de-woerter:de-yeah-ja`),
+    /// or by the stem of its source file for rules without a pack
+    pub fn qualified_id(&self) -> String {
+        let namespace = match (&self.pack, &self.source_file) {
+            (Some(pack), _) => pack.name.clone(),
+            (None, Some(file)) => Path::new(file)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            (None, None) => return self.id.clone(),
+        };
+        format!("{}:{}", namespace, self.id)
+    }
+
+    /// Whether the rule is addressed by `id`, either bare or namespaced
+    pub fn has_id(&self, id: &str) -> bool {
+        self.id == id || (id.contains(':') && self.qualified_id() == id)
+    }
+
+    /// Why the rule must not apply given the rules that matched so far, if it must not
+    pub fn unmet_requirement(&self, matched: &[&Rule]) -> Option<String> {
+        let has_matched = |id: &String| matched.iter().any(|r| r.has_id(id));
+
+        if let Some(id) = self.requires_matched.iter().find(|id| !has_matched(id)) {
+            return Some(format!("requires '{}' to have matched", id));
+        }
+        if let Some(id) = self.requires_not_matched.iter().find(|id| has_matched(id)) {
+            return Some(format!("requires '{}' not to have matched", id));
+        }
+        None
+    }
+
+    /// Get the effective pattern, adding (?i) if ignore_case is set.
+    /// Literal patterns are escaped so they match verbatim.
     pub fn effective_pattern(&self) -> String {
-        if self.ignore_case && !self.pattern.starts_with("(?i)") {
-            format!("(?i){}", self.pattern)
+        let pattern = match self.rule_type {
+            RuleType::Literal => regex::escape(&self.pattern),
+            _ => self.pattern.clone(),
+        };
+
+        if self.ignore_case && !pattern.starts_with("(?i)") {
+            format!("(?i){}", pattern)
         } else {
-            self.pattern.clone()
+            pattern
+        }
+    }
+
+    /// Whether this rule is backed by a compiled regex
+    pub fn uses_regex(&self) -> bool {
+        matches!(self.rule_type, RuleType::Regex | RuleType::Literal)
+    }
+
+    /// Check that the rule is well-formed before it is saved or previewed
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("Rule id must not be empty".to_string());
+        }
+        if self.id.chars().any(char::is_whitespace) {
+            return Err(format!("Rule id '{}' must not contain whitespace", self.id));
+        }
+        if self.id.contains(':') {
+            return Err(format!(
+                "Rule id '{}' must not contain ':' (it separates pack and id)",
+                self.id
+            ));
+        }
+        if self.pattern.is_empty() {
+            return Err(format!("Rule '{}' has an empty pattern", self.id));
+        }
+
+        match self.rule_type {
+            RuleType::Regex | RuleType::Literal => regex::Regex::new(&self.effective_pattern())
+                .map(|_| ())
+                .map_err(|e| format!("Invalid regex in rule '{}': {}", self.id, e)),
+            RuleType::Function => BuiltinFunction::from_name(&self.pattern)
+                .map(|_| ())
+                .ok_or_else(|| {
+                    format!("Unknown function '{}' in rule '{}'", self.pattern, self.id)
+                }),
+            RuleType::Shell => Ok(()),
         }
     }
 }
@@ -155,6 +405,7 @@ mod tests {
         let rule = Rule {
             id: "test".to_string(),
             description: None,
+            tags: Vec::new(),
             rule_type: RuleType::Regex,
             pattern: r"\btest\b".to_string(),
             replacement: "TEST".to_string(),
@@ -163,12 +414,74 @@ mod tests {
             ignore_case: true,
             timeout_ms: 5000,
             stop_on_match: false,
+            group: None,
+            requires_matched: Vec::new(),
+            requires_not_matched: Vec::new(),
             source_file: None,
+            pack: None,
+            overridden: Vec::new(),
         };
 
         assert_eq!(rule.effective_pattern(), r"(?i)\btest\b");
     }
 
+    #[test]
+    fn test_literal_pattern_is_escaped() {
+        let rule = Rule {
+            id: "smiley".to_string(),
+            description: None,
+            tags: Vec::new(),
+            rule_type: RuleType::Literal,
+            pattern: ":-)".to_string(),
+            replacement: "🙂".to_string(),
+            priority: 0,
+            enabled: true,
+            ignore_case: true,
+            timeout_ms: 5000,
+            stop_on_match: false,
+            group: None,
+            requires_matched: Vec::new(),
+            requires_not_matched: Vec::new(),
+            source_file: None,
+            pack: None,
+            overridden: Vec::new(),
+        };
+
+        assert_eq!(rule.effective_pattern(), r"(?i):\-\)");
+        assert!(rule.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_rules() {
+        let mut rule = Rule {
+            id: "broken".to_string(),
+            description: None,
+            tags: Vec::new(),
+            rule_type: RuleType::Regex,
+            pattern: "(unclosed".to_string(),
+            replacement: String::new(),
+            priority: 0,
+            enabled: true,
+            ignore_case: false,
+            timeout_ms: 5000,
+            stop_on_match: false,
+            group: None,
+            requires_matched: Vec::new(),
+            requires_not_matched: Vec::new(),
+            source_file: None,
+            pack: None,
+            overridden: Vec::new(),
+        };
+        assert!(rule.validate().unwrap_err().contains("Invalid regex"));
+
+        rule.rule_type = RuleType::Function;
+        rule.pattern = "shout".to_string();
+        assert!(rule.validate().unwrap_err().contains("Unknown function"));
+
+        rule.id = "has space".to_string();
+        assert!(rule.validate().is_err());
+    }
+
     #[test]
     fn test_builtin_functions() {
         assert_eq!(BuiltinFunction::Uppercase.apply("hello"), "HELLO");
@@ -188,4 +501,48 @@ mod tests {
         assert!(BuiltinFunction::from_name("UPPER").is_some());
         assert!(BuiltinFunction::from_name("unknown").is_none());
     }
+
+    #[test]
+    fn test_rule_filter() {
+        let mut rule: Rule = serde_json::from_value(serde_json::json!({
+            "id": "de-punkt", "pattern": "punkt", "tags": ["punctuation"]
+        }))
+        .unwrap();
+        rule.source_file = Some("rules/de/satzzeichen.json".to_string());
+
+        let filter = |json| serde_json::from_value::<RuleFilter>(json).unwrap();
+        assert!(filter(serde_json::json!({})).matches(&rule));
+        assert!(filter(serde_json::json!({"tag": "punctuation", "type": "regex"})).matches(&rule));
+        assert!(filter(serde_json::json!({"file": "de/satzzeichen.json"})).matches(&rule));
+        assert!(!filter(serde_json::json!({"file": "satzzeichen"})).matches(&rule));
+        assert!(!filter(serde_json::json!({"tag": "numbers"})).matches(&rule));
+        assert!(!filter(serde_json::json!({"pack": "de"})).matches(&rule));
+        assert!(!filter(serde_json::json!({"enabled": false})).matches(&rule));
+        assert!(!filter(serde_json::json!({"enabled": true})).is_selective());
+    }
+
+    #[test]
+    fn test_rule_selector() {
+        let mut rule: Rule = serde_json::from_value(serde_json::json!({
+            "id": "de-punkt", "pattern": "punkt", "tags": ["punctuation"]
+        }))
+        .unwrap();
+        rule.pack = Some(Arc::new(PackInfo {
+            name: "de-satzzeichen".to_string(),
+            ..Default::default()
+        }));
+
+        let selector = |json| serde_json::from_value::<RuleSelector>(json).unwrap();
+        assert!(selector(serde_json::json!({})).matches(&rule));
+        for entry in [
+            "de-punkt",
+            "de-satzzeichen:de-punkt",
+            "punctuation",
+            "de-satzzeichen",
+        ] {
+            assert!(selector(serde_json::json!({"rules_include": [entry]})).matches(&rule));
+            assert!(!selector(serde_json::json!({"rules_exclude": [entry]})).matches(&rule));
+        }
+        assert!(!selector(serde_json::json!({"rules_include": ["numbers"]})).matches(&rule));
+    }
 }
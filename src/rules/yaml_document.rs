@@ -0,0 +1,753 @@
+//! Format-preserving edits of YAML rules files
+//!
+//! Edits work line by line on block-style lists (`- id: ...`), the layout
+//! `convert` writes and most hand-written files use. Only the touched lines
+//! change; comments and the layout of other rules stay byte-identical.
+//! Overrides are set the same way in block-style `overrides:` mappings.
+
+use super::document::RulesEditor;
+use super::types::Rule;
+use serde_json::Value;
+
+/// A line of the source text
+#[derive(Debug)]
+struct Line<'a> {
+    /// Byte offset of the line start
+    start: usize,
+    /// Byte offset after the line break (or end of text)
+    end: usize,
+    /// Line content without the line break
+    text: &'a str,
+    /// Number of leading spaces
+    indent: usize,
+}
+
+impl Line<'_> {
+    fn is_blank_or_comment(&self) -> bool {
+        let trimmed = self.text.trim();
+        trimmed.is_empty() || trimmed.starts_with('#')
+    }
+}
+
+fn split_lines(text: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let end = text[start..]
+            .find('\n')
+            .map_or(text.len(), |i| start + i + 1);
+        let line = text[start..end].trim_end_matches(['\n', '\r']);
+        lines.push(Line {
+            start,
+            end,
+            text: line,
+            indent: line.len() - line.trim_start_matches(' ').len(),
+        });
+        start = end;
+    }
+    lines
+}
+
+/// An entry of the top-level rules list
+#[derive(Debug)]
+struct Item {
+    /// Start of the `- ` line
+    start: usize,
+    /// End of the entry's last line
+    end: usize,
+    /// Column of the entry's keys
+    key_indent: Option<usize>,
+    /// Value of the `id` key
+    id: Option<String>,
+    /// Written in flow style (`- {id: a, ...}`)
+    flow: bool,
+}
+
+/// Split `key: value` content into the key and the offset after the colon
+fn parse_key(content: &str) -> Option<(String, usize)> {
+    let colon = if content.starts_with(['"', '\'']) {
+        let quote = content.chars().next()?;
+        let close = content[1..].find(quote)? + 1;
+        close + content[close..].find(':')?
+    } else {
+        content
+            .match_indices(':')
+            .map(|(i, _)| i)
+            .find(|&i| content[i + 1..].is_empty() || content[i + 1..].starts_with(' '))?
+    };
+    let key = serde_yaml::from_str::<String>(content[..colon].trim_end()).ok()?;
+    Some((key, colon + 1))
+}
+
+/// Length of a value without its trailing comment and spaces
+fn value_len(value: &str) -> usize {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in value.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && previous == ' ' => return value[..i].trim_end().len(),
+            None => {},
+        }
+        previous = c;
+    }
+    value.trim_end().len()
+}
+
+/// Render a value for use after `key: `
+fn render_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => match serde_yaml::to_string(s) {
+            Ok(yaml) if !yaml.trim_end().contains('\n') => yaml.trim_end().to_string(),
+            _ => value.to_string(),
+        },
+        // JSON literals, arrays and objects are valid YAML flow values
+        _ => value.to_string(),
+    }
+}
+
+/// Render a rule as a list entry indented by `indent` spaces
+fn render_item(rule: &Rule, indent: usize) -> Result<String, String> {
+    let yaml = serde_yaml::to_string(std::slice::from_ref(rule)).map_err(|e| e.to_string())?;
+    let prefix = " ".repeat(indent);
+    Ok(yaml
+        .lines()
+        .map(|line| format!("{}{}\n", prefix, line))
+        .collect())
+}
+
+/// Apply a single text replacement
+fn splice(text: &str, start: usize, end: usize, replacement: &str) -> String {
+    let mut out = String::with_capacity(text.len() + replacement.len());
+    out.push_str(&text[..start]);
+    out.push_str(replacement);
+    out.push_str(&text[end..]);
+    out
+}
+
+/// Where the rules list lives in the file
+#[derive(Debug, Clone, Copy)]
+enum Placement {
+    /// The whole file is the list
+    Root,
+    /// Under the top-level `rules:` key; `value_start` is right after its colon
+    Key { value_start: usize },
+    /// Envelope without a `rules:` key yet
+    Missing,
+}
+
+/// A YAML rules file parsed for targeted edits
+#[derive(Debug)]
+pub struct YamlDocument<'a> {
+    text: &'a str,
+    placement: Placement,
+    /// Column of the list's `- ` markers
+    indent: usize,
+    items: Vec<Item>,
+    /// Span of an empty flow list (`[]`) that new rules replace
+    empty_list: Option<(usize, usize)>,
+}
+
+impl<'a> YamlDocument<'a> {
+    /// Parse the text of a rules file
+    pub fn parse(text: &'a str) -> Result<Self, String> {
+        let value = serde_yaml::from_str::<serde_yaml::Value>(text).map_err(|e| e.to_string())?;
+        let (count, envelope) = match &value {
+            serde_yaml::Value::Sequence(rules) => (rules.len(), false),
+            serde_yaml::Value::Null => (0, false),
+            serde_yaml::Value::Mapping(map) => match map.get("rules") {
+                Some(serde_yaml::Value::Sequence(rules)) => (rules.len(), true),
+                Some(serde_yaml::Value::Null) | None => (0, true),
+                Some(_) => return Err("`rules` must be a list of rules".to_string()),
+            },
+            _ => return Err("rules file must contain a list of rules".to_string()),
+        };
+
+        let lines = split_lines(text);
+        let mut placement = Placement::Root;
+        let mut region = &lines[..];
+        let mut empty_list = None;
+
+        if envelope {
+            placement = Placement::Missing;
+            region = &[];
+            let key_line = lines.iter().position(|l| {
+                l.indent == 0
+                    && !l.is_blank_or_comment()
+                    && parse_key(l.text).is_some_and(|(key, _)| key == "rules")
+            });
+            if let Some(index) = key_line {
+                let line = &lines[index];
+                let value_start = line.start + parse_key(line.text).map_or(0, |(_, at)| at);
+                if text[value_start..line.start + line.text.len()]
+                    .trim_start()
+                    .starts_with('[')
+                {
+                    empty_list = Some((value_start, line.end));
+                }
+                placement = Placement::Key { value_start };
+                region = &lines[index + 1..];
+            }
+        }
+
+        let mut indent = None;
+        let mut items: Vec<Item> = Vec::new();
+
+        for line in region {
+            if line.is_blank_or_comment() {
+                continue;
+            }
+            let content = &line.text[line.indent..];
+            let is_entry = content == "-" || content.starts_with("- ");
+            if content == "---" || content == "..." {
+                continue;
+            }
+            if let Placement::Key { .. } = placement {
+                // The list ends at the next top-level key
+                let list_indent = indent.unwrap_or(0);
+                if line.indent < list_indent || (line.indent == list_indent && !is_entry) {
+                    break;
+                }
+            } else if indent.is_none() && content.starts_with('[') {
+                empty_list = Some((line.start, line.end));
+                continue;
+            }
+
+            let list_indent = *indent.get_or_insert(line.indent);
+            if line.indent > list_indent {
+                if let Some(item) = items.last_mut() {
+                    item.end = line.end;
+                    item.key_indent.get_or_insert(line.indent);
+                }
+                continue;
+            }
+            if line.indent < list_indent || !is_entry {
+                return Err(format!(
+                    "line {}: expected a '- ' list entry for a rule",
+                    text[..line.start].matches('\n').count() + 1
+                ));
+            }
+
+            let rest = &content[1..];
+            let value = rest.trim_start();
+            let key_indent = (!value.is_empty() && !value.starts_with('#'))
+                .then(|| line.indent + 1 + rest.len() - value.len());
+            items.push(Item {
+                start: line.start,
+                end: line.end,
+                key_indent,
+                id: None,
+                flow: value.starts_with('{'),
+            });
+        }
+
+        if items.len() != count {
+            return Err("flow-style rule lists can't be edited in place".to_string());
+        }
+
+        for item in &mut items {
+            let entry = serde_yaml::from_str::<Vec<serde_yaml::Value>>(&text[item.start..item.end])
+                .map_err(|e| e.to_string())?;
+            item.id = entry
+                .first()
+                .and_then(|rule| rule.get("id"))
+                .and_then(|id| id.as_str())
+                .map(str::to_string);
+        }
+
+        let default_indent = if envelope { 2 } else { 0 };
+        Ok(Self {
+            text,
+            placement,
+            indent: indent.unwrap_or(default_indent),
+            items,
+            empty_list,
+        })
+    }
+
+    /// The entry of the rule with the given id
+    fn find_rule(&self, rule_id: &str) -> Result<&Item, String> {
+        self.items
+            .iter()
+            .find(|item| item.id.as_deref() == Some(rule_id))
+            .ok_or_else(|| format!("rule '{}' not found", rule_id))
+    }
+
+    /// Text to insert at `at` so that it starts on a new line
+    fn line_break_before(&self, at: usize) -> &'static str {
+        if at > 0 && !self.text[..at].ends_with('\n') {
+            "\n"
+        } else {
+            ""
+        }
+    }
+
+    /// Set the nested keys of `path` in the block mapping made of `lines`,
+    /// whose keys sit at column `indent`. Missing keys are inserted at `end`.
+    fn set_nested(
+        &self,
+        lines: &[Line],
+        end: usize,
+        indent: usize,
+        path: &[&str],
+        rendered: &str,
+    ) -> Result<String, String> {
+        let Some((key, rest)) = path.split_first() else {
+            return Err("no key to set".to_string());
+        };
+
+        let found = lines.iter().enumerate().find_map(|(i, line)| {
+            if line.indent != indent || line.is_blank_or_comment() {
+                return None;
+            }
+            parse_key(&line.text[indent..])
+                .filter(|(found, _)| found == key)
+                .map(|(_, after_colon)| (i, after_colon))
+        });
+        let Some((index, after_colon)) = found else {
+            let insertion = format!(
+                "{}{}",
+                self.line_break_before(end),
+                render_nested(indent, path, rendered)?
+            );
+            return Ok(splice(self.text, end, end, &insertion));
+        };
+
+        let line = &lines[index];
+        let start = line.start + indent + after_colon;
+        let inline = &self.text[start..line.start + line.text.len()];
+        let inline_end = start + value_len(inline);
+        let mut children: Vec<&Line> = lines[index + 1..]
+            .iter()
+            .take_while(|l| l.indent > indent || l.text.trim().is_empty())
+            .collect();
+        while children.last().is_some_and(|l| l.text.trim().is_empty()) {
+            children.pop();
+        }
+
+        if rest.is_empty() {
+            let value_end = children
+                .last()
+                .map_or(inline_end, |last| last.start + last.text.len());
+            return Ok(splice(
+                self.text,
+                start,
+                value_end,
+                &format!(" {}", rendered),
+            ));
+        }
+
+        match inline[..value_len(inline)].trim() {
+            "" => {},
+            // An empty mapping turns into a block
+            "{}" | "~" | "null" => {
+                let block = render_nested(indent + 2, rest, rendered)?;
+                return Ok(splice(
+                    self.text,
+                    start,
+                    inline_end,
+                    &format!("\n{}", block.trim_end_matches('\n')),
+                ));
+            },
+            _ => {
+                return Err(format!(
+                    "'{}' is written in flow style and can't be edited in place",
+                    key
+                ));
+            },
+        }
+
+        let child_indent = children
+            .iter()
+            .find(|l| !l.is_blank_or_comment())
+            .map_or(indent + 2, |l| l.indent);
+        let child_end = children.last().map_or(line.end, |last| last.end);
+        let child_lines = &lines[index + 1..index + 1 + children.len()];
+        self.set_nested(child_lines, child_end, child_indent, rest, rendered)
+    }
+}
+
+/// Render nested keys as a block mapping starting at column `indent`,
+/// with the value on the innermost key
+fn render_nested(indent: usize, path: &[&str], rendered: &str) -> Result<String, String> {
+    let mut out = String::new();
+    for (depth, key) in path.iter().enumerate() {
+        let key_yaml = serde_yaml::to_string(key).map_err(|e| e.to_string())?;
+        out.push_str(&" ".repeat(indent + 2 * depth));
+        out.push_str(key_yaml.trim_end());
+        out.push(':');
+        if depth + 1 == path.len() {
+            out.push(' ');
+            out.push_str(rendered);
+        }
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+impl RulesEditor for YamlDocument<'_> {
+    fn set_rule_field(&self, rule_id: &str, key: &str, value: &Value) -> Result<String, String> {
+        let item = self.find_rule(rule_id)?;
+        let key_indent = match item.key_indent {
+            Some(indent) if !item.flow => indent,
+            _ => {
+                return Err(format!(
+                    "rule '{}' is written in flow style and can't be edited in place",
+                    rule_id
+                ));
+            },
+        };
+        let rendered = render_value(value);
+
+        let lines = split_lines(&self.text[..item.end]);
+        let item_lines: Vec<&Line> = lines.iter().filter(|l| l.start >= item.start).collect();
+
+        for (i, line) in item_lines.iter().enumerate() {
+            let is_key_line = i == 0 || line.indent == key_indent;
+            if !is_key_line || line.is_blank_or_comment() || line.text.len() < key_indent {
+                continue;
+            }
+            let Some((found, after_colon)) = parse_key(&line.text[key_indent..]) else {
+                continue;
+            };
+            if found != key {
+                continue;
+            }
+
+            // Value starts after the colon and either ends on this line or is a
+            // nested block on the following, more indented lines
+            let start = line.start + key_indent + after_colon;
+            let block_end = item_lines[i + 1..]
+                .iter()
+                .take_while(|l| l.indent > key_indent || l.text.trim().is_empty())
+                .filter(|l| !l.text.trim().is_empty())
+                .last();
+            let end = match block_end {
+                Some(last) => last.start + last.text.len(),
+                None => start + value_len(&self.text[start..line.start + line.text.len()]),
+            };
+            return Ok(splice(self.text, start, end, &format!(" {}", rendered)));
+        }
+
+        let key_yaml = serde_yaml::to_string(key).map_err(|e| e.to_string())?;
+        let insertion = format!(
+            "{}{}{}: {}\n",
+            self.line_break_before(item.end),
+            " ".repeat(key_indent),
+            key_yaml.trim_end(),
+            rendered
+        );
+        Ok(splice(self.text, item.end, item.end, &insertion))
+    }
+
+    fn replace_rule(&self, rule_id: &str, rule: &Rule) -> Result<String, String> {
+        let item = self.find_rule(rule_id)?;
+        Ok(splice(
+            self.text,
+            item.start,
+            item.end,
+            &render_item(rule, self.indent)?,
+        ))
+    }
+
+    fn insert_rule(&self, rule: &Rule) -> Result<String, String> {
+        let rendered = render_item(rule, self.indent)?;
+
+        if let Some(last) = self.items.last() {
+            let insertion = format!("{}{}", self.line_break_before(last.end), rendered);
+            return Ok(splice(self.text, last.end, last.end, &insertion));
+        }
+
+        match (self.placement, self.empty_list) {
+            (Placement::Root, Some((start, end))) => Ok(splice(self.text, start, end, &rendered)),
+            (_, Some((start, end))) => {
+                Ok(splice(self.text, start, end, &format!("\n{}", rendered)))
+            },
+            (Placement::Key { value_start }, None) => {
+                let at = self.text[value_start..]
+                    .find('\n')
+                    .map_or(self.text.len(), |i| value_start + i + 1);
+                let insertion = format!("{}{}", self.line_break_before(at), rendered);
+                Ok(splice(self.text, at, at, &insertion))
+            },
+            (placement, None) => {
+                let at = self.text.len();
+                let header = match placement {
+                    Placement::Missing => "rules:\n",
+                    _ => "",
+                };
+                let insertion = format!("{}{}{}", self.line_break_before(at), header, rendered);
+                Ok(splice(self.text, at, at, &insertion))
+            },
+        }
+    }
+
+    fn remove_rule(&self, rule_id: &str) -> Result<String, String> {
+        let item = self.find_rule(rule_id)?;
+
+        // Take the comment lines directly above the rule with it
+        let lines = split_lines(&self.text[..item.start]);
+        let start = lines
+            .iter()
+            .rev()
+            .take_while(|l| l.text.trim_start().starts_with('#'))
+            .last()
+            .map_or(item.start, |l| l.start);
+
+        if self.items.len() > 1 {
+            return Ok(splice(self.text, start, item.end, ""));
+        }
+
+        // An empty value is null rather than a list, so keep an explicit empty one
+        match self.placement {
+            Placement::Key { value_start } => {
+                let output = splice(self.text, start, item.end, "");
+                Ok(splice(&output, value_start, value_start, " []"))
+            },
+            _ => Ok(splice(self.text, start, item.end, "[]\n")),
+        }
+    }
+
+    fn set_override_field(
+        &self,
+        rule_id: &str,
+        key: &str,
+        value: &Value,
+    ) -> Result<String, String> {
+        if let Placement::Root = self.placement {
+            if !self.items.is_empty() || self.empty_list.is_some() {
+                return Err("overrides file must be a mapping with an `overrides` key".to_string());
+            }
+        }
+
+        // `overrides: {<id>: {<key>: <value>}}` as nested blocks
+        let lines = split_lines(self.text);
+        let output = self.set_nested(
+            &lines,
+            self.text.len(),
+            0,
+            &["overrides", rule_id, key],
+            &render_value(value),
+        )?;
+
+        // Check the line-based edit against a real parse
+        let parsed =
+            serde_yaml::from_str::<serde_yaml::Value>(&output).map_err(|e| e.to_string())?;
+        let written = parsed
+            .get("overrides")
+            .and_then(|overrides| overrides.get(rule_id))
+            .and_then(|entry| entry.get(key))
+            .map(|value| serde_json::to_value(value).map_err(|e| e.to_string()))
+            .transpose()?;
+        if written.as_ref() != Some(value) {
+            return Err(format!(
+                "override of rule '{}' can't be edited in place",
+                rule_id
+            ));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = "# Punctuation
+- id: slash
+  pattern: \\bslash\\b
+  replacement: /
+  enabled: true  # toggled from the dashboard
+
+# Numbers
+- id: digits
+  description: >
+    Replace digits
+    with a hash
+  pattern: \\d+
+  replacement: '#'
+";
+
+    fn rule(id: &str) -> Rule {
+        serde_json::from_value(serde_json::json!({"id": id, "pattern": "x"})).unwrap()
+    }
+
+    #[test]
+    fn test_set_field_keeps_layout() {
+        let doc = YamlDocument::parse(RULES).unwrap();
+
+        let out = doc
+            .set_rule_field("slash", "enabled", &Value::Bool(false))
+            .unwrap();
+        assert_eq!(out, RULES.replace("enabled: true ", "enabled: false "));
+
+        let out = doc
+            .set_rule_field("digits", "enabled", &Value::Bool(false))
+            .unwrap();
+        assert_eq!(out, format!("{}  enabled: false\n", RULES));
+
+        let out = doc
+            .set_rule_field("digits", "description", &Value::String("Digits".into()))
+            .unwrap();
+        assert!(
+            out.contains("  description: Digits\n  pattern: \\d+\n"),
+            "{}",
+            out
+        );
+        assert!(!out.contains("with a hash"));
+    }
+
+    #[test]
+    fn test_insert_replace_remove() {
+        let doc = YamlDocument::parse(RULES).unwrap();
+
+        let out = doc.insert_rule(&rule("new")).unwrap();
+        assert!(out.starts_with(RULES));
+        assert!(
+            out.ends_with(
+                "- id: new\n  type: regex\n  pattern: x\n  priority: 0\n  enabled: true\n"
+            ),
+            "{}",
+            out
+        );
+
+        let out = doc.replace_rule("slash", &rule("slash")).unwrap();
+        assert!(
+            out.starts_with("# Punctuation\n- id: slash\n  type: regex\n  pattern: x\n"),
+            "{}",
+            out
+        );
+        assert!(out.contains("\n# Numbers\n- id: digits\n"));
+
+        let out = doc.remove_rule("digits").unwrap();
+        assert_eq!(out, RULES[..RULES.find("# Numbers").unwrap()]);
+
+        let out = YamlDocument::parse(&out)
+            .unwrap()
+            .remove_rule("slash")
+            .unwrap();
+        assert_eq!(out, "[]\n\n");
+        let out = YamlDocument::parse(&out)
+            .unwrap()
+            .insert_rule(&rule("a"))
+            .unwrap();
+        assert!(out.starts_with("- id: a\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_flow_style_is_rejected() {
+        let doc = YamlDocument::parse("- {id: a, pattern: x}\n").unwrap();
+        let err = doc
+            .set_rule_field("a", "enabled", &Value::Bool(false))
+            .unwrap_err();
+        assert!(err.contains("flow style"), "{}", err);
+        assert!(YamlDocument::parse("[{id: a, pattern: x}]").is_err());
+    }
+
+    const ENVELOPE: &str = "pack:
+  name: de
+  version: 1.0.0
+
+rules:
+  - id: a
+    pattern: x
+  # Second rule
+  - id: b
+    pattern: y
+";
+
+    #[test]
+    fn test_envelope_rules() {
+        let doc = YamlDocument::parse(ENVELOPE).unwrap();
+
+        let out = doc
+            .set_rule_field("a", "enabled", &Value::Bool(false))
+            .unwrap();
+        assert_eq!(
+            out,
+            ENVELOPE.replace("    pattern: x\n", "    pattern: x\n    enabled: false\n")
+        );
+
+        let out = doc.insert_rule(&rule("c")).unwrap();
+        assert!(
+            out.contains("    pattern: y\n  - id: c\n    type: regex\n"),
+            "{}",
+            out
+        );
+
+        let out = doc.remove_rule("b").unwrap();
+        let out = YamlDocument::parse(&out).unwrap().remove_rule("a").unwrap();
+        assert_eq!(out, "pack:\n  name: de\n  version: 1.0.0\n\nrules: []\n");
+
+        let out = YamlDocument::parse(&out)
+            .unwrap()
+            .insert_rule(&rule("a"))
+            .unwrap();
+        assert!(
+            out.contains("\nrules:\n  - id: a\n    type: regex\n"),
+            "{}",
+            out
+        );
+
+        // Entries may also sit at the key's own indentation
+        let flush = "rules:\n- id: a\n  pattern: x\npack:\n  name: de\n";
+        let out = YamlDocument::parse(flush)
+            .unwrap()
+            .insert_rule(&rule("b"))
+            .unwrap();
+        assert!(
+            out.starts_with("rules:\n- id: a\n  pattern: x\n- id: b\n"),
+            "{}",
+            out
+        );
+        assert!(out.ends_with("pack:\n  name: de\n"));
+    }
+
+    #[test]
+    fn test_set_override_field() {
+        let set = |text: &str, rule_id: &str, value: bool| {
+            YamlDocument::parse(text).unwrap().set_override_field(
+                rule_id,
+                "enabled",
+                &Value::Bool(value),
+            )
+        };
+
+        assert_eq!(
+            set("", "a", false).unwrap(),
+            "overrides:\n  a:\n    enabled: false\n"
+        );
+        assert_eq!(
+            set("overrides: {}  # none yet\n", "a", false).unwrap(),
+            "overrides:\n  a:\n    enabled: false  # none yet\n"
+        );
+
+        let overrides = "overrides:
+  # Louder
+  a:
+    replacement: '!'
+    enabled: true  # from the dashboard
+
+rules:
+  - id: b
+    pattern: x
+";
+        assert_eq!(
+            set(overrides, "a", false).unwrap(),
+            overrides.replace("enabled: true ", "enabled: false ")
+        );
+        assert_eq!(
+            set(overrides, "de:c", false).unwrap(),
+            overrides.replace(
+                "from the dashboard\n",
+                "from the dashboard\n  de:c:\n    enabled: false\n"
+            )
+        );
+
+        let err = set("overrides:\n  a: {enabled: true}\n", "a", false).unwrap_err();
+        assert!(err.contains("flow style"), "{}", err);
+        assert!(set("- id: a\n  pattern: x\n", "a", false).is_err());
+    }
+}
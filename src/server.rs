@@ -1,24 +1,51 @@
 //! HTTP server setup and routing
 
+use crate::auth;
+use crate::config::Config;
+use crate::extract::Extraction;
 use crate::handlers;
+use crate::history::History;
 use crate::models::{
-    ChatCompletionRequest, ChatCompletionResponse, Choice, HealthResponse, Message, ModelInfo,
-    ModelsResponse, ResponseMessage, RuleInfo, RuleToggleResponse, RulesResponse,
-    TransformationLogEntry, TransformationLogResponse, Usage,
+    AnthropicContentBlock, AnthropicDeltaUsage, AnthropicErrorDetail, AnthropicErrorResponse,
+    AnthropicMessage, AnthropicMessageDelta, AnthropicMessagesRequest, AnthropicStreamEvent,
+    AnthropicTextDelta, AnthropicUsage, BatchTransformRequest, BatchTransformResponse,
+    ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, Choice, ChunkChoice,
+    ChunkDelta, CompletionChoice, CompletionPrompt, CompletionRequest, CompletionResponse,
+    ContentPart, CreateRuleRequest, ErrorDetail, ErrorResponse, HealthResponse, HistoryResponse,
+    MatchSpan, Message, MessageContent, ModelInfo, ModelsResponse, OllamaChatRequest,
+    OllamaChatResponse, OllamaErrorResponse, OllamaGenerateRequest, OllamaGenerateResponse,
+    OllamaMessage, OllamaModel, OllamaModelDetails, OllamaOptions, OllamaStats, OllamaTagsResponse,
+    PackSummary, ResponseMessage, ResponseOutputContent, ResponseOutputItem, ResponsesInput,
+    ResponsesRequest, ResponsesResponse, ResponsesUsage, RuleInfo, RuleMatchEntry,
+    RulePreviewEntry, RulePreviewRequest, RulePreviewResponse, RuleToggleResponse, RuleTraceEntry,
+    RuleValidationError, RulesBulkResponse, RulesResponse, SamplingParams, SkippedRuleEntry,
+    StopSequences, StreamOptions, TransformRequest, TransformResponse, TransformationLogEntry,
+    TransformationLogResponse, Usage,
 };
-use crate::rules::RuleEngine;
-use axum::{Router, routing::delete, routing::get, routing::post};
+use crate::rules::{EngineOptions, PackInfo, Rule, RuleEngine, RuleFilter, RuleSelector, RuleType};
+use crate::upstream::Upstream;
+use axum::{Router, middleware, routing::delete, routing::get, routing::post, routing::put};
+use chrono::{DateTime, Utc};
 use std::net::{SocketAddr, TcpListener};
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
-use utoipa::OpenApi;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
 /// Application state shared across handlers
 #[derive(Clone)]
 pub struct AppState {
     pub rule_engine: Arc<RuleEngine>,
+    /// On-disk history, if enabled
+    pub history: Option<Arc<History>>,
+    /// Transcript extractors for prompts
+    pub extraction: Arc<Extraction>,
+    /// Upstream LLM stage, if configured
+    pub upstream: Option<Arc<Upstream>>,
+    /// When the server started, reported as the modification time of the model
+    pub started_at: DateTime<Utc>,
 }
 
 /// OpenAPI documentation
@@ -32,39 +59,143 @@ pub struct AppState {
     ),
     paths(
         handlers::health,
+        handlers::transform_text,
+        handlers::transform_batch,
         handlers::chat_completions,
+        handlers::completions,
+        handlers::responses,
         handlers::list_models,
+        handlers::anthropic_messages,
+        handlers::ollama_chat,
+        handlers::ollama_generate,
+        handlers::ollama_tags,
         handlers::get_logs,
         handlers::clear_logs,
+        handlers::stream_logs,
+        handlers::get_history,
         handlers::get_rules,
         handlers::toggle_rule,
+        handlers::enable_rules,
+        handlers::disable_rules,
+        handlers::create_rule,
+        handlers::update_rule,
+        handlers::delete_rule,
+        handlers::preview_rules,
     ),
     components(schemas(
+        TransformRequest,
+        TransformResponse,
+        BatchTransformRequest,
+        BatchTransformResponse,
+        RuleTraceEntry,
+        MatchSpan,
+        RuleSelector,
         ChatCompletionRequest,
         ChatCompletionResponse,
+        ChatCompletionChunk,
+        ChunkChoice,
+        ChunkDelta,
+        StreamOptions,
+        SamplingParams,
+        StopSequences,
         Message,
+        MessageContent,
+        ContentPart,
         Choice,
         ResponseMessage,
         Usage,
+        CompletionRequest,
+        CompletionPrompt,
+        CompletionResponse,
+        CompletionChoice,
+        ResponsesRequest,
+        ResponsesInput,
+        ResponsesResponse,
+        ResponseOutputItem,
+        ResponseOutputContent,
+        ResponsesUsage,
+        ErrorResponse,
+        ErrorDetail,
+        AnthropicMessagesRequest,
+        AnthropicMessage,
+        AnthropicContentBlock,
+        AnthropicUsage,
+        AnthropicStreamEvent,
+        AnthropicTextDelta,
+        AnthropicMessageDelta,
+        AnthropicDeltaUsage,
+        AnthropicErrorResponse,
+        AnthropicErrorDetail,
+        OllamaChatRequest,
+        OllamaGenerateRequest,
+        OllamaChatResponse,
+        OllamaGenerateResponse,
+        OllamaMessage,
+        OllamaStats,
+        OllamaOptions,
+        OllamaTagsResponse,
+        OllamaErrorResponse,
+        OllamaModel,
+        OllamaModelDetails,
         HealthResponse,
         ModelsResponse,
         ModelInfo,
         TransformationLogResponse,
         TransformationLogEntry,
+        HistoryResponse,
+        RuleMatchEntry,
+        SkippedRuleEntry,
         RulesResponse,
         RuleInfo,
+        PackSummary,
+        PackInfo,
         RuleToggleResponse,
+        RulesBulkResponse,
+        RuleFilter,
+        Rule,
+        RuleType,
+        CreateRuleRequest,
+        RulePreviewRequest,
+        RulePreviewResponse,
+        RulePreviewEntry,
+        RuleValidationError,
     )),
     tags(
         (name = "Health", description = "Health check endpoints"),
-        (name = "Chat", description = "OpenAI-compatible chat completion"),
+        (name = "Transform", description = "Native text transformation with rule traces"),
+        (name = "Chat", description = "OpenAI-compatible chat completion, legacy completion and Responses API"),
         (name = "Models", description = "Model listing"),
+        (name = "Anthropic", description = "Anthropic Messages-compatible endpoint"),
+        (name = "Ollama", description = "Ollama-compatible chat, generate and model listing"),
         (name = "Logs", description = "Transformation logging"),
         (name = "Rules", description = "Rule management"),
-    )
+    ),
+    modifiers(&SecurityAddon),
+    security(("api_key" = []))
 )]
 pub struct ApiDoc;
 
+/// Documents the optional API key as a bearer token
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some(
+                        "Required if the server sets `api_key` or `admin_key`; \
+                         also accepted as an `x-api-key` header",
+                    ))
+                    .build(),
+            ),
+        );
+    }
+}
+
 /// Check if a port is available for binding
 fn check_port_available(host: &str, port: u16) -> Result<(), String> {
     let addr = format!("{}:{}", host, port);
@@ -85,37 +216,112 @@ fn check_port_available(host: &str, port: u16) -> Result<(), String> {
 }
 
 /// Run the HTTP server
-pub async fn run(
-    host: &str,
-    port: u16,
-    rules_paths: &[String],
-    enable_shell_rules: bool,
-) -> anyhow::Result<()> {
+pub async fn run(config: &Config) -> anyhow::Result<()> {
+    let (host, port) = (config.host.as_str(), config.port);
+
     // Check if port is available before doing anything else
     if let Err(msg) = check_port_available(host, port) {
         anyhow::bail!(msg);
     }
 
     // Initialize rule engine
-    let rule_engine = Arc::new(RuleEngine::new_from_paths(rules_paths, enable_shell_rules)?);
+    let options = config.engine_options()?;
+    let privacy = options.privacy.clone();
+    let rule_engine = Arc::new(RuleEngine::with_options(
+        &config.get_rules_paths(),
+        options.clone(),
+    )?);
 
     // Start file watcher for hot-reload
     rule_engine.clone().watch_for_changes()?;
 
-    let state = AppState { rule_engine };
+    let history = match config.history_options() {
+        Some(options) => {
+            let history = History::open(options)?;
+            tracing::info!("Recording history in {}", history.dir().display());
+            Some(Arc::new(history))
+        },
+        None => None,
+    };
+
+    let upstream = match config.upstream_options() {
+        Some(upstream) => {
+            // Post-stage rules share the options, but not the overrides of the main rules
+            let post_rules = if upstream.post_rules_paths.is_empty() {
+                None
+            } else {
+                let options = EngineOptions {
+                    overrides_path: None,
+                    ..options
+                };
+                let engine = Arc::new(RuleEngine::with_options(
+                    &upstream.post_rules_paths,
+                    options,
+                )?);
+                engine.clone().watch_for_changes()?;
+                Some(engine)
+            };
+            let upstream = Upstream::new(upstream, post_rules, privacy)?;
+            tracing::info!("Forwarding rule output to {}", upstream.base_url());
+            Some(Arc::new(upstream))
+        },
+        None => None,
+    };
+
+    let state = AppState {
+        rule_engine,
+        history,
+        extraction: Arc::new(config.extraction()?),
+        upstream,
+        started_at: Utc::now(),
+    };
+
+    let auth = Arc::new(config.auth());
+    if auth.is_enabled() {
+        tracing::info!("API keys required");
+    }
 
     // Build router
-    let app = Router::new()
-        // Dashboard UI
-        .route("/", get(handlers::dashboard))
-        // API routes
-        .route("/health", get(handlers::health))
+    let api_routes = Router::new()
+        .route("/v1/transform", post(handlers::transform_text))
+        .route("/v1/transform/batch", post(handlers::transform_batch))
         .route("/v1/chat/completions", post(handlers::chat_completions))
+        .route("/v1/completions", post(handlers::completions))
+        .route("/v1/responses", post(handlers::responses))
         .route("/v1/models", get(handlers::list_models))
+        // Anthropic-compatible API
+        .route("/v1/messages", post(handlers::anthropic_messages))
+        // Ollama-compatible API
+        .route("/api/chat", post(handlers::ollama_chat))
+        .route("/api/generate", post(handlers::ollama_generate))
+        .route("/api/tags", get(handlers::ollama_tags))
+        .route_layer(middleware::from_fn_with_state(
+            auth.clone(),
+            auth::require_api,
+        ));
+
+    let admin_routes = Router::new()
         .route("/v1/logs", get(handlers::get_logs))
         .route("/v1/logs", delete(handlers::clear_logs))
+        .route("/v1/logs/stream", get(handlers::stream_logs))
+        .route("/v1/history", get(handlers::get_history))
         .route("/v1/rules", get(handlers::get_rules))
+        .route("/v1/rules", post(handlers::create_rule))
+        .route("/v1/rules/:rule_id", put(handlers::update_rule))
+        .route("/v1/rules/:rule_id", delete(handlers::delete_rule))
         .route("/v1/rules/:rule_id/toggle", post(handlers::toggle_rule))
+        .route("/v1/rules/bulk/enable", post(handlers::enable_rules))
+        .route("/v1/rules/bulk/disable", post(handlers::disable_rules))
+        .route("/v1/preview", post(handlers::preview_rules))
+        .route_layer(middleware::from_fn_with_state(auth, auth::require_admin));
+
+    let app = Router::new()
+        // Dashboard UI
+        .route("/", get(handlers::dashboard))
+        // API routes
+        .route("/health", get(handlers::health))
+        .merge(api_routes)
+        .merge(admin_routes)
         // Swagger UI
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         // Middleware
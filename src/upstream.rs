@@ -0,0 +1,363 @@
+//! Upstream LLM stage
+//!
+//! Optionally forwards the rule output to an OpenAI-compatible server (for
+//! example a local llama.cpp server) for a pass the rules cannot do, such as
+//! grammar smoothing. The request's messages and sampling parameters are
+//! forwarded with the rule output in place of the transcript, and the answer
+//! can be cleaned up by a second set of rules.
+//! When the upstream fails or times out, the rule output is returned as is.
+
+use crate::error::AppError;
+use crate::models::{Message, SamplingParams};
+use crate::privacy::Privacy;
+use crate::rules::RuleEngine;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where and how to reach the upstream server
+#[derive(Debug, Clone)]
+pub struct UpstreamOptions {
+    /// Base URL of the OpenAI-compatible API, e.g. `http://127.0.0.1:8080/v1`
+    pub base_url: String,
+
+    /// Sent as a bearer token, if set
+    pub api_key: Option<String>,
+
+    /// Model to request (default: the model of the incoming request)
+    pub model: Option<String>,
+
+    /// Time allowed for the whole upstream request
+    pub timeout: Duration,
+
+    /// Rules applied to the upstream answer (post stage)
+    pub post_rules_paths: Vec<String>,
+}
+
+/// A text message forwarded to the upstream server
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UpstreamMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// The conversation of an incoming request as forwarded to the upstream:
+/// its messages in order and its sampling parameters
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpstreamChat {
+    pub messages: Vec<UpstreamMessage>,
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
+}
+
+impl UpstreamChat {
+    /// The system prompts followed by the request's messages, with the text
+    /// parts of each message (empty for messages without text)
+    pub fn new(system: &[String], messages: &[Message], sampling: SamplingParams) -> Self {
+        let system = system.iter().map(|content| UpstreamMessage {
+            role: "system".to_string(),
+            content: content.clone(),
+        });
+        let messages = messages.iter().map(|message| UpstreamMessage {
+            role: message.role.clone(),
+            content: message.text().unwrap_or_default(),
+        });
+        Self {
+            messages: system.chain(messages).collect(),
+            sampling,
+        }
+    }
+
+    /// Replace the content of the last user message, adding one if there is none
+    pub fn set_user(&mut self, content: String) {
+        match self.messages.iter_mut().rev().find(|m| m.role == "user") {
+            Some(message) => message.content = content,
+            None => self.messages.push(UpstreamMessage {
+                role: "user".to_string(),
+                content,
+            }),
+        }
+    }
+
+    /// The system (and developer) messages
+    pub fn system_mut(&mut self) -> impl Iterator<Item = &mut UpstreamMessage> {
+        self.messages
+            .iter_mut()
+            .filter(|m| matches!(m.role.as_str(), "system" | "developer"))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct UpstreamRequest<'a> {
+    model: &'a str,
+    #[serde(flatten)]
+    chat: &'a UpstreamChat,
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpstreamResponse {
+    choices: Vec<UpstreamChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpstreamChoice {
+    message: UpstreamAnswer,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpstreamAnswer {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// Client for the upstream stage with its post-stage rules
+pub struct Upstream {
+    client: reqwest::Client,
+    options: UpstreamOptions,
+    post_rules: Option<Arc<RuleEngine>>,
+    privacy: Privacy,
+}
+
+impl Upstream {
+    /// Create the client; `post_rules` is the engine for the post stage, if any
+    pub fn new(
+        options: UpstreamOptions,
+        post_rules: Option<Arc<RuleEngine>>,
+        privacy: Privacy,
+    ) -> Result<Self, AppError> {
+        let client = reqwest::Client::builder()
+            .timeout(options.timeout)
+            .build()
+            .map_err(|e| AppError::UpstreamError(e.to_string()))?;
+
+        Ok(Self {
+            client,
+            options,
+            post_rules,
+            privacy,
+        })
+    }
+
+    /// The base URL of the upstream server
+    pub fn base_url(&self) -> &str {
+        &self.options.base_url
+    }
+
+    /// Run the upstream stage: send the request's conversation (with the rule
+    /// output substituted in) and apply the post-stage rules to the answer.
+    /// None if the upstream fails, so the rule output can be used.
+    pub async fn process(&self, chat: &UpstreamChat, model: &str) -> Option<String> {
+        let answer = match self.complete(chat, model).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                tracing::warn!("Upstream failed, using the rule output: {}", e);
+                return None;
+            },
+        };
+        tracing::debug!("Upstream answer: {}", self.privacy.text(&answer));
+
+        Some(match &self.post_rules {
+            Some(rules) => rules.apply(&answer),
+            None => answer,
+        })
+    }
+
+    /// Send a chat completion request and return the content of the first choice
+    async fn complete(&self, chat: &UpstreamChat, model: &str) -> Result<String, AppError> {
+        let body = UpstreamRequest {
+            model: self.options.model.as_deref().unwrap_or(model),
+            chat,
+            stream: false,
+        };
+
+        let url = format!(
+            "{}/chat/completions",
+            self.options.base_url.trim_end_matches('/')
+        );
+        let mut request = self.client.post(url).json(&body);
+        if let Some(key) = &self.options.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| AppError::UpstreamError(e.to_string()))?;
+        let response: UpstreamResponse = response
+            .json()
+            .await
+            .map_err(|e| AppError::UpstreamError(e.to_string()))?;
+
+        response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| AppError::UpstreamError("answer without content".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
+
+    /// Serve a mock chat completion endpoint that uppercases the user message
+    /// after the given delay; returns its base URL
+    async fn mock_server(delay: Duration) -> String {
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                tokio::time::sleep(delay).await;
+                let messages = body["messages"].as_array().unwrap();
+                let user = messages.last().unwrap()["content"].as_str().unwrap();
+                Json(serde_json::json!({
+                    "choices": [{"message": {
+                        "role": "assistant",
+                        "content": format!("{} [{} via {}]", user.to_uppercase(), messages.len(), body["model"].as_str().unwrap())
+                    }}]
+                }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/v1/", addr)
+    }
+
+    fn client(base_url: String, timeout: Duration, model: Option<&str>) -> Upstream {
+        let options = UpstreamOptions {
+            base_url,
+            api_key: Some("secret".to_string()),
+            model: model.map(String::from),
+            timeout,
+            post_rules_paths: Vec::new(),
+        };
+        Upstream::new(options, None, Privacy::default()).unwrap()
+    }
+
+    /// A conversation of the system prompts and one user message
+    fn chat(system: &[&str], user: &str) -> UpstreamChat {
+        let mut chat = UpstreamChat::new(
+            &system.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            &[],
+            SamplingParams::default(),
+        );
+        chat.set_user(user.to_string());
+        chat
+    }
+
+    #[tokio::test]
+    async fn test_upstream_answer() {
+        let base_url = mock_server(Duration::ZERO).await;
+
+        let upstream = client(base_url.clone(), Duration::from_secs(5), None);
+        let answer = upstream
+            .process(&chat(&["Fix grammar"], "foo / bar"), "local-rules")
+            .await;
+        assert_eq!(answer.as_deref(), Some("FOO / BAR [2 via local-rules]"));
+
+        // The configured model replaces the requested one
+        let upstream = client(base_url, Duration::from_secs(5), Some("llama"));
+        let answer = upstream.process(&chat(&[], "a"), "local-rules").await;
+        assert_eq!(answer.as_deref(), Some("A [1 via llama]"));
+    }
+
+    #[tokio::test]
+    async fn test_forwards_conversation_and_sampling() {
+        // Answer with the request body
+        let app = Router::new().route(
+            "/chat/completions",
+            post(|Json(body): Json<serde_json::Value>| async move {
+                Json(serde_json::json!({
+                    "choices": [{"message": {"role": "assistant", "content": body.to_string()}}]
+                }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let request: crate::models::ChatCompletionRequest =
+            serde_json::from_value(serde_json::json!({
+                "messages": [
+                    {"role": "system", "content": "Fix grammar"},
+                    {"role": "user", "content": "first"},
+                    {"role": "assistant", "content": "First."},
+                    {"role": "user", "content": "second slash"},
+                ],
+                "temperature": 0.2,
+                "max_completion_tokens": 64,
+                "stop": ["\n"],
+                "stream": true,
+            }))
+            .unwrap();
+        let mut chat =
+            UpstreamChat::new(&[], request.messages.as_deref().unwrap(), request.sampling);
+        chat.set_user("second /".to_string());
+
+        let upstream = client(base_url, Duration::from_secs(5), None);
+        let answer = upstream.process(&chat, "local-rules").await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(&answer).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "model": "local-rules",
+                "messages": [
+                    {"role": "system", "content": "Fix grammar"},
+                    {"role": "user", "content": "first"},
+                    {"role": "assistant", "content": "First."},
+                    {"role": "user", "content": "second /"},
+                ],
+                "temperature": 0.2,
+                "max_tokens": 64,
+                "stop": ["\n"],
+                "stream": false,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_rules() {
+        let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            br#"[{"id": "brackets", "pattern": " \\[.*\\]", "replacement": ""}]"#,
+        )
+        .unwrap();
+        let post_rules = RuleEngine::with_options(
+            &[file.path().to_str().unwrap().to_string()],
+            Default::default(),
+        )
+        .unwrap();
+
+        let mut upstream = client(
+            mock_server(Duration::ZERO).await,
+            Duration::from_secs(5),
+            None,
+        );
+        upstream.post_rules = Some(Arc::new(post_rules));
+        assert_eq!(
+            upstream.process(&chat(&[], "a"), "m").await.as_deref(),
+            Some("A")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upstream_failure() {
+        // Timeout
+        let base_url = mock_server(Duration::from_secs(2)).await;
+        let upstream = client(base_url, Duration::from_millis(100), None);
+        assert_eq!(upstream.process(&chat(&[], "a"), "m").await, None);
+
+        // Nothing listening
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        drop(listener);
+        let upstream = client(base_url, Duration::from_secs(5), None);
+        assert_eq!(upstream.process(&chat(&[], "a"), "m").await, None);
+    }
+}
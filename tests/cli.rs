@@ -0,0 +1,42 @@
+//! Tests of the `handy-rules` command line
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Write a config with a single rule replacing "slash" with "/"
+fn write_config(dir: &Path) -> String {
+    let rules = dir.join("rules.json");
+    fs::write(
+        &rules,
+        r#"[{"id": "slash", "pattern": "\\bslash\\b", "replacement": "/"}]"#,
+    )
+    .unwrap();
+
+    let config = dir.join("config.json");
+    fs::write(
+        &config,
+        serde_json::json!({ "rules_paths": [rules] }).to_string(),
+    )
+    .unwrap();
+    config.display().to_string()
+}
+
+#[test]
+fn test_transform_file_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(dir.path());
+    let file = dir.path().join("notes.txt");
+    fs::write(&file, "a slash b\nc slash d\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_handy-rules"))
+        .args(["-c", &config, "transform", "-i"])
+        .arg(&file)
+        .arg("-o")
+        .arg(&file)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "a / b\nc / d\n");
+}